@@ -3,11 +3,20 @@ use serde::{Serialize, Deserialize};
 use std::future::Future;
 use futures::Stream;
 use std::pin::Pin;
+use std::marker::PhantomData;
+use crate::MiniModalError;
+use crate::function_call::FunctionCall;
 
 // New trait to encapsulate common requirements
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be sent to or from a remote function",
+    note = "arguments and outputs of a #[function] must be Serialize + Deserialize + Send + Sync + Debug + 'static"
+)]
 pub trait BaseBound: Serialize + for<'de> Deserialize<'de> + Send + Sync + Debug + 'static {}
 
-// Implement CommonBounds for all types that meet the requirements
+// Implement CommonBounds for all types that meet the requirements,
+// errors name `BaseBound` instead of the first missing bound
+#[diagnostic::do_not_recommend]
 impl<T> BaseBound for T
 where
     T: Serialize + for<'de> Deserialize<'de> + Send + Sync + Debug + 'static
@@ -20,9 +29,46 @@ where
 {
     type LocalOutput: Future<Output = O> + Send;
     type RemoteOutput: Future<Output = O> + Send;
+    type SpawnOutput: Future<Output = Result<FunctionCall<O>, MiniModalError>> + Send;
     
     fn local(input: I) -> Self::LocalOutput;
     fn remote(input: I) -> Self::RemoteOutput;
+    /// submits the call and returns once the server started it
+    fn spawn(input: I) -> Self::SpawnOutput;
+}
+
+/// The arguments of a `#[function]`, collected from its original parameter list
+/// by the generated `call`, e.g. `multi_arg::call(1, 2).remote()`
+pub struct Call<F, I, O> {
+    input: I,
+    function: PhantomData<fn() -> (F, O)>,
+}
+
+impl<F, I, O> Call<F, I, O>
+where
+    F: Function<I, O>,
+    I: BaseBound,
+    O: BaseBound,
+{
+    pub fn new(input: I) -> Self {
+        Call { input, function: PhantomData }
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    pub fn local(self) -> F::LocalOutput {
+        F::local(self.input)
+    }
+
+    pub fn remote(self) -> F::RemoteOutput {
+        F::remote(self.input)
+    }
+
+    pub fn spawn(self) -> F::SpawnOutput {
+        F::spawn(self.input)
+    }
 }
 
 pub trait BatchFunction<I, O>: Function<I, O>
@@ -58,4 +104,53 @@ impl<T: Send + Sync + 'static> LocalResult<T> for T {
     fn into_future(self) -> Box<dyn Future<Output = T> + Send + Sync> {
         Box::new(std::future::ready(self))
     }
-}
\ No newline at end of file
+}
+
+/// Implemented by the only output type a `#[function]` may have
+#[diagnostic::on_unimplemented(
+    message = "#[function] must return `Result<_, MiniModalError>`, found `{Self}`",
+)]
+pub trait MiniModalResult {
+    type Ok;
+
+    fn from_ok(value: Self::Ok) -> Self;
+    fn from_error(error: MiniModalError) -> Self;
+}
+
+impl<T> MiniModalResult for Result<T, MiniModalError> {
+    type Ok = T;
+
+    fn from_ok(value: T) -> Self {
+        Ok(value)
+    }
+
+    fn from_error(error: MiniModalError) -> Self {
+        Err(error)
+    }
+}
+
+/// The outputs a finished job can be decoded into, `message` is the
+/// serialized value or the error of the function
+pub trait RemoteResult: MiniModalResult {
+    fn from_message(success: bool, message: String) -> Self;
+}
+
+impl<T: BaseBound> RemoteResult for Result<T, MiniModalError> {
+    fn from_message(success: bool, message: String) -> Self {
+        if !success {
+            return Err(MiniModalError::FunctionError(message));
+        }
+        serde_json::from_str(&message)
+            .map_err(|e| MiniModalError::SerializationError(e.to_string()))
+    }
+}
+
+/// used by the `#[function]` macro to check each argument at the attribute
+pub fn assert_base_bound<T: BaseBound>() {}
+
+/// used by the `#[function]` macro to check the output at the attribute
+pub fn assert_minimodal_result<T>()
+where
+    T: MiniModalResult + BaseBound,
+    T::Ok: BaseBound,
+{}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use minimodal_proto::proto::minimodal::{
+    GetResultRequest,
+    GetResultResponse,
+};
+use crate::MiniModalError;
+use crate::session::Session;
+use crate::function::{BaseBound, RemoteResult};
+
+/// The server waits at most this long in one `GetResult` call
+const MAX_WAIT_PER_CALL: Duration = Duration::from_secs(60);
+
+/// the statuses of a job which has not finished
+const PENDING: [&str; 3] = ["queued", "building", "running"];
+
+/// A call started with `spawn`, running on the server as the job `job_id`.
+/// The handle only holds the job id, so it can be stored or sent elsewhere
+/// and the result fetched later, as long as the server still keeps it.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct FunctionCall<O> {
+    job_id: String,
+    #[serde(skip)]
+    output: PhantomData<fn() -> O>,
+}
+
+impl<O> Clone for FunctionCall<O> {
+    fn clone(&self) -> Self {
+        FunctionCall::new(self.job_id.clone())
+    }
+}
+
+impl<O> fmt::Debug for FunctionCall<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionCall").field("job_id", &self.job_id).finish()
+    }
+}
+
+impl<O> PartialEq for FunctionCall<O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.job_id == other.job_id
+    }
+}
+
+impl<O> FunctionCall<O> {
+    pub fn new(job_id: String) -> Self {
+        FunctionCall { job_id, output: PhantomData }
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+}
+
+impl<O> FunctionCall<O>
+where
+    O: RemoteResult + BaseBound,
+{
+    async fn get_result(&self, timeout: Duration) -> Result<GetResultResponse, MiniModalError> {
+        let mut client = Session::load()?.connect().await?;
+        let request = GetResultRequest {
+            job_id: self.job_id.clone(),
+            timeout_ms: timeout.as_millis() as u64,
+        };
+        client.get_result(request)
+            .await
+            .map(|response| response.into_inner())
+            .map_err(|e| MiniModalError::ServerError(e.message().to_string()))
+    }
+
+    /// the output of the function once the job finished
+    fn output(&self, response: GetResultResponse) -> Option<O> {
+        match (response.status.as_str(), response.result) {
+            (status, _) if PENDING.contains(&status) => None,
+            (_, Some(result)) => Some(O::from_message(result.success, result.message)),
+            (status, None) => Some(O::from_error(MiniModalError::FunctionError(
+                format!("job {} was {}", self.job_id, status)
+            ))),
+        }
+    }
+
+    /// the output if the job finished, `None` while it is running.
+    /// Errors are about reaching the server or finding the job,
+    /// the errors of the function are in the output
+    pub async fn poll(&self) -> Result<Option<O>, MiniModalError> {
+        let response = self.get_result(Duration::ZERO).await?;
+        Ok(self.output(response))
+    }
+
+    /// waits up to `timeout` for the job to finish, a job still running
+    /// then gives a `TimeoutError`
+    pub async fn get(&self, timeout: Duration) -> O {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let response = match self.get_result(remaining.min(MAX_WAIT_PER_CALL)).await {
+                Ok(response) => response,
+                Err(e) => return O::from_error(e),
+            };
+            if let Some(output) = self.output(response) {
+                return output;
+            }
+            if remaining.is_zero() {
+                return O::from_error(MiniModalError::TimeoutError(
+                    format!("job {} is still running after {:?}", self.job_id, timeout)
+                ));
+            }
+        }
+    }
+}
@@ -1,5 +1,13 @@
 pub mod function;
+pub mod registry;
+pub mod function_call;
+pub mod trace_context;
+pub mod session;
 pub use function::{Function, BatchFunction, StreamingFunction};
+pub use function_call::FunctionCall;
+pub use session::Session;
+// the generated code logs and traces through it
+pub use tracing;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -10,6 +18,8 @@ pub enum MiniModalError {
     OtherError(String),
     ConnectionError(String),
     SerializationError(String),
+    /// a spawned call did not finish in time
+    TimeoutError(String),
 }
 
 impl Display for MiniModalError {
@@ -0,0 +1,65 @@
+// used by the code generated by `#[function]`
+pub use inventory;
+
+/// An argument as declared in the signature
+#[derive(Debug)]
+pub struct ArgInfo {
+    /// the name on the wire, `arg{i}` for arguments bound to a pattern
+    pub name: &'static str,
+    /// the type as written in the signature
+    pub ty: &'static str,
+    /// the owned type the argument is sent as
+    pub wire_type: &'static str,
+}
+
+/// The options given to the attribute, e.g. `#[function(secrets = ["db"])]`
+#[derive(Debug)]
+pub struct FunctionOptions {
+    pub secrets: &'static [&'static str],
+    pub profile: Option<&'static str>,
+    pub features: &'static [&'static str],
+    pub rustflags: Option<&'static str>,
+    /// the deployed app the function is called in
+    pub app: Option<&'static str>,
+    pub schedule: Option<&'static str>,
+    pub period: Option<&'static str>,
+    pub web_endpoint: bool,
+}
+
+/// A `#[function]` of the program, submitted to the link-time registry
+/// by the macro so functions can be listed without knowing them in advance
+#[derive(Debug)]
+pub struct FunctionInfo {
+    pub name: &'static str,
+    /// `module_path!()` where the function is defined, starting with the crate name
+    pub module_path: &'static str,
+    /// `CARGO_PKG_NAME` of the package defining the function
+    pub package: &'static str,
+    pub args: &'static [ArgInfo],
+    /// the output type as written in the signature
+    pub output_type: &'static str,
+    pub is_async: bool,
+    /// the function has type or const parameters, the types of its
+    /// arguments are only known at the call site
+    pub is_generic: bool,
+    pub options: FunctionOptions,
+}
+
+impl FunctionInfo {
+    /// the path of the function, e.g. `my_crate::jobs::train`
+    pub fn path(&self) -> String {
+        format!("{}::{}", self.module_path, self.name)
+    }
+}
+
+inventory::collect!(FunctionInfo);
+
+/// every `#[function]` linked into the program
+pub fn functions() -> impl Iterator<Item = &'static FunctionInfo> {
+    inventory::iter::<FunctionInfo>.into_iter()
+}
+
+/// looks up a function by its path, see `FunctionInfo::path`
+pub fn find_function(path: &str) -> Option<&'static FunctionInfo> {
+    functions().find(|function| function.path() == path)
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::path::PathBuf;
+use serde::Deserialize;
+use tonic::{Request, Status};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::{Interceptor, interceptor::InterceptedService};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+use minimodal_proto::proto::minimodal::mini_modal_client::MiniModalClient;
+use crate::MiniModalError;
+
+/// the server of a session without an address configured
+pub const DEFAULT_ADDR: &str = "http://[::1]:50051";
+
+/// environment variables overriding the config file
+pub const ADDR_ENV: &str = "MINIMODAL_ADDR";
+pub const TOKEN_ENV: &str = "MINIMODAL_TOKEN";
+pub const CA_CERT_ENV: &str = "MINIMODAL_CA_CERT";
+pub const CLIENT_CERT_ENV: &str = "MINIMODAL_CLIENT_CERT";
+pub const CLIENT_KEY_ENV: &str = "MINIMODAL_CLIENT_KEY";
+/// the path of the config file, `~/.minimodal/config.toml` by default
+pub const CONFIG_ENV: &str = "MINIMODAL_CONFIG";
+
+/// A client of the server, every request carries the token of its session
+pub type Client = MiniModalClient<InterceptedService<Channel, Authorization>>;
+
+/// The contents of the config file, e.g.
+/// ```toml
+/// addr = "http://[::1]:50051"
+/// token = "..."
+/// ca_cert = "certs/ca.pem"
+/// client_cert = "certs/client.pem"
+/// client_key = "certs/client.key"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    addr: Option<String>,
+    token: Option<String>,
+    ca_cert: Option<PathBuf>,
+    client_cert: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+}
+
+/// The certificates of an `https://` session, all in PEM
+#[derive(Debug, Clone, Default)]
+pub struct ClientTls {
+    /// the CA which signed the certificate of the server, the system's roots without
+    pub ca_cert: Option<PathBuf>,
+    /// the certificate and key the client presents to a server requiring mutual TLS
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl ClientTls {
+    fn config(&self) -> Result<ClientTlsConfig, MiniModalError> {
+        let read = |path: &PathBuf| std::fs::read(path)
+            .map_err(|e| MiniModalError::ConnectionError(format!("failed to read {}: {}", path.display(), e)));
+        let config = match &self.ca_cert {
+            Some(ca_cert) => ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read(ca_cert)?)),
+            None => ClientTlsConfig::new().with_native_roots(),
+        };
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => Ok(config.identity(Identity::from_pem(read(cert)?, read(key)?))),
+            (None, None) => Ok(config),
+            _ => Err(MiniModalError::ConnectionError("a client certificate needs its key, and a key its certificate".to_string())),
+        }
+    }
+}
+
+/// The server to call and the token to call it with
+#[derive(Clone)]
+pub struct Session {
+    addr: String,
+    token: Option<String>,
+    tls: ClientTls,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("addr", &self.addr)
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("tls", &self.tls)
+            .finish()
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    match std::env::var_os(CONFIG_ENV) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".minimodal").join("config.toml")),
+    }
+}
+
+fn read_config() -> Result<Config, MiniModalError> {
+    let path = match config_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(Config::default()),
+    };
+    let content = std::fs::read_to_string(&path)?;
+    toml::from_str(&content)
+        .map_err(|e| MiniModalError::OtherError(format!("invalid config {}: {}", path.display(), e)))
+}
+
+/// an empty variable counts as unset
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+impl Session {
+    pub fn new(addr: impl Into<String>, token: Option<String>) -> Session {
+        Session { addr: addr.into(), token, tls: ClientTls::default() }
+    }
+
+    /// the session of the environment: `MINIMODAL_ADDR`, `MINIMODAL_TOKEN` and the
+    /// certificates of `MINIMODAL_CA_CERT`, `MINIMODAL_CLIENT_CERT` and `MINIMODAL_CLIENT_KEY`,
+    /// else the config file, else the local server without a token
+    pub fn load() -> Result<Session, MiniModalError> {
+        let config = read_config()?;
+        Ok(Session {
+            addr: env(ADDR_ENV).or(config.addr).unwrap_or_else(|| DEFAULT_ADDR.to_string()),
+            token: env(TOKEN_ENV).or(config.token),
+            tls: ClientTls {
+                ca_cert: env(CA_CERT_ENV).map(PathBuf::from).or(config.ca_cert),
+                client_cert: env(CLIENT_CERT_ENV).map(PathBuf::from).or(config.client_cert),
+                client_key: env(CLIENT_KEY_ENV).map(PathBuf::from).or(config.client_key),
+            },
+        })
+    }
+
+    /// the certificates of an `https://` address
+    pub fn with_tls(self, tls: ClientTls) -> Session {
+        Session { tls, ..self }
+    }
+
+    /// replaces the address and the token which are given
+    pub fn with_overrides(self, addr: Option<String>, token: Option<String>) -> Session {
+        Session {
+            addr: addr.unwrap_or(self.addr),
+            token: token.or(self.token),
+            tls: self.tls,
+        }
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub async fn connect(&self) -> Result<Client, MiniModalError> {
+        let authorization = match &self.token {
+            Some(token) => Some(format!("Bearer {}", token).parse()
+                .map_err(|_| MiniModalError::OtherError("the token is not a valid header value".to_string()))?),
+            None => None,
+        };
+        let mut endpoint = Channel::from_shared(self.addr.clone())
+            .map_err(|e| MiniModalError::ConnectionError(format!("invalid address {}: {}", self.addr, e)))?;
+        // the certificates are only used for https, http stays plain
+        if self.addr.starts_with("https://") {
+            endpoint = endpoint.tls_config(self.tls.config()?)
+                .map_err(|e| MiniModalError::ConnectionError(format!("invalid TLS config for {}: {}", self.addr, e)))?;
+        }
+        let channel = endpoint
+            .connect()
+            .await
+            .map_err(|e| MiniModalError::ConnectionError(format!("failed to connect to {}: {}", self.addr, e)))?;
+        Ok(MiniModalClient::with_interceptor(channel, Authorization(authorization)))
+    }
+}
+
+/// Adds `authorization: Bearer <token>` to the requests of a client
+#[derive(Clone)]
+pub struct Authorization(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for Authorization {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(authorization) = &self.0 {
+            request.metadata_mut().insert("authorization", authorization.clone());
+        }
+        Ok(request)
+    }
+}
@@ -0,0 +1,81 @@
+use opentelemetry::Context;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use tonic::Request;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// the gRPC metadata key carrying the trace context of a call
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// the environment variable carrying the trace context into a function
+pub const TRACEPARENT_ENV: &str = "TRACEPARENT";
+
+/// The W3C `traceparent` of a span context,
+/// e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+pub fn format(span_context: &SpanContext) -> String {
+    format!(
+        "00-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags().to_u8(),
+    )
+}
+
+/// the remote span context of a `traceparent`, `None` when it is malformed
+pub fn parse(traceparent: &str) -> Option<SpanContext> {
+    let parts: Vec<&str> = traceparent.trim().split('-').collect();
+    let [version, trace_id, span_id, flags] = parts.as_slice() else {
+        return None;
+    };
+    let is_hex = |part: &str, len: usize| part.len() == len && part.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex(version, 2) || *version == "ff" || !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+    let span_context = SpanContext::new(
+        TraceId::from_hex(trace_id).ok()?,
+        SpanId::from_hex(span_id).ok()?,
+        TraceFlags::new(u8::from_str_radix(flags, 16).ok()?),
+        true,
+        TraceState::default(),
+    );
+    span_context.is_valid().then_some(span_context)
+}
+
+/// the `traceparent` of `span`, `None` unless it is recorded
+/// by an OpenTelemetry layer of the subscriber
+pub fn traceparent(span: &Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+    span_context.is_valid().then(|| format(&span_context))
+}
+
+/// the `traceparent` of the current span, or the one the function
+/// was started with, so the calls it makes join the trace of its caller
+pub fn current() -> Option<String> {
+    traceparent(&Span::current()).or_else(|| {
+        std::env::var(TRACEPARENT_ENV).ok().filter(|traceparent| parse(traceparent).is_some())
+    })
+}
+
+/// adds the current trace context to the metadata of a request
+pub fn inject<T>(request: &mut Request<T>) {
+    if let Some(value) = current().and_then(|traceparent| traceparent.parse().ok()) {
+        request.metadata_mut().insert(TRACEPARENT_HEADER, value);
+    }
+}
+
+/// the trace context a request was sent with
+pub fn extract<T>(request: &Request<T>) -> Option<String> {
+    request.metadata()
+        .get(TRACEPARENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|traceparent| parse(traceparent).is_some())
+        .map(|traceparent| traceparent.to_string())
+}
+
+/// makes `span` a child of the span a `traceparent` comes from
+pub fn set_parent(span: &Span, traceparent: &str) {
+    if let Some(span_context) = parse(traceparent) {
+        span.set_parent(Context::new().with_remote_span_context(span_context));
+    }
+}
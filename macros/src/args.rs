@@ -1,4 +1,5 @@
-use syn::{parse::Parse, parse::ParseStream, Expr, Ident, Token};
+use syn::spanned::Spanned;
+use syn::{parse::Parse, parse::ParseStream, Expr, Ident, Token, LitStr};
 use darling::FromMeta;
 use proc_macro::TokenStream;
 
@@ -6,6 +7,28 @@ use proc_macro::TokenStream;
 pub struct MacroArgs {
     #[darling(default)]
     pub debug: bool,
+    /// names of server side secrets injected as environment variables
+    #[darling(default)]
+    pub secrets: Vec<LitStr>,
+    /// cargo profile used for the server side build
+    #[darling(default)]
+    pub profile: Option<LitStr>,
+    #[darling(default)]
+    pub features: Vec<LitStr>,
+    #[darling(default)]
+    pub rustflags: Option<LitStr>,
+    /// run the function deployed under this app instead of the mounted project
+    #[darling(default)]
+    pub app: Option<LitStr>,
+    /// cron expression the server runs the deployed function on, e.g. "0 3 * * *"
+    #[darling(default)]
+    pub schedule: Option<LitStr>,
+    /// interval the server runs the deployed function at, e.g. "15m"
+    #[darling(default)]
+    pub period: Option<LitStr>,
+    /// exposed as `POST /apps/<app>/<function>` once deployed
+    #[darling(default)]
+    pub web_endpoint: bool,
 }
 
 impl MacroArgs {
@@ -14,6 +37,35 @@ impl MacroArgs {
             .map_err(|e| syn::Error::new(proc_macro2::Span::call_site(), e))?;
         
         Self::from_list(&args)
-            .map_err(|e| syn::Error::new(proc_macro2::Span::call_site(), e))
+            .map_err(|e| syn::Error::new(e.span(), e))
+    }
+
+    /// scheduled functions are run by the server, which has no arguments to give,
+    /// and deployed functions are called with arguments inferred from JSON
+    pub fn validate_deployment_options(&self, sig: &syn::Signature) -> syn::Result<()> {
+        if let (Some(_), Some(period)) = (&self.schedule, &self.period) {
+            return Err(syn::Error::new(period.span(), "#[function] takes either a `schedule` or a `period`, not both"));
+        }
+        if let Some(schedule) = self.schedule.as_ref().or(self.period.as_ref()) {
+            if !sig.inputs.is_empty() {
+                return Err(syn::Error::new(schedule.span(), "scheduled functions can't take arguments"));
+            }
+        }
+        if self.web_endpoint {
+            if let Some(param) = sig.generics.type_params().next() {
+                return Err(syn::Error::new(param.span(), "web endpoints can't be generic"));
+            }
+            if let Some(param) = sig.generics.const_params().next() {
+                return Err(syn::Error::new(param.span(), "web endpoints can't be generic"));
+            }
+            let borrowed = sig.inputs.iter().find_map(|input| match input {
+                syn::FnArg::Typed(pat_ty) if matches!(*pat_ty.ty, syn::Type::Reference(_) | syn::Type::ImplTrait(_)) => Some(&pat_ty.ty),
+                _ => None,
+            });
+            if let Some(ty) = borrowed {
+                return Err(syn::Error::new(ty.span(), "web endpoints take their arguments by value, use an owned type"));
+            }
+        }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::Visibility;
+use crate::macro_builder::MacroBuilder;
+
+/// inherent methods taking the original parameter list,
+/// so callers don't have to build the input tuple
+pub fn impl_call_methods(
+    vis: &Visibility,
+    macro_builder: &MacroBuilder,
+) -> TokenStream2 {
+
+    let MacroBuilder {
+        fn_name,
+        generics,
+        new_inp_type,
+        output_type,
+        params,
+        into_wire,
+        lifetimes,
+        impl_where_clause,
+        ..
+    } = macro_builder;
+
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let (method_generics, _, method_where_clause) = lifetimes.split_for_impl();
+    let function = quote! { <Self as Function<#new_inp_type, #output_type>> };
+
+    // borrowed arguments are cloned into their wire types,
+    // changes to `&mut` arguments are not seen by the caller
+    quote! {
+        impl #impl_generics #fn_name #ty_generics #impl_where_clause {
+            #vis fn call #method_generics (#(#params),*) -> basemodules::function::Call<Self, #new_inp_type, #output_type> #method_where_clause {
+                basemodules::function::Call::new((#(#into_wire),*))
+            }
+
+            #vis fn local_args #method_generics (#(#params),*) -> #function::LocalOutput #method_where_clause {
+                #function::local((#(#into_wire),*))
+            }
+
+            #vis fn remote_args #method_generics (#(#params),*) -> #function::RemoteOutput #method_where_clause {
+                #function::remote((#(#into_wire),*))
+            }
+
+            #vis fn spawn_args #method_generics (#(#params),*) -> #function::SpawnOutput #method_where_clause {
+                #function::spawn((#(#into_wire),*))
+            }
+        }
+    }
+}
@@ -5,16 +5,35 @@ use crate::macro_builder::MacroBuilder;
 use crate::stream_trait::impl_stream_trait;
 use crate::map_trait::impl_map_trait;
 use crate::function_trait::impl_function_trait;
+use crate::call_methods::impl_call_methods;
+use crate::registry::register_function;
+use crate::args::MacroArgs;
 /// the core logic in the "function" macro
 /// 
 /// it takes a function and its attributes.
-pub fn function_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn function_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let macro_args = match MacroArgs::parse(attr) {
+        Ok(macro_args) => macro_args,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let item_fn = parse_macro_input!(item as ItemFn);   
     
     let vis = &item_fn.vis.clone();
     let is_async = item_fn.sig.asyncness.is_some();
 
-    let macro_builder = MacroBuilder::new(item_fn);
+    if let Err(e) = macro_args.validate_deployment_options(&item_fn.sig) {
+        let errors = e.to_compile_error();
+        return quote! { #errors #item_fn }.into();
+    }
+
+    let macro_builder = match MacroBuilder::new(item_fn.clone()) {
+        Ok(macro_builder) => macro_builder,
+        // keep the function so the errors are not buried under unresolved names
+        Err(e) => {
+            let errors = e.to_compile_error();
+            return quote! { #errors #item_fn }.into();
+        }
+    };
 
     let cloned_generics = macro_builder.generics.clone();
     // phantom fields for generic types unused by the struct
@@ -30,7 +49,10 @@ pub fn function_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let stream_trait = impl_stream_trait(&macro_builder);
     let map_trait = impl_map_trait(&macro_builder);
 
-    let function_trait = impl_function_trait(is_async, &macro_builder);
+    let function_trait = impl_function_trait(is_async, &macro_builder, &macro_args);
+    let call_methods = impl_call_methods(vis, &macro_builder);
+    let registration = register_function(is_async, &macro_builder, &macro_args);
+    let assertions = macro_builder.assertions();
 
     let MacroBuilder {
         fn_name, 
@@ -39,13 +61,20 @@ pub fn function_impl(_attr: TokenStream, item: TokenStream) -> TokenStream {
         ..
     } = macro_builder;
 
+    // the struct only lives in the type namespace,
+    // so the original function keeps its name
     quote! {
+        #item_fn
+
         #vis struct #fn_name #generics #where_clause {
             #(#phantom_fields)*
         }
 
+        #assertions
         #function_trait
+        #call_methods
         #stream_trait
+        #registration
         #map_trait
 
     }.into()
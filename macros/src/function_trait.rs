@@ -2,6 +2,7 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, format_ident};
 use syn::Ident;
 use crate::macro_builder::MacroBuilder;
+use crate::args::MacroArgs;
 
 fn generate_local_impl(
     is_async: bool,
@@ -12,32 +13,34 @@ fn generate_local_impl(
         new_inp_type, 
         output_type, 
         input_idents, 
-        block,
+        input_bindings,
+        call_args,
+        fn_path,
         ..
     } = macro_builder;
     
     let new_input_ident = generate_new_input_ident(&input_idents);
+    // sync functions are called when the future is first polled
+    let await_call = is_async.then(|| quote! { .await });
     
-    // Add this block to check the return type
-
-    if is_async {
-        quote! {
-            type LocalOutput = Pin<Box<dyn Future<Output = #output_type> + Send + 'static>>;
-            fn local(#new_input_ident: #new_inp_type) -> Self::LocalOutput {
-                Box::pin(async move { let (#(#input_idents),*) = #new_input_ident; #block })
-            }
-        }
-    } else {
-        quote! {
-            type LocalOutput = #output_type;
-            fn local(#new_input_ident: #new_inp_type) -> Self::LocalOutput {
-                let (#(#input_idents),*) = #new_input_ident; #block
-            }
+    // the original function is called with the wire values,
+    // borrowed again where it takes references
+    quote! {
+        type LocalOutput = Pin<Box<dyn Future<Output = #output_type> + Send + 'static>>;
+        fn local(#new_input_ident: #new_inp_type) -> Self::LocalOutput {
+            Box::pin(async move { 
+                let (#(#input_bindings),*) = #new_input_ident; 
+                #fn_path(#(#call_args),*) #await_call
+            })
         }
     }
 }
 
 fn generate_new_input_ident(input_idents: &Vec<Ident>) -> Ident {
+    // functions without arguments take `()`
+    if input_idents.is_empty() {
+        return format_ident!("input");
+    }
     format_ident!(
         "{}", 
         input_idents.iter()
@@ -48,7 +51,9 @@ fn generate_new_input_ident(input_idents: &Vec<Ident>) -> Ident {
 }
 
 fn generate_remote_impl(
+    is_async: bool,
     macro_builder: &MacroBuilder,
+    macro_args: &MacroArgs,
 ) -> TokenStream2 {
 
     let new_input_ident = generate_new_input_ident(&macro_builder.input_idents);
@@ -59,28 +64,37 @@ fn generate_remote_impl(
         output_type, 
         input_idents, 
         types_and_names,
+        generic_args,
         .. 
     } = macro_builder;
 
-    let remote_block_body = quote! {
+    let secrets = macro_args.secrets.iter().map(|secret| secret.value());
+    let profile = macro_args.profile.as_ref().map(|profile| profile.value()).unwrap_or_default();
+    let features = macro_args.features.iter().map(|feature| feature.value());
+    let rustflags = macro_args.rustflags.as_ref().map(|rustflags| rustflags.value()).unwrap_or_default();
+    let app = macro_args.app.as_ref().map(|app| app.value()).unwrap_or_default();
+
+    // connects, mounts the project unless the function is deployed
+    // and builds the `request` for the call, which carries the trace
+    // context of the current span
+    let request_block = quote! {
         use basemodules::MiniModalError;
-        use minimodal_proto::proto::minimodal::{
-            mini_modal_client::MiniModalClient, 
-            run_function_response::Response, 
-            RunFunctionResponse,
-            RunFunctionRequest
-        };
-        use tonic::{Request, Status, Streaming};
+        use minimodal_proto::proto::minimodal::RunFunctionRequest;
+        use tonic::{Request, Status};
         use serde_json;
         use minimodal_rs::utilities::serialize_inputs;
         use minimodal_rs::mount::mount_project;
-        use minimodal_proto::proto::minimodal::NameAndType;
+        use minimodal_proto::proto::minimodal::{generic_arg, GenericArg, NameAndType, BuildOptions};
 
-        let mut client = MiniModalClient::connect("http://[::1]:50051").await?;
-        
-        mount_project(&mut client, vec![".git".to_string()])
-            .await
-            .map_err(|e| MiniModalError::from(anyhow::Error::from(e)))?;
+        // the server and token of the environment or the config file
+        let mut client = basemodules::session::Session::load()?.connect().await?;
+
+        // a deployed app is already built on the server
+        if #app.is_empty() {
+            mount_project(&mut client, &minimodal_rs::mount_rules::MountRules::default(), Some(env!("CARGO_PKG_NAME")))
+                .await
+                .map_err(|e| MiniModalError::from(anyhow::Error::from(e)))?;
+        }
 
         let serialized_inputs = serialize_inputs(
             &[#(stringify!(#input_idents)),*], 
@@ -88,33 +102,55 @@ fn generate_remote_impl(
         )?;
             
         
-        let request = Request::new(RunFunctionRequest {
+        let mut request = Request::new(RunFunctionRequest {
             function_id: stringify!(#fn_name).to_string(),
             serialized_inputs : serialized_inputs,
             field_types: vec![#(#types_and_names),*],
-            output_type: stringify!(#output_type).to_string()
+            output_type: std::any::type_name::<#output_type>().to_string(),
+            secrets: vec![#(#secrets.to_string()),*],
+            build_options: Some(BuildOptions {
+                profile: #profile.to_string(),
+                features: vec![#(#features.to_string()),*],
+                rustflags: #rustflags.to_string(),
+            }),
+            package: env!("CARGO_PKG_NAME").to_string(),
+            crate_name: env!("CARGO_CRATE_NAME").to_string(),
+            bin_name: option_env!("CARGO_BIN_NAME").unwrap_or_default().to_string(),
+            module_path: module_path!().to_string(),
+            generic_args: vec![#(GenericArg { arg: Some(#generic_args) }),*],
+            is_sync: !#is_async,
+            app: #app.to_string(),
         });
+        basemodules::trace_context::inject(&mut request);
+    };
+
+    let remote_block_body = quote! {
+        #request_block
+        use minimodal_proto::proto::minimodal::{run_function_response::Response, RunFunctionResponse};
+        use tonic::Streaming;
 
         let mut response_stream : Streaming<RunFunctionResponse> = 
             client.run_function(request).await
             .map_err(|e| MiniModalError::from(anyhow::Error::from(e)))?
             .into_inner();
 
-        println!("response_stream: {:?}", response_stream);
-
         while let Some(response) = response_stream.next().await {
             let response = response.map_err(|e| MiniModalError::from(anyhow::Error::from(e)))?;
             match response.response {
                 Some(Response::Result(task_result)) => {
-                    if task_result.success {
-                        return serde_json::from_str(&task_result.message)
-                            .map_err(|e| MiniModalError::SerializationError(e.to_string()))
-                    } else {
-                        return Err(MiniModalError::FunctionError(task_result.message));
-                    }
+                    return Ok(basemodules::function::RemoteResult::from_message(
+                        task_result.success,
+                        task_result.message,
+                    ));
                 }
                 Some(Response::LogLine(line)) => {
-                    println!("log: {}", line);
+                    basemodules::tracing::info!("{}", line);
+                }
+                Some(Response::JobId(job_id)) => {
+                    basemodules::tracing::debug!(%job_id, "started");
+                }
+                Some(Response::Job(job)) => {
+                    basemodules::tracing::debug!(job_id = %job.id, status = %job.status, "job");
                 }
                 None => {
                     return Err(MiniModalError::OtherError("No result received".to_string()));
@@ -124,13 +160,39 @@ fn generate_remote_impl(
         Err(MiniModalError::OtherError("Stream ended without result".to_string()))
     };
 
+    // the result stays on the server, the handle fetches it later
+    let spawn_block_body = quote! {
+        #request_block
+
+        let response = client.submit_function(request).await
+            .map_err(|e| MiniModalError::from(anyhow::Error::from(e)))?
+            .into_inner();
+        Ok(basemodules::function_call::FunctionCall::new(response.job_id))
+    };
+
+    // the span is a child of the caller's current span
+    // and the parent of the job on the server
     quote! {
         type RemoteOutput = Pin<Box<dyn Future<Output = #output_type> + Send + 'static>>;
         fn remote(#new_input_ident: #new_inp_type) -> Self::RemoteOutput {
+            use basemodules::tracing::Instrument;
+            let span = basemodules::tracing::info_span!("remote", function = stringify!(#fn_name));
             Box::pin(async move { 
                 let (#(#input_idents),*) = #new_input_ident; 
-                #remote_block_body
-            })
+                // errors reaching the server end up in the output as well
+                let result: Result<#output_type, MiniModalError> = async move { #remote_block_body }.await;
+                result.unwrap_or_else(basemodules::function::MiniModalResult::from_error)
+            }.instrument(span))
+        }
+
+        type SpawnOutput = Pin<Box<dyn Future<Output = Result<basemodules::function_call::FunctionCall<#output_type>, basemodules::MiniModalError>> + Send + 'static>>;
+        fn spawn(#new_input_ident: #new_inp_type) -> Self::SpawnOutput {
+            use basemodules::tracing::Instrument;
+            let span = basemodules::tracing::info_span!("spawn", function = stringify!(#fn_name));
+            Box::pin(async move { 
+                let (#(#input_idents),*) = #new_input_ident; 
+                #spawn_block_body
+            }.instrument(span))
         }
     }
 }
@@ -138,22 +200,24 @@ fn generate_remote_impl(
 pub fn impl_function_trait(
     is_async: bool,
     macro_builder: &MacroBuilder,
+    macro_args: &MacroArgs,
 ) -> TokenStream2 {
 
     let MacroBuilder {
         fn_name, 
         generics, 
-        where_clause, 
+        impl_where_clause, 
         new_inp_type, 
         output_type, 
         ..
     } = macro_builder;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
 
-    let remote_impl = generate_remote_impl(&macro_builder);
+    let remote_impl = generate_remote_impl(is_async, &macro_builder, macro_args);
     let local_impl = generate_local_impl(is_async, &macro_builder);
 
     quote! {
-        impl #generics Function<#new_inp_type, #output_type> for #fn_name #generics #where_clause {
+        impl #impl_generics Function<#new_inp_type, #output_type> for #fn_name #ty_generics #impl_where_clause {
             #local_impl
             #remote_impl
         }
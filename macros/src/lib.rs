@@ -4,8 +4,11 @@ mod utils;
 mod args;
 mod stream_trait;
 mod map_trait;
+mod call_methods;
+mod registry;
 mod core_function_impl;
 mod macro_builder;
+mod wire_type;
 
 #[proc_macro_attribute]
 pub fn function(_args: TokenStream, input: TokenStream) -> TokenStream {
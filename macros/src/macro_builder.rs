@@ -1,73 +1,316 @@
 use syn::{
-    Generics, 
-    WhereClause, 
-    Type, 
-    Ident, 
-    Block, 
-    FnArg, 
-    ReturnType, 
+    Generics,
+    WhereClause,
+    Type,
+    Ident,
+    FnArg,
+    ReturnType,
     ItemFn,
-    parse_quote
+    Pat,
+    GenericParam,
+    TypeParamBound,
+    WherePredicate,
+    Lifetime,
+    parse_quote,
+    spanned::Spanned,
 };
 
 use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned, format_ident, ToTokens};
+use crate::wire_type::{wire_type, Borrow};
 pub struct MacroBuilder {
     pub fn_name: Ident,
     pub generics: Generics,
     pub where_clause: Option<WhereClause>,
+    /// the where clause of the generated impls, they only hold once the
+    /// assertions pass, so a bad argument or output is reported once
+    pub impl_where_clause: WhereClause,
     pub new_inp_type: Box<Type>,
     pub output_type: Box<Type>,
+    /// the names of the arguments on the wire,
+    /// `arg{i}` for arguments bound to a pattern
     pub input_idents: Vec<Ident>,
-    pub block: Block,
+    /// the bindings of the wire values in `local`, e.g. `mut x`
+    pub input_bindings: Vec<TokenStream>,
+    /// the owned types the arguments are sent as
+    pub input_types: Vec<Type>,
+    /// the types of the arguments as written in the signature
+    pub declared_types: Vec<Type>,
+    /// the wire values passed to the original function, e.g. `&x`
+    pub call_args: Vec<TokenStream>,
+    /// the original function, instantiated with the generic parameters
+    pub fn_path: TokenStream,
+    /// the original parameter list, with patterns bound to their wire name
+    pub params: Vec<TokenStream>,
+    /// converts each parameter into its wire type
+    pub into_wire: Vec<TokenStream>,
+    /// the lifetimes of the original function, taken by the methods
+    /// with the original parameter list
+    pub lifetimes: Generics,
     pub types_and_names: Vec<TokenStream>,
+    /// the concrete generic arguments at the monomorphized call site
+    pub generic_args: Vec<TokenStream>,
 }
 
-impl MacroBuilder {
-    pub fn new(item: ItemFn) -> Self {
-        let ItemFn { sig, block, .. } = item;
-
-        let input_args: Vec<(Ident, Type)> = sig.inputs.iter()
-            .filter_map(|arg| match arg {
-                FnArg::Typed(pat_ty) => {
-                    if let syn::Pat::Ident(pat_ident) = &*pat_ty.pat {
-                        Some((pat_ident.ident.clone(), (*pat_ty.ty).clone()))
-                    } else {
-                        None
-                    }
-                }
-                _ => None
+/// The generated struct and impls only take the type and const parameters
+/// of the function, lifetimes only appear in the borrowed arguments.
+fn strip_lifetimes(generics: &Generics) -> Generics {
+    let lifetimes: Vec<Lifetime> = generics.lifetimes().map(|param| param.lifetime.clone()).collect();
+    // bounds like `'static` stay
+    let keep_bound = |bound: &TypeParamBound| match bound {
+        TypeParamBound::Lifetime(lifetime) => !lifetimes.contains(lifetime),
+        _ => true,
+    };
+
+    let mut generics = generics.clone();
+    generics.params = generics.params.clone().into_iter()
+        .filter(|param| !matches!(param, GenericParam::Lifetime(_)))
+        .map(|mut param| {
+            if let GenericParam::Type(type_param) = &mut param {
+                type_param.bounds = type_param.bounds.clone().into_iter().filter(keep_bound).collect();
+            }
+            param
+        })
+        .collect();
+    if let Some(where_clause) = &mut generics.where_clause {
+        where_clause.predicates = where_clause.predicates.clone().into_iter()
+            .filter_map(|predicate| match predicate {
+                WherePredicate::Lifetime(_) => None,
+                WherePredicate::Type(mut predicate) => {
+                    predicate.bounds = predicate.bounds.into_iter().filter(keep_bound).collect();
+                    (!predicate.bounds.is_empty()).then(|| WherePredicate::Type(predicate))
+                },
+                predicate => Some(predicate),
             })
             .collect();
+    }
+    generics
+}
+
+/// the counterpart of `strip_lifetimes`
+fn only_lifetimes(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    generics.params = generics.params.clone().into_iter()
+        .filter(|param| matches!(param, GenericParam::Lifetime(_)))
+        .collect();
+    if let Some(where_clause) = &mut generics.where_clause {
+        where_clause.predicates = where_clause.predicates.clone().into_iter()
+            .filter(|predicate| matches!(predicate, WherePredicate::Lifetime(_)))
+            .collect();
+    }
+    generics
+}
+
+/// collects every error of the signature instead of stopping at the first one
+fn combine_errors(errors: Vec<syn::Error>) -> syn::Result<()> {
+    match errors.into_iter().reduce(|mut combined, error| { combined.combine(error); combined }) {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+impl MacroBuilder {
+    pub fn new(item: ItemFn) -> syn::Result<Self> {
+        let ItemFn { sig, .. } = item;
+
+        let mut errors = Vec::new();
+
+        if let Some(unsafety) = &sig.unsafety {
+            errors.push(syn::Error::new(unsafety.span(), "#[function] does not support unsafe functions"));
+        }
+        if let Some(abi) = &sig.abi {
+            errors.push(syn::Error::new_spanned(abi, "#[function] does not support extern functions"));
+        }
+        if let Some(variadic) = &sig.variadic {
+            errors.push(syn::Error::new(variadic.span(), "#[function] does not support variadic arguments"));
+        }
+        struct InputArg {
+            ident: Ident,
+            binding: TokenStream,
+            ty: Type,
+            call_arg: TokenStream,
+            borrow: Borrow,
+            annotate: bool,
+            param: TokenStream,
+            into_wire: TokenStream,
+            declared_type: Type,
+        }
+
+        let mut input_args: Vec<InputArg> = Vec::new();
+        for (i, arg) in sig.inputs.iter().enumerate() {
+            let pat_ty = match arg {
+                FnArg::Receiver(receiver) => {
+                    errors.push(syn::Error::new_spanned(
+                        receiver,
+                        "#[function] can't be used on methods, remove the `self` argument"
+                    ));
+                    continue;
+                },
+                FnArg::Typed(pat_ty) => pat_ty,
+            };
+            // patterns are destructured by the original function
+            let ident = match &*pat_ty.pat {
+                Pat::Ident(pat_ident) if pat_ident.by_ref.is_none() && pat_ident.subpat.is_none() => pat_ident.ident.clone(),
+                _ => format_ident!("arg{}", i),
+            };
+            let wire = match wire_type(&pat_ty.ty, &ident) {
+                Ok(wire) => wire,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                },
+            };
+            let ty = &pat_ty.ty;
+            let mutability = (wire.borrow == Borrow::Mut).then(|| quote! { mut });
+            let borrow = wire.borrow.tokens();
+            input_args.push(InputArg {
+                binding: quote! { #mutability #ident },
+                call_arg: quote! { #borrow #ident },
+                param: quote! { #ident: #ty },
+                into_wire: wire.into_wire,
+                declared_type: (**ty).clone(),
+                ident,
+                ty: wire.ty,
+                borrow: wire.borrow,
+                annotate: wire.annotate,
+            });
+        }
+
+        let output_type = match sig.output {
+            ReturnType::Type(_, ty) => Some(ty),
+            ReturnType::Default => {
+                errors.push(syn::Error::new(
+                    sig.paren_token.span.close(),
+                    "#[function] must return `Result<_, MiniModalError>`"
+                ));
+                None
+            },
+        };
+
+        combine_errors(errors)?;
+        let output_type = output_type.expect("missing output type is reported above");
 
-        let (input_idents, input_types): (Vec<_>, Vec<_>) = input_args.iter().cloned().unzip();
+        let input_idents: Vec<Ident> = input_args.iter().map(|arg| arg.ident.clone()).collect();
+        let input_bindings: Vec<TokenStream> = input_args.iter().map(|arg| arg.binding.clone()).collect();
+        let input_types: Vec<Type> = input_args.iter().map(|arg| arg.ty.clone()).collect();
+        let declared_types: Vec<Type> = input_args.iter().map(|arg| arg.declared_type.clone()).collect();
+        let call_args: Vec<TokenStream> = input_args.iter().map(|arg| arg.call_arg.clone()).collect();
+        let params: Vec<TokenStream> = input_args.iter().map(|arg| arg.param.clone()).collect();
+        let into_wire: Vec<TokenStream> = input_args.iter().map(|arg| arg.into_wire.clone()).collect();
 
         let types_and_names : Vec<TokenStream> = input_args
             .iter()
-            .map(
-                |(name, ty)| 
-                quote::quote! { NameAndType { name: stringify!(#name).to_string(), ty: stringify!(#ty).to_string() } }
-            )
+            .map(|InputArg { ident, ty, borrow, annotate, .. }| {
+                let borrow = borrow.prefix();
+                quote! {
+                    NameAndType {
+                        name: stringify!(#ident).to_string(),
+                        ty: std::any::type_name::<#ty>().to_string(),
+                        borrow: #borrow.to_string(),
+                        annotate: #annotate,
+                    }
+                }
+            })
             .collect::<Vec<_>>();
 
-        let new_inp_type = parse_quote!((#(#input_types),*)); 
-        // Create NameAndType structs for each input
-        let output_type = match sig.output {
-            ReturnType::Type(_, ty) => ty,
-            ReturnType::Default => panic!("Output type must be of type Result<_, MiniModalError>"),
+        // const values are sent as literals, `{:?}` quotes chars
+        let generic_args : Vec<TokenStream> = sig.generics.params
+            .iter()
+            .filter_map(|param| match param {
+                syn::GenericParam::Type(type_param) => {
+                    let ident = &type_param.ident;
+                    Some(quote! { generic_arg::Arg::TypeName(std::any::type_name::<#ident>().to_string()) })
+                },
+                syn::GenericParam::Const(const_param) => {
+                    let ident = &const_param.ident;
+                    Some(quote! { generic_arg::Arg::ConstValue(format!("{:?}", #ident)) })
+                },
+                // lifetimes are inferred at the call site
+                syn::GenericParam::Lifetime(_) => None,
+            })
+            .collect();
+
+        // lifetimes are left to inference, they can't be given
+        // explicitly when they are late bound
+        let fn_name = sig.ident;
+        let generic_params: Vec<TokenStream> = sig.generics.params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Type(type_param) => Some(type_param.ident.to_token_stream()),
+                GenericParam::Const(const_param) => Some(const_param.ident.to_token_stream()),
+                GenericParam::Lifetime(_) => None,
+            })
+            .collect();
+        let fn_path = if generic_params.is_empty() {
+            quote! { #fn_name }
+        } else {
+            quote! { #fn_name::<#(#generic_params),*> }
         };
 
-        Self {
-            fn_name: sig.ident,
-            where_clause: sig.generics.where_clause.clone(),
-            generics: sig.generics,
+        let new_inp_type = parse_quote!((#(#input_types),*));
+        let generics = strip_lifetimes(&sig.generics);
+        let lifetimes = only_lifetimes(&sig.generics);
+
+        // the bounds are behind a binder, so they are only checked
+        // where the impls are used and not repeated at the attribute
+        let mut impl_where_clause = generics.where_clause.clone()
+            .unwrap_or_else(|| parse_quote!(where));
+        impl_where_clause.predicates.push(parse_quote!(
+            for<'__minimodal> #new_inp_type: basemodules::function::BaseBound
+        ));
+        impl_where_clause.predicates.push(parse_quote!(
+            for<'__minimodal> #output_type: basemodules::function::RemoteResult + basemodules::function::BaseBound
+        ));
+
+        Ok(Self {
+            fn_name,
+            where_clause: generics.where_clause.clone(),
+            impl_where_clause,
+            generics,
             new_inp_type,
             output_type,
             input_idents,
-            block: *block,
+            input_bindings,
+            input_types,
+            declared_types,
+            call_args,
+            fn_path,
+            params,
+            into_wire,
+            lifetimes,
             types_and_names: types_and_names,
-        }
+            generic_args,
+        })
 
     }
 
+    /// Compile time checks that every argument and the output can be sent
+    /// over the wire, so misuse is reported on the offending type
+    /// instead of somewhere in the generated trait impls.
+    pub fn assertions(&self) -> TokenStream {
+        let MacroBuilder {
+            generics,
+            where_clause,
+            input_types,
+            output_type,
+            ..
+        } = self;
+
+        let input_assertions = input_types.iter().map(|ty| quote_spanned! {ty.span()=>
+            basemodules::function::assert_base_bound::<#ty>();
+        });
+        let output_assertion = quote_spanned! {output_type.span()=>
+            basemodules::function::assert_minimodal_result::<#output_type>();
+        };
 
-}
\ No newline at end of file
+        quote! {
+            const _: () = {
+                #[allow(dead_code)]
+                fn __minimodal_assertions #generics () #where_clause {
+                    #(#input_assertions)*
+                    #output_assertion
+                }
+            };
+        }
+    }
+}
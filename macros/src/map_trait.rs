@@ -41,16 +41,17 @@ pub fn impl_map_trait(
     let MacroBuilder {
         fn_name,
         generics,
-        where_clause,
+        impl_where_clause,
         new_inp_type,
         output_type,
         ..
     } = macro_builder;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
 
     let map_impl = generate_map_impl(new_inp_type, output_type);
     let map_async_impl = generate_map_async_impl(new_inp_type);
     quote!{
-        impl #generics BatchFunction<#new_inp_type, #output_type> for #fn_name #generics #where_clause {
+        impl #impl_generics BatchFunction<#new_inp_type, #output_type> for #fn_name #ty_generics #impl_where_clause {
             #map_impl
             #map_async_impl
         }
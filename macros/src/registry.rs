@@ -0,0 +1,69 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use crate::macro_builder::MacroBuilder;
+use crate::args::MacroArgs;
+
+fn optional(value: Option<String>) -> TokenStream2 {
+    match value {
+        Some(value) => quote! { Some(#value) },
+        None => quote! { None },
+    }
+}
+
+/// submits the metadata of the function to the link-time registry
+pub fn register_function(
+    is_async: bool,
+    macro_builder: &MacroBuilder,
+    macro_args: &MacroArgs,
+) -> TokenStream2 {
+
+    let MacroBuilder {
+        fn_name,
+        input_idents,
+        input_types,
+        declared_types,
+        output_type,
+        generic_args,
+        ..
+    } = macro_builder;
+
+    let is_generic = !generic_args.is_empty();
+    let secrets = macro_args.secrets.iter().map(|secret| secret.value());
+    let profile = optional(macro_args.profile.as_ref().map(|profile| profile.value()));
+    let features = macro_args.features.iter().map(|feature| feature.value());
+    let rustflags = optional(macro_args.rustflags.as_ref().map(|rustflags| rustflags.value()));
+    let app = optional(macro_args.app.as_ref().map(|app| app.value()));
+    let schedule = optional(macro_args.schedule.as_ref().map(|schedule| schedule.value()));
+    let period = optional(macro_args.period.as_ref().map(|period| period.value()));
+    let web_endpoint = macro_args.web_endpoint;
+
+    quote! {
+        basemodules::registry::inventory::submit! {
+            basemodules::registry::FunctionInfo {
+                name: stringify!(#fn_name),
+                module_path: module_path!(),
+                package: env!("CARGO_PKG_NAME"),
+                args: &[#(
+                    basemodules::registry::ArgInfo {
+                        name: stringify!(#input_idents),
+                        ty: stringify!(#declared_types),
+                        wire_type: stringify!(#input_types),
+                    }
+                ),*],
+                output_type: stringify!(#output_type),
+                is_async: #is_async,
+                is_generic: #is_generic,
+                options: basemodules::registry::FunctionOptions {
+                    secrets: &[#(#secrets),*],
+                    profile: #profile,
+                    features: &[#(#features),*],
+                    rustflags: #rustflags,
+                    app: #app,
+                    schedule: #schedule,
+                    period: #period,
+                    web_endpoint: #web_endpoint,
+                },
+            }
+        }
+    }
+}
@@ -9,14 +9,15 @@ pub fn impl_stream_trait(
     let MacroBuilder {
         fn_name,
         generics,
-        where_clause,
+        impl_where_clause,
         new_inp_type,
         output_type,
         ..
     } = macro_builder;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
 
     quote! {
-        impl #generics StreamingFunction<#new_inp_type, #output_type> for #fn_name #generics #where_clause {
+        impl #impl_generics StreamingFunction<#new_inp_type, #output_type> for #fn_name #ty_generics #impl_where_clause {
             type InputStream = Pin<Box<dyn Stream<Item = #new_inp_type> + Send>>;
             type OutputStream = Pin<Box<dyn Stream<Item = Self::RemoteOutput> + Send>>;
             fn map_stream(input: Self::InputStream) -> Self::OutputStream {
@@ -0,0 +1,155 @@
+use syn::{
+    Type,
+    Ident,
+    TypePath,
+    TypeParamBound,
+    PathArguments,
+    GenericArgument,
+    parse_quote,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// How the owned value sent over the wire is passed to the original function
+#[derive(Clone, Copy, PartialEq)]
+pub enum Borrow {
+    Value,
+    Shared,
+    Mut,
+}
+
+impl Borrow {
+    /// the prefix of the argument at the call, as sent to the server
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Borrow::Value => "",
+            Borrow::Shared => "&",
+            Borrow::Mut => "&mut ",
+        }
+    }
+
+    pub fn tokens(&self) -> TokenStream {
+        match self {
+            Borrow::Value => quote! {},
+            Borrow::Shared => quote! { & },
+            Borrow::Mut => quote! { &mut },
+        }
+    }
+}
+
+/// An argument is always sent as an owned value, `WireType` describes
+/// that value and how the original parameter is recovered from it.
+pub struct WireType {
+    pub ty: Type,
+    pub borrow: Borrow,
+    /// the parameter is a reference or `impl Trait`, so the wire type
+    /// can't be inferred from the call
+    pub annotate: bool,
+    /// converts the original parameter into the wire type
+    pub into_wire: TokenStream,
+}
+
+fn last_segment_is(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => path.segments.last()
+            .map_or(false, |segment| segment.ident == name && segment.arguments.is_none()),
+        _ => false,
+    }
+}
+
+/// the owned counterpart of a borrowed type, e.g. `str` to `String`.
+/// Any other type is sent as is and has to be `Clone` to be borrowed from
+fn owned_type(ty: &Type) -> Type {
+    match ty {
+        Type::Group(group) => owned_type(&group.elem),
+        Type::Paren(paren) => owned_type(&paren.elem),
+        Type::Slice(slice) => {
+            let elem = &slice.elem;
+            parse_quote!(std::vec::Vec<#elem>)
+        },
+        ty if last_segment_is(ty, "str") => parse_quote!(std::string::String),
+        ty if last_segment_is(ty, "Path") => parse_quote!(std::path::PathBuf),
+        ty if last_segment_is(ty, "OsStr") => parse_quote!(std::ffi::OsString),
+        ty if last_segment_is(ty, "CStr") => parse_quote!(std::ffi::CString),
+        ty => ty.clone(),
+    }
+}
+
+fn first_type_argument(arguments: &PathArguments) -> Option<&Type> {
+    match arguments {
+        PathArguments::AngleBracketed(arguments) => arguments.args.iter().find_map(|argument| match argument {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn associated_type<'a>(arguments: &'a PathArguments, name: &str) -> Option<&'a Type> {
+    match arguments {
+        PathArguments::AngleBracketed(arguments) => arguments.args.iter().find_map(|argument| match argument {
+            GenericArgument::AssocType(assoc) if assoc.ident == name => Some(&assoc.ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// an owned type implementing the bounds of an `impl Trait` argument,
+/// and the conversion of the argument into it
+fn impl_trait_wire_type<'a>(
+    bounds: impl Iterator<Item = &'a TypeParamBound>,
+    ident: &Ident,
+) -> Option<(Type, TokenStream)> {
+    bounds.filter_map(|bound| match bound {
+        TypeParamBound::Trait(trait_bound) => trait_bound.path.segments.last(),
+        _ => None,
+    })
+    .find_map(|segment| match segment.ident.to_string().as_str() {
+        "AsRef" => first_type_argument(&segment.arguments).map(|target| (
+            owned_type(target),
+            quote! { std::borrow::ToOwned::to_owned(std::convert::AsRef::<#target>::as_ref(&#ident)) },
+        )),
+        "Borrow" => first_type_argument(&segment.arguments).map(|target| (
+            owned_type(target),
+            quote! { std::borrow::ToOwned::to_owned(std::borrow::Borrow::<#target>::borrow(&#ident)) },
+        )),
+        "Into" => first_type_argument(&segment.arguments).map(|target| (
+            target.clone(),
+            quote! { std::convert::Into::<#target>::into(#ident) },
+        )),
+        "IntoIterator" => associated_type(&segment.arguments, "Item").map(|item| (
+            parse_quote!(std::vec::Vec<#item>),
+            quote! { std::iter::IntoIterator::into_iter(#ident).collect::<std::vec::Vec<#item>>() },
+        )),
+        "ToString" | "Display" => Some((
+            parse_quote!(std::string::String),
+            quote! { std::string::ToString::to_string(&#ident) },
+        )),
+        _ => None,
+    })
+}
+
+/// `ident` is the name the argument is bound to on the wire
+pub fn wire_type(ty: &Type, ident: &Ident) -> syn::Result<WireType> {
+    match ty {
+        Type::Group(group) => wire_type(&group.elem, ident),
+        Type::Paren(paren) => wire_type(&paren.elem, ident),
+        Type::Reference(reference) => Ok(WireType {
+            ty: owned_type(&reference.elem),
+            borrow: if reference.mutability.is_some() { Borrow::Mut } else { Borrow::Shared },
+            annotate: true,
+            into_wire: quote! { std::borrow::ToOwned::to_owned(&*#ident) },
+        }),
+        Type::ImplTrait(impl_trait) => match impl_trait_wire_type(impl_trait.bounds.iter(), ident) {
+            Some((ty, into_wire)) => Ok(WireType { ty, borrow: Borrow::Value, annotate: true, into_wire }),
+            None => Err(syn::Error::new_spanned(
+                impl_trait,
+                "#[function] sends arguments by value and only supports `impl Trait` arguments \
+                 bounded by AsRef<_>, Borrow<_>, Into<_>, IntoIterator<Item = _>, ToString or Display, \
+                 use a concrete type instead"
+            )),
+        },
+        ty => Ok(WireType { ty: ty.clone(), borrow: Borrow::Value, annotate: false, into_wire: quote! { #ident } }),
+    }
+}
@@ -1,4 +1,10 @@
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("../proto/minimodal.proto")?;
+    // the descriptors are served by the reflection service of the server
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("minimodal_descriptor.bin"))
+        .compile(&["../proto/minimodal.proto"], &["../proto"])?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,3 +1,6 @@
 pub mod minimodal {
     tonic::include_proto!("minimodal");
+
+    /// the encoded descriptors of minimodal.proto
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("minimodal_descriptor");
 }
\ No newline at end of file
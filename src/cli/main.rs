@@ -0,0 +1,527 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use anyhow::{Error, anyhow};
+use clap::{Args, Parser, Subcommand};
+use cargo_metadata::MetadataCommand;
+use minimodal_proto::proto::minimodal::{
+    run_function_response::Response as RunFunctionResult,
+    cancel_job_response::Result as CancelJobResult,
+    mount_project_response::Result as MountProjectResult,
+    ListJobsRequest,
+    GetJobRequest,
+    GetJobLogsRequest,
+    CancelJobRequest,
+    CollectGarbageRequest,
+    RollbackAppRequest,
+    ListAppsRequest,
+    ListScheduledRunsRequest,
+    GetResultRequest,
+    deploy_app_response::Result as DeployAppResult,
+    rollback_app_response::Result as RollbackAppResult,
+};
+use minimodal_rs::mount::{plan_mount, mount_project, deploy_app, mount_targets, select_package, MountTarget};
+use minimodal_rs::mount_rules::MountRules;
+use minimodal_rs::invoke::{locate_function, build_request};
+use minimodal_rs::server::server::{serve, ServeOptions};
+use minimodal_rs::server::schedule::parse_period;
+use minimodal_rs::server::telemetry;
+use minimodal_rs::server::tls::{self, ServerTls};
+use minimodal_rs::server::mounts::MountQuotas;
+use basemodules::trace_context;
+use basemodules::MiniModalError;
+use basemodules::session::{Client, Session};
+use tracing::info;
+
+/// the statuses of a job which has not finished
+const PENDING: [&str; 3] = ["queued", "building", "running"];
+
+/// Operate a minimodal server from the command line
+#[derive(Parser)]
+#[command(name = "minimodal")]
+struct Cli {
+    /// address of the server, defaults to MINIMODAL_ADDR, the config file or http://[::1]:50051
+    #[arg(long, global = true)]
+    addr: Option<String>,
+    /// token of the requests, defaults to MINIMODAL_TOKEN or the config file
+    #[arg(long, global = true)]
+    token: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Which files are mounted, besides `.gitignore`, `.minimodalignore` and `[package.metadata.minimodal]`
+#[derive(Args)]
+struct MountArgs {
+    /// paths relative to the workspace root which are not mounted
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// globs of files mounted even when ignored, excluded or large, e.g. 'data/*.csv'
+    #[arg(long)]
+    include: Vec<String>,
+    /// skip larger files, in bytes, 4MiB by default
+    #[arg(long)]
+    max_file_size: Option<u64>,
+}
+
+impl From<MountArgs> for MountRules {
+    fn from(args: MountArgs) -> MountRules {
+        MountRules { exclude: args.exclude, include: args.include, max_file_bytes: args.max_file_size }
+    }
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum Command {
+    /// start a server
+    Serve {
+        #[arg(long, default_value = "[::1]:50051")]
+        listen: String,
+        /// where mounted projects are built
+        #[arg(long, default_value = "src/server/shadow_dir")]
+        dirname: String,
+        #[arg(long, default_value = "src/server/secrets")]
+        secrets_dir: String,
+        /// where deployed apps are kept
+        #[arg(long, default_value = "src/server/deployments")]
+        deployments_dir: String,
+        /// also serve the web endpoints of deployed functions, e.g. [::1]:8080
+        #[arg(long)]
+        http: Option<String>,
+        /// how long the results of submitted calls are kept, e.g. 30m or 7d
+        #[arg(long, default_value = "1d")]
+        result_retention: String,
+        /// serve Prometheus metrics on /metrics, e.g. [::1]:9090
+        #[arg(long)]
+        metrics: Option<String>,
+        /// export traces to an OTLP collector, e.g. http://localhost:4317
+        #[arg(long)]
+        otlp_endpoint: Option<String>,
+        /// accept only requests with a token of this file, see the README
+        #[arg(long)]
+        tokens_file: Option<String>,
+        /// serve over TLS with this certificate (PEM), needs --tls-key
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// require client certificates signed by this CA (PEM), i.e. mutual TLS
+        #[arg(long, requires = "tls_cert")]
+        tls_client_ca: Option<PathBuf>,
+        /// how long the jobs may run on after SIGTERM or SIGINT before they are cancelled
+        #[arg(long, default_value = "30s")]
+        shutdown_timeout: String,
+        /// the most files a mount or deployment may have, 10000 by default
+        #[arg(long)]
+        max_mount_files: Option<usize>,
+        /// the largest file of a mount in bytes, 16MiB by default
+        #[arg(long)]
+        max_mount_file_bytes: Option<usize>,
+        /// the largest mount in bytes, 64MiB by default
+        #[arg(long)]
+        max_mount_bytes: Option<usize>,
+    },
+    /// write a self-signed CA with a server and a client certificate, for development
+    TlsDevCa {
+        /// where the certificates and keys are written
+        #[arg(long, default_value = "certs")]
+        dir: PathBuf,
+        /// further host names or addresses of the server, besides localhost
+        #[arg(long)]
+        name: Vec<String>,
+    },
+    /// mount the project in the current directory
+    Mount {
+        /// list the files which would be mounted instead
+        #[arg(long)]
+        dry_run: bool,
+        /// the workspace member to mount, defaults to the root package
+        #[arg(long, short)]
+        package: Option<String>,
+        #[command(flatten)]
+        rules: MountArgs,
+    },
+    /// mount the project and run one of its functions
+    Run {
+        /// the path of the function, e.g. jobs::train
+        function: String,
+        /// the arguments, as a JSON object keyed by name or an array
+        #[arg(long, short, default_value = "null")]
+        input: String,
+        #[arg(long, short)]
+        package: Option<String>,
+        /// the binary defining the function, defaults to the library
+        #[arg(long)]
+        bin: Option<String>,
+        /// run the function deployed under this app instead of mounting
+        #[arg(long)]
+        app: Option<String>,
+        /// submit the call and print its job id instead of waiting for the result
+        #[arg(long)]
+        detach: bool,
+    },
+    /// deploy the project in the current directory as a new version of an app
+    Deploy {
+        app: String,
+        #[arg(long, short)]
+        package: Option<String>,
+        #[command(flatten)]
+        rules: MountArgs,
+    },
+    /// activate a previous version of an app
+    Rollback {
+        app: String,
+        /// defaults to the version before the active one
+        #[arg(long)]
+        version: Option<u32>,
+    },
+    /// list the deployed apps and their versions
+    Apps,
+    /// list the runs of scheduled functions, the most recent first
+    Runs {
+        #[arg(long)]
+        app: Option<String>,
+        /// e.g. my_crate::jobs::nightly
+        #[arg(long)]
+        function: Option<String>,
+        #[arg(long, default_value = "20")]
+        limit: u32,
+        /// print the logs of each run
+        #[arg(long)]
+        logs: bool,
+    },
+    /// list the jobs of the server
+    Ps,
+    /// print the status, origin and timestamps of a job
+    Job {
+        job: String,
+    },
+    /// print the logs of a job
+    Logs {
+        job: String,
+    },
+    /// print the result of a job, waiting for it to finish
+    Result {
+        job: String,
+        /// seconds to wait, 0 answers at once
+        #[arg(long, default_value = "60")]
+        wait: u64,
+    },
+    /// cancel a running job
+    Cancel {
+        job: String,
+    },
+    /// forget finished jobs and remove leftover executables
+    Gc,
+}
+
+/// the message of an error loading or connecting a session
+fn session_error(e: MiniModalError) -> Error {
+    match e {
+        MiniModalError::ConnectionError(message) | MiniModalError::OtherError(message) => anyhow!(message),
+        e => anyhow!("{}", e),
+    }
+}
+
+async fn connect(session: &Session) -> Result<Client, Error> {
+    session.connect().await.map_err(session_error)
+}
+
+/// the target defining the function, the library unless `bin` is given
+fn select_target(targets: &[MountTarget], bin: Option<&str>) -> Result<MountTarget, Error> {
+    let target = match bin {
+        Some(bin) => targets.iter().find(|target| target.is_bin() && target.crate_name == bin.replace('-', "_")),
+        None => targets.iter()
+            .find(|target| target.kind == "lib")
+            .or_else(|| targets.iter().find(|target| target.is_bin())),
+    };
+    target.cloned().ok_or(anyhow!("no such target, available: {:?}", targets.iter().map(|target| &target.crate_name).collect::<Vec<_>>()))
+}
+
+async fn run(
+    session: &Session,
+    function: &str,
+    input: &str,
+    package: Option<&str>,
+    bin: Option<&str>,
+    app: Option<&str>,
+    detach: bool,
+) -> Result<(), Error> {
+    let input: serde_json::Value = serde_json::from_str(input)
+        .map_err(|e| anyhow!("--input is not valid JSON: {}", e))?;
+
+    let metadata = MetadataCommand::new().exec()?;
+    let workspace_root: PathBuf = metadata.workspace_root.clone().into();
+    let package = select_package(&metadata, package)?;
+    let targets = mount_targets(package, &workspace_root)?;
+    let target = select_target(&targets, bin)?;
+    let other_roots: Vec<PathBuf> = targets.iter()
+        .filter(|other| other.root != target.root)
+        .map(|other| workspace_root.join(&other.root))
+        .collect();
+
+    let remote_function = locate_function(&workspace_root, &target, &other_roots, function)?;
+    let mut request = build_request(&target, &remote_function, input)?;
+
+    let mut client = connect(session).await?;
+    match app {
+        Some(app) => request.app = app.to_string(),
+        None => {
+            mount_project(&mut client, &MountRules::default(), Some(&package.name)).await?;
+        },
+    }
+
+    // a TRACEPARENT in the environment makes the call part of that trace
+    let mut request = tonic::Request::new(request);
+    trace_context::inject(&mut request);
+
+    if detach {
+        println!("{}", client.submit_function(request).await?.into_inner().job_id);
+        return Ok(());
+    }
+
+    let mut stream = client.run_function(request).await?.into_inner();
+    while let Some(response) = stream.message().await? {
+        match response.response {
+            Some(RunFunctionResult::JobId(job_id)) => eprintln!("job: {}", job_id),
+            Some(RunFunctionResult::Job(job)) => eprintln!("job {} is {}", job.id, job.status),
+            Some(RunFunctionResult::LogLine(line)) => eprintln!("{}", line),
+            Some(RunFunctionResult::Result(result)) if result.success => {
+                println!("{}", result.message);
+                return Ok(());
+            },
+            Some(RunFunctionResult::Result(result)) => return Err(anyhow!("{}", result.message)),
+            None => {},
+        }
+    }
+    Err(anyhow!("the server closed the stream without a result"))
+}
+
+async fn execute(cli: Cli) -> Result<(), Error> {
+    let session = Session::load().map_err(session_error)?.with_overrides(cli.addr, cli.token);
+    match cli.command {
+        Command::Serve { listen, dirname, secrets_dir, deployments_dir, http, result_retention, metrics, otlp_endpoint, tokens_file, tls_cert, tls_key, tls_client_ca, shutdown_timeout, max_mount_files, max_mount_file_bytes, max_mount_bytes } => {
+            telemetry::init(otlp_endpoint.as_deref())?;
+            if let Some(otlp_endpoint) = &otlp_endpoint {
+                info!("📡 Exporting traces to {}", otlp_endpoint);
+            }
+            info!("🎬 Starting up minimodal server on {}", listen);
+            let http = match http {
+                Some(http) => {
+                    info!("🌐 Web endpoints on http://{}", http);
+                    Some(http.parse()?)
+                },
+                None => None,
+            };
+            let metrics = match metrics {
+                Some(metrics) => {
+                    info!("📈 Metrics on http://{}/metrics", metrics);
+                    Some(metrics.parse()?)
+                },
+                None => None,
+            };
+            if let Some(tokens_file) = &tokens_file {
+                info!("🔑 Tokens file: {}", tokens_file);
+            }
+            let default_quotas = MountQuotas::default();
+            let options = ServeOptions {
+                http_addr: http,
+                metrics_addr: metrics,
+                result_retention: parse_period(&result_retention)?,
+                tokens_file,
+                tls: match (tls_cert, tls_key) {
+                    (Some(cert), Some(key)) => Some(ServerTls { cert, key, client_ca: tls_client_ca }),
+                    _ => None,
+                },
+                shutdown_timeout: parse_period(&shutdown_timeout)?,
+                mount_quotas: MountQuotas {
+                    max_files: max_mount_files.unwrap_or(default_quotas.max_files),
+                    max_file_bytes: max_mount_file_bytes.unwrap_or(default_quotas.max_file_bytes),
+                    max_total_bytes: max_mount_bytes.unwrap_or(default_quotas.max_total_bytes),
+                },
+            };
+            serve(listen.parse()?, dirname, secrets_dir, deployments_dir, options)
+                .await
+                .map_err(|e| anyhow!(e))?;
+        },
+        Command::TlsDevCa { dir, name } => {
+            let files = tls::generate_dev_ca(&dir, &name)?;
+            println!("CA certificate      {}", files.ca_cert.display());
+            println!("server certificate  {}  key {}", files.server_cert.display(), files.server_key.display());
+            println!("client certificate  {}  key {}", files.client_cert.display(), files.client_key.display());
+            println!();
+            println!("minimodal serve --tls-cert {} --tls-key {} --tls-client-ca {}",
+                files.server_cert.display(), files.server_key.display(), files.ca_cert.display());
+            println!("MINIMODAL_ADDR=https://localhost:50051 MINIMODAL_CA_CERT={} MINIMODAL_CLIENT_CERT={} MINIMODAL_CLIENT_KEY={} minimodal ps",
+                files.ca_cert.display(), files.client_cert.display(), files.client_key.display());
+        },
+        Command::Mount { dry_run: true, package, rules } => {
+            let plan = plan_mount(&rules.into(), package.as_deref())?;
+            let mut files: Vec<(&String, &Vec<u8>)> = plan.files.iter().collect();
+            files.sort();
+            for (file_path, content) in files.iter() {
+                let reason = plan.reasons.get(*file_path).map(|reason| reason.to_string()).unwrap_or_default();
+                println!("{:>10}  {}  ({})", content.len(), file_path, reason);
+            }
+            let mut symlinks: Vec<(&String, &String)> = plan.symlinks.iter().collect();
+            symlinks.sort();
+            for (file_path, target) in symlinks.iter() {
+                let reason = plan.reasons.get(*file_path).map(|reason| reason.to_string()).unwrap_or_default();
+                println!("{:>10}  {} -> {}  ({})", "", file_path, target, reason);
+            }
+            for file in plan.skipped.iter() {
+                println!("{:>10}  {}  (skipped, {})", file.size, file.path, file.reason);
+            }
+            println!("{} files, {} symlinks, {} bytes, {} skipped", files.len(), symlinks.len(),
+                files.iter().map(|(_, content)| content.len()).sum::<usize>(), plan.skipped.len());
+        },
+        Command::Mount { dry_run: false, package, rules } => {
+            let mut client = connect(&session).await?;
+            match mount_project(&mut client, &rules.into(), package.as_deref()).await?.result {
+                Some(MountProjectResult::Success(message)) => println!("{}", message),
+                Some(MountProjectResult::Error(message)) => return Err(anyhow!(message)),
+                None => return Err(anyhow!("the server sent no mount result")),
+            }
+        },
+        Command::Run { function, input, package, bin, app, detach } => {
+            run(&session, &function, &input, package.as_deref(), bin.as_deref(), app.as_deref(), detach).await?;
+        },
+        Command::Deploy { app, package, rules } => {
+            let mut client = connect(&session).await?;
+            match deploy_app(&mut client, &app, &rules.into(), package.as_deref()).await?.result {
+                Some(DeployAppResult::Success(version)) => {
+                    println!("Deployed version {} of {}", version.version, app);
+                    for function in version.functions.iter() {
+                        println!("  {}", function);
+                    }
+                    for endpoint in version.web_endpoints.iter() {
+                        println!("web endpoint POST /apps/{}/{}", app, endpoint);
+                    }
+                    for schedule in version.schedules.iter() {
+                        println!("scheduled {}", schedule);
+                    }
+                    for skipped in version.skipped.iter() {
+                        eprintln!("skipped {}", skipped);
+                    }
+                },
+                Some(DeployAppResult::Error(message)) => return Err(anyhow!(message)),
+                None => return Err(anyhow!("the server sent no deploy result")),
+            }
+        },
+        Command::Rollback { app, version } => {
+            let request = RollbackAppRequest { app, version: version.unwrap_or(0) };
+            match connect(&session).await?.rollback_app(request).await?.into_inner().result {
+                Some(RollbackAppResult::Success(message)) => println!("{}", message),
+                Some(RollbackAppResult::Error(message)) => return Err(anyhow!(message)),
+                None => return Err(anyhow!("the server sent no result")),
+            }
+        },
+        Command::Runs { app, function, limit, logs } => {
+            let request = ListScheduledRunsRequest {
+                app: app.unwrap_or_default(),
+                function: function.unwrap_or_default(),
+                limit,
+            };
+            let runs = connect(&session).await?.list_scheduled_runs(request).await?.into_inner().runs;
+            println!("{:<12}  {:<9}  {:<20}  {:<7}  FUNCTION", "SCHEDULED", "STATUS", "APP", "VERSION");
+            for run in runs {
+                println!("{:<12}  {:<9}  {:<20}  {:<7}  {}", run.scheduled_at, run.status, run.app, run.version, run.function);
+                if logs {
+                    for line in run.logs.iter() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+        },
+        Command::Apps => {
+            let apps = connect(&session).await?.list_apps(ListAppsRequest {}).await?.into_inner().apps;
+            println!("{:<20}  {:<7}  {:<10}  FUNCTIONS", "APP", "VERSION", "CREATED");
+            for app in apps {
+                for version in app.versions.iter() {
+                    let marker = if version.version == app.active_version { "*" } else { " " };
+                    println!(
+                        "{:<20}  {:<7}  {:<10}  {}",
+                        app.name,
+                        format!("{}{}", version.version, marker),
+                        version.created_at,
+                        version.functions.join(", "),
+                    );
+                }
+            }
+        },
+        Command::Ps => {
+            let jobs = connect(&session).await?.list_jobs(ListJobsRequest {}).await?.into_inner().jobs;
+            println!("{:<36}  {:<9}  {:<10}  {:<21}  FUNCTION", "ID", "STATUS", "CREATED", "CALLER");
+            for job in jobs {
+                println!("{:<36}  {:<9}  {:<10}  {:<21}  {}", job.id, job.status, job.created_at, job.caller, job.function);
+            }
+        },
+        Command::Job { job } => {
+            let job = connect(&session).await?
+                .get_job(GetJobRequest { job_id: job })
+                .await?
+                .into_inner()
+                .job
+                .ok_or(anyhow!("the server sent no job"))?;
+            println!("id:          {}", job.id);
+            println!("function:    {}", job.function);
+            println!("status:      {}", job.status);
+            println!("mount:       {}", job.mount_id);
+            println!("caller:      {}", job.caller);
+            println!("created at:  {}", job.created_at);
+            println!("started at:  {}", job.started_at);
+            println!("finished at: {}", job.finished_at);
+        },
+        Command::Logs { job } => {
+            let lines = connect(&session).await?
+                .get_job_logs(GetJobLogsRequest { job_id: job })
+                .await?
+                .into_inner()
+                .lines;
+            for line in lines {
+                println!("{}", line);
+            }
+        },
+        Command::Result { job, wait } => {
+            let mut client = connect(&session).await?;
+            // the server answers a long wait in several calls
+            let deadline = Instant::now() + Duration::from_secs(wait);
+            let response = loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let request = GetResultRequest { job_id: job.clone(), timeout_ms: remaining.as_millis() as u64 };
+                let response = client.get_result(request).await?.into_inner();
+                if !PENDING.contains(&response.status.as_str()) || remaining.is_zero() {
+                    break response;
+                }
+            };
+            match response.result {
+                Some(result) if result.success => println!("{}", result.message),
+                Some(result) => return Err(anyhow!("{}", result.message)),
+                None => return Err(anyhow!("job {} is {}", job, response.status)),
+            }
+        },
+        Command::Cancel { job } => {
+            let response = connect(&session).await?.cancel_job(CancelJobRequest { job_id: job }).await?.into_inner();
+            match response.result {
+                Some(CancelJobResult::Success(message)) => println!("{}", message),
+                Some(CancelJobResult::Error(message)) => return Err(anyhow!(message)),
+                None => return Err(anyhow!("the server sent no result")),
+            }
+        },
+        Command::Gc => {
+            let response = connect(&session).await?.collect_garbage(CollectGarbageRequest {}).await?.into_inner();
+            println!("forgot {} finished jobs", response.jobs.len());
+            for file in response.files.iter() {
+                println!("removed {}", file);
+            }
+        },
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = execute(Cli::parse()).await {
+        eprintln!("🔥 Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Error, anyhow};
+use ignore::WalkBuilder;
+use serde_json::{Map, Value};
+use minimodal_proto::proto::minimodal::{RunFunctionRequest, NameAndType};
+use crate::mount::MountTarget;
+use crate::parse_file::{find_remote_functions, RemoteFunction};
+
+/// the module path of a source file relative to the directory of the crate
+/// root, e.g. src/jobs/train.rs for src/lib.rs is `jobs::train`
+pub fn module_path_of(file: &Path, root: &Path) -> Option<Vec<String>> {
+    if file == root {
+        return Some(Vec::new());
+    }
+    let relative = file.strip_prefix(root.parent()?).ok()?;
+    let mut segments: Vec<String> = relative.with_extension("")
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if segments.last().map(String::as_str) == Some("mod") {
+        segments.pop();
+    }
+    Some(segments)
+}
+
+/// every `#[function]` of `target`, with their modules relative to the crate
+/// root. Files under the crate root's directory are mapped to modules by
+/// their path, `other_roots` are the roots of the other targets sharing it
+pub fn remote_functions(
+    workspace_root: &Path,
+    target: &MountTarget,
+    other_roots: &[PathBuf],
+) -> Result<Vec<RemoteFunction>, Error> {
+    let root = workspace_root.join(&target.root);
+    let root_dir = root.parent().ok_or(anyhow!("crate root {} has no parent", root.display()))?;
+    let walker = WalkBuilder::new(root_dir).build();
+
+    let mut remote_functions = Vec::new();
+    for entry in walker.filter_map(Result::ok) {
+        let file = entry.path();
+        if file.extension().is_none_or(|extension| extension != "rs") || other_roots.iter().any(|other| other == file) {
+            continue;
+        }
+        let module_path = match module_path_of(file, &root) {
+            Some(module_path) => module_path,
+            None => continue,
+        };
+        let content = fs::read_to_string(file)?;
+        let ast = match syn::parse_file(&content) {
+            Ok(ast) => ast,
+            Err(_) => continue,
+        };
+        let functions = find_remote_functions(&ast, &["function".to_string()])
+            .map_err(|e| anyhow!("Failed to read #[function] in {}: {}", file.display(), e))?;
+        for mut function in functions {
+            // the modules of the file come before the inline modules
+            function.modules = module_path.iter().chain(function.modules.iter()).cloned().collect();
+            remote_functions.push(function);
+        }
+    }
+    Ok(remote_functions)
+}
+
+/// finds the `#[function]` called `path` in `target`, the path is relative
+/// to the crate root and may start with the crate name or `crate`
+pub fn locate_function(
+    workspace_root: &Path,
+    target: &MountTarget,
+    other_roots: &[PathBuf],
+    path: &str
+) -> Result<RemoteFunction, Error> {
+    let mut wanted: Vec<&str> = path.split("::").collect();
+    if wanted.len() > 1 && (wanted[0] == "crate" || wanted[0] == target.crate_name) {
+        wanted.remove(0);
+    }
+    let wanted = wanted.join("::");
+
+    remote_functions(workspace_root, target, other_roots)?
+        .into_iter()
+        .find(|function| function.path() == wanted)
+        .ok_or(anyhow!("no #[function] {} in {} {}", path, target.kind, target.crate_name))
+}
+
+/// the inputs of the function `name` keyed by argument name, given either
+/// as an object keyed by argument name or as an array of the arguments in order
+pub fn named_inputs(name: &str, args: &[String], input: Value) -> Result<Map<String, Value>, Error> {
+    match input {
+        Value::Null if args.is_empty() => Ok(Map::new()),
+        Value::Array(values) => {
+            if values.len() != args.len() {
+                return Err(anyhow!("{} takes {} arguments, got {}", name, args.len(), values.len()));
+            }
+            Ok(args.iter().cloned().zip(values).collect())
+        },
+        Value::Object(values) => {
+            if let Some(missing) = args.iter().find(|arg| !values.contains_key(*arg)) {
+                return Err(anyhow!("missing argument {} of {}", missing, name));
+            }
+            if let Some(unknown) = values.keys().find(|key| !args.contains(key)) {
+                return Err(anyhow!("{} has no argument {}", name, unknown));
+            }
+            Ok(values)
+        },
+        _ => Err(anyhow!("the input of {} must be a JSON object or array of its arguments", name)),
+    }
+}
+
+/// the request running `function` of `target` with `input`, as the
+/// `#[function]` macro would send it
+pub fn build_request(target: &MountTarget, function: &RemoteFunction, input: Value) -> Result<RunFunctionRequest, Error> {
+    // the wire types of these are only known to the macro
+    if function.is_generic {
+        return Err(anyhow!("{} is generic and can only be called from Rust", function.name));
+    }
+    if function.borrows {
+        return Err(anyhow!("{} takes references or `impl Trait` and can only be called from Rust", function.name));
+    }
+
+    let inputs = named_inputs(&function.name, &function.args, input)?;
+    let mut module_path = vec![target.crate_name.clone()];
+    module_path.extend(function.modules.iter().cloned());
+
+    Ok(RunFunctionRequest {
+        function_id: function.name.clone(),
+        serialized_inputs: Value::Object(inputs).to_string(),
+        field_types: function.args.iter()
+            .map(|arg| NameAndType { name: arg.clone(), ..Default::default() })
+            .collect(),
+        secrets: function.secrets.clone(),
+        build_options: Some(function.build_options()),
+        package: target.package.clone(),
+        crate_name: target.crate_name.clone(),
+        bin_name: if target.is_bin() { target.crate_name.clone() } else { String::new() },
+        module_path: module_path.join("::"),
+        is_sync: !function.is_async,
+        ..Default::default()
+    })
+}
@@ -1,5 +1,8 @@
 pub mod server;
 pub mod enums;
 pub mod mount;
+pub mod mount_rules;
+pub mod manifest;
 pub mod parse_file;
-pub mod utilities;
\ No newline at end of file
+pub mod utilities;
+pub mod invoke;
\ No newline at end of file
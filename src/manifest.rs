@@ -0,0 +1,425 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use anyhow::{Error, anyhow};
+use toml::{Table, Value};
+use crate::mount::MountTarget;
+
+/// where the path dependencies from outside the workspace are mounted, relative to its root
+pub const PATH_DEPENDENCIES_DIR: &str = ".minimodal/deps";
+
+/// the crates the generated entrypoints use, with the features they need,
+/// added to the mounted package unless it depends on them already
+const RUNTIME_DEPENDENCIES: [(&str, &str, &[&str]); 2] = [
+    ("tokio", "1", &["rt", "macros"]),
+    ("serde_json", "1", &[]),
+];
+
+const DEPENDENCY_KINDS: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// the fields of `[workspace.package]` holding paths relative to the workspace root
+const INHERITED_PATHS: [&str; 2] = ["readme", "license-file"];
+
+/// the targets cargo discovers or a manifest declares, replaced by the entrypoints
+const TARGET_KINDS: [(&str, &str); 4] = [
+    ("bin", "autobins"),
+    ("example", "autoexamples"),
+    ("test", "autotests"),
+    ("bench", "autobenches"),
+];
+
+pub fn parse_manifest(content: &[u8], path: &str) -> Result<Table, Error> {
+    let content = std::str::from_utf8(content)
+        .map_err(|e| anyhow!("{} is not UTF-8: {}", path, e))?;
+    toml::from_str(content).map_err(|e| anyhow!("Failed to parse {}: {}", path, e))
+}
+
+/// the path from the directory `from` to `to`, both `/` separated and relative
+/// to the same root, or both absolute
+pub fn relative_between(from: &str, to: &str) -> String {
+    let components = |path: &'_ str| -> Vec<String> {
+        path.split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .map(str::to_string)
+            .collect()
+    };
+    let (from, to) = (components(from), components(to));
+    let common = from.iter().zip(to.iter()).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<&str> = vec![".."; from.len() - common];
+    parts.extend(to[common..].iter().map(String::as_str));
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// calls `f` with every table of dependencies: those of each kind, the
+/// platform specific ones and those a workspace lets its members inherit
+fn for_each_dependency_table(
+    manifest: &mut Table,
+    mut f: impl FnMut(&mut Table) -> Result<(), Error>,
+) -> Result<(), Error> {
+    for kind in DEPENDENCY_KINDS {
+        if let Some(Value::Table(dependencies)) = manifest.get_mut(kind) {
+            f(dependencies)?;
+        }
+    }
+    if let Some(Value::Table(platforms)) = manifest.get_mut("target") {
+        for (_, platform) in platforms.iter_mut() {
+            for kind in DEPENDENCY_KINDS {
+                if let Some(Value::Table(dependencies)) = platform.get_mut(kind) {
+                    f(dependencies)?;
+                }
+            }
+        }
+    }
+    if let Some(Value::Table(dependencies)) = manifest.get_mut("workspace").and_then(|workspace| workspace.get_mut("dependencies")) {
+        f(dependencies)?;
+    }
+    Ok(())
+}
+
+fn is_inherited(value: &Value) -> bool {
+    value.get("workspace").and_then(Value::as_bool) == Some(true)
+}
+
+/// a dependency declared in `[workspace.dependencies]` with the features and `optional` of the member
+fn inherit_dependency(name: &str, dependency: &Value, templates: Option<&Table>, to_workspace_root: &str) -> Result<Value, Error> {
+    let mut inherited = match templates.and_then(|templates| templates.get(name)) {
+        Some(Value::String(version)) => Table::from_iter([("version".to_string(), Value::String(version.clone()))]),
+        Some(Value::Table(template)) => template.clone(),
+        _ => return Err(anyhow!("the dependency {} is inherited, but [workspace.dependencies] doesn't declare it", name)),
+    };
+    if let Some(Value::String(path)) = inherited.get_mut("path") {
+        *path = format!("{}/{}", to_workspace_root, path);
+    }
+    if let Some(Value::Array(features)) = dependency.get("features") {
+        if let Value::Array(inherited_features) = inherited.entry("features").or_insert(Value::Array(Vec::new())) {
+            inherited_features.extend(features.iter().cloned());
+        }
+    }
+    if let Some(optional) = dependency.get("optional") {
+        inherited.insert("optional".to_string(), optional.clone());
+    }
+    Ok(Value::Table(inherited))
+}
+
+/// replaces the `workspace = true` fields, dependencies and lints of a member with the values of
+/// its workspace, `to_workspace_root` leads from the member's directory to the workspace's
+pub fn resolve_workspace_inheritance(
+    manifest: &mut Table,
+    workspace_manifest: &Table,
+    to_workspace_root: &str,
+) -> Result<(), Error> {
+    let workspace = workspace_manifest.get("workspace").and_then(Value::as_table);
+    let template = |key: &str| workspace.and_then(|workspace| workspace.get(key)).and_then(Value::as_table);
+
+    if let Some(Value::Table(package)) = manifest.get_mut("package") {
+        for (key, value) in package.iter_mut() {
+            if !is_inherited(value) {
+                continue;
+            }
+            let inherited = template("package").and_then(|package| package.get(key))
+                .ok_or_else(|| anyhow!("package.{} is inherited, but [workspace.package] doesn't set it", key))?;
+            *value = match inherited {
+                Value::String(path) if INHERITED_PATHS.contains(&key.as_str()) => Value::String(format!("{}/{}", to_workspace_root, path)),
+                inherited => inherited.clone(),
+            };
+        }
+    }
+
+    for_each_dependency_table(manifest, |dependencies| {
+        for (name, dependency) in dependencies.iter_mut() {
+            if is_inherited(dependency) {
+                *dependency = inherit_dependency(name, dependency, template("dependencies"), to_workspace_root)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    if manifest.get("lints").is_some_and(is_inherited) {
+        let lints = workspace.and_then(|workspace| workspace.get("lints"))
+            .ok_or_else(|| anyhow!("lints are inherited, but the workspace has no [workspace.lints]"))?;
+        manifest.insert("lints".to_string(), lints.clone());
+    }
+    Ok(())
+}
+
+/// the workspace manifest above `manifest_dir`, with its directory, a manifest
+/// with a `[workspace]` table being its own workspace
+pub fn find_workspace(manifest_dir: &Path) -> Result<Option<(PathBuf, Table)>, Error> {
+    for dir in manifest_dir.ancestors() {
+        let path = dir.join("Cargo.toml");
+        if !path.is_file() {
+            continue;
+        }
+        let manifest = parse_manifest(&std::fs::read(&path)?, &path.to_string_lossy())?;
+        if manifest.contains_key("workspace") {
+            return Ok(Some((dir.to_path_buf(), manifest)));
+        }
+    }
+    Ok(None)
+}
+
+/// Mounts the path dependencies from outside the workspace below `PATH_DEPENDENCIES_DIR`
+/// and points the manifests of the mount at where their dependencies are mounted
+pub struct PathDependencies {
+    workspace_root: PathBuf,
+    /// the directories mounted from outside, with where they are mounted
+    relocated: Vec<(PathBuf, String)>,
+    /// the directories whose files are still to be mounted
+    pending_dirs: Vec<(PathBuf, String)>,
+    /// the dependencies from outside whose manifests are still to be rewritten
+    pending_manifests: Vec<(PathBuf, String)>,
+    seen: HashSet<PathBuf>,
+}
+
+impl PathDependencies {
+    pub fn new(workspace_root: &Path) -> Result<PathDependencies, Error> {
+        Ok(PathDependencies {
+            workspace_root: workspace_root.canonicalize()?,
+            relocated: Vec::new(),
+            pending_dirs: Vec::new(),
+            pending_manifests: Vec::new(),
+            seen: HashSet::new(),
+        })
+    }
+
+    /// where the directory of a dependency is mounted, relative to the workspace root
+    fn mounted_path(&mut self, source: &Path) -> String {
+        if let Ok(inside) = source.strip_prefix(&self.workspace_root) {
+            return slash_path(inside);
+        }
+        let relocated = self.relocated.iter()
+            .find_map(|(relocated, mount_dir)| source.strip_prefix(relocated).ok().map(|below| (mount_dir, below)));
+        let mounted = match relocated {
+            Some((mount_dir, below)) if below.as_os_str().is_empty() => mount_dir.clone(),
+            Some((mount_dir, below)) => format!("{}/{}", mount_dir, slash_path(below)),
+            None => {
+                let name = source.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| "root".to_string());
+                let mut mount_dir = format!("{}/{}", PATH_DEPENDENCIES_DIR, name);
+                let mut index = 2;
+                while self.relocated.iter().any(|(_, relocated)| *relocated == mount_dir) {
+                    mount_dir = format!("{}/{}-{}", PATH_DEPENDENCIES_DIR, name, index);
+                    index += 1;
+                }
+                self.relocated.push((source.to_path_buf(), mount_dir.clone()));
+                self.pending_dirs.push((source.to_path_buf(), mount_dir.clone()));
+                mount_dir
+            },
+        };
+        if self.seen.insert(source.to_path_buf()) {
+            self.pending_manifests.push((source.to_path_buf(), mounted.clone()));
+        }
+        mounted
+    }
+
+    /// points the path dependencies of the manifest in `source_dir`, which is mounted at
+    /// `mount_dir`, at where they are mounted, true if any changed
+    pub fn rewrite(&mut self, manifest: &mut Table, source_dir: &Path, mount_dir: &str) -> Result<bool, Error> {
+        let mut changed = false;
+        for_each_dependency_table(manifest, |dependencies| {
+            for (name, dependency) in dependencies.iter_mut() {
+                let path = match dependency.get_mut("path") {
+                    Some(Value::String(path)) => path,
+                    _ => continue,
+                };
+                let source = source_dir.join(&*path).canonicalize()
+                    .map_err(|e| anyhow!("the path dependency {} at {} was not found: {}", name, path, e))?;
+                let mounted = relative_between(mount_dir, &self.mounted_path(&source));
+                if *path != mounted {
+                    *path = mounted;
+                    changed = true;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(changed)
+    }
+
+    /// a directory from outside the workspace whose files are still to be mounted
+    pub fn next_dir(&mut self) -> Option<(PathBuf, String)> {
+        self.pending_dirs.pop()
+    }
+
+    /// a dependency from outside the workspace whose manifest is still to be rewritten,
+    /// once the directories are mounted
+    pub fn next_manifest(&mut self) -> Option<(PathBuf, String)> {
+        self.pending_manifests.pop()
+    }
+}
+
+/// the manifest of a path dependency mounted from outside the workspace: its inheritance is resolved
+/// against its own workspace and its `[workspace]` dropped, it is built in the one of the mount
+pub fn rewrite_dependency_manifest(
+    content: &[u8],
+    source_path: &Path,
+    mount_path: &str,
+    path_dependencies: &mut PathDependencies,
+) -> Result<Vec<u8>, Error> {
+    let mut manifest = parse_manifest(content, &source_path.to_string_lossy())?;
+    let source_dir = source_path.parent().unwrap_or(Path::new("."));
+    if let Some((workspace_dir, workspace)) = find_workspace(source_dir)? {
+        let to_workspace_root = relative_between(&slash_path(source_dir), &slash_path(&workspace_dir));
+        resolve_workspace_inheritance(&mut manifest, &workspace, &to_workspace_root)?;
+    }
+    manifest.remove("workspace");
+    if let Some(Value::Table(package)) = manifest.get_mut("package") {
+        package.remove("workspace");
+    }
+    let mount_dir = Path::new(mount_path).parent().map(slash_path).unwrap_or_default();
+    path_dependencies.rewrite(&mut manifest, source_dir, &mount_dir)?;
+    Ok(toml::to_string(&manifest)?.into_bytes())
+}
+
+/// the features of the package no longer refer to `name` as an optional dependency,
+/// the feature it implied is kept as an empty one
+fn make_required(manifest: &mut Table, name: &str) {
+    let features = match manifest.get_mut("features") {
+        Some(Value::Table(features)) => features,
+        _ => return,
+    };
+    let dep_feature = format!("dep:{}", name);
+    let weak_prefix = format!("{}?/", name);
+    let implicit = !features.values()
+        .filter_map(Value::as_array)
+        .flatten()
+        .any(|enabled| enabled.as_str() == Some(dep_feature.as_str()));
+    for enabled in features.iter_mut().filter_map(|(_, enabled)| enabled.as_array_mut()) {
+        enabled.retain(|enabled| enabled.as_str() != Some(dep_feature.as_str()) && enabled.as_str() != Some(name));
+        for enabled in enabled.iter_mut() {
+            if let Some(feature) = enabled.as_str().and_then(|enabled| enabled.strip_prefix(&weak_prefix)) {
+                *enabled = Value::String(format!("{}/{}", name, feature));
+            }
+        }
+    }
+    if implicit {
+        features.entry(name).or_insert(Value::Array(Vec::new()));
+    }
+}
+
+/// the package a dependency of a manifest names, which may be renamed
+fn dependency_package<'a>(name: &'a str, dependency: &'a Value) -> &'a str {
+    dependency.get("package").and_then(Value::as_str).unwrap_or(name)
+}
+
+/// the key the package depends on the crate `package` under, if it does
+fn dependency_key<'a>(dependencies: &'a Table, package: &str) -> Option<&'a str> {
+    dependencies.iter()
+        .find(|(name, dependency)| dependency_package(name, dependency) == package)
+        .map(|(name, _)| name.as_str())
+}
+
+/// the runtime dependencies the package renamed, with the name of their crate,
+/// e.g. `("rt", "tokio")` for `rt = { package = "tokio" }`
+pub fn renamed_runtime_dependencies(manifest: &Table) -> Vec<(String, String)> {
+    let dependencies = match manifest.get("dependencies") {
+        Some(Value::Table(dependencies)) => dependencies,
+        _ => return Vec::new(),
+    };
+    RUNTIME_DEPENDENCIES.iter()
+        .filter_map(|(name, _, _)| match dependency_key(dependencies, name) {
+            Some(key) if key != *name => Some((key.to_string(), name.to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// adds the runtime dependencies the package lacks, and the features they need,
+/// those it has, maybe renamed, are made required since the entrypoints always use them
+fn add_runtime_dependencies(manifest: &mut Table) {
+    let mut made_required = Vec::new();
+    let dependencies = match manifest.entry("dependencies").or_insert(Value::Table(Table::new())) {
+        Value::Table(dependencies) => dependencies,
+        _ => return,
+    };
+    for (name, version, features) in RUNTIME_DEPENDENCIES {
+        let key = dependency_key(dependencies, name).unwrap_or(name).to_string();
+        let dependency = dependencies.entry(&key).or_insert(Value::String(version.to_string()));
+        if let Some(Value::Boolean(true)) = dependency.as_table_mut().and_then(|dependency| dependency.remove("optional")) {
+            made_required.push(key.clone());
+        }
+        if features.is_empty() {
+            continue;
+        }
+        if let Value::String(version) = dependency {
+            *dependency = Value::Table(Table::from_iter([("version".to_string(), Value::String(version.clone()))]));
+        }
+        if let Some(Value::Array(enabled)) = dependency.as_table_mut().map(|dependency| {
+            dependency.entry("features").or_insert(Value::Array(Vec::new()))
+        }) {
+            for feature in features {
+                if !enabled.iter().any(|enabled| enabled.as_str() == Some(feature)) {
+                    enabled.push(Value::String(feature.to_string()));
+                }
+            }
+        }
+    }
+    for key in made_required {
+        make_required(manifest, &key);
+    }
+}
+
+/// Rewrites the manifest of the mounted package for the shadow build:
+/// the original targets are replaced by a `[[bin]]` section per entrypoint,
+/// the runtime dependencies of the entrypoints are added, and dev-dependencies
+/// are made available to the entrypoints when tests, examples or benches are mounted,
+/// except `dependents`, the packages depending on the mounted one, through which
+/// it would depend on itself.
+pub fn build_cargo_toml(
+    cargo_toml_content : &mut Vec<u8>,
+    targets: &[MountTarget],
+    manifest_dir: &str,
+    dependents: &[String],
+) -> Result<(), Error> {
+    let mut manifest = parse_manifest(cargo_toml_content, "Cargo.toml")?;
+
+    let needs_dev_dependencies = targets.iter().any(|target| matches!(target.kind.as_str(), "test" | "example" | "bench"));
+    if let Some(Value::Table(dev_dependencies)) = manifest.get("dev-dependencies").cloned().filter(|_| needs_dev_dependencies) {
+        if let Value::Table(dependencies) = manifest.entry("dependencies").or_insert(Value::Table(Table::new())) {
+            for (name, dependency) in dev_dependencies {
+                if !dependents.iter().any(|dependent| dependent == dependency_package(&name, &dependency)) {
+                    dependencies.entry(name).or_insert(dependency);
+                }
+            }
+        }
+    }
+    add_runtime_dependencies(&mut manifest);
+
+    // only the entrypoints are built, they contain the code of the original targets,
+    // which may need features or files the mount doesn't have
+    for (kind, _) in TARGET_KINDS {
+        manifest.remove(kind);
+    }
+    if let Some(Value::Table(package)) = manifest.get_mut("package") {
+        for (_, auto) in TARGET_KINDS {
+            package.insert(auto.to_string(), Value::Boolean(false));
+        }
+        package.remove("default-run");
+    }
+
+    let bins = targets.iter()
+        .map(|target| {
+            let entry = Path::new(&target.entry);
+            let path = entry.strip_prefix(manifest_dir).unwrap_or(entry);
+            Value::Table(Table::from_iter([
+                ("name".to_string(), Value::String(target.entry_bin.clone())),
+                ("path".to_string(), Value::String(slash_path(path))),
+                ("test".to_string(), Value::Boolean(false)),
+                ("bench".to_string(), Value::Boolean(false)),
+                ("doc".to_string(), Value::Boolean(false)),
+            ]))
+        })
+        .collect();
+    manifest.insert("bin".to_string(), Value::Array(bins));
+
+    *cargo_toml_content = toml::to_string(&manifest)?.into_bytes();
+    Ok(())
+}
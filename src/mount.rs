@@ -1,141 +1,472 @@
 use std::fs;
-use cargo_toml::{Manifest, Dependency};
-use anyhow::Error;
-use cargo_metadata::MetadataCommand;
-use ignore::WalkBuilder;
+use anyhow::{Error, anyhow};
+use cargo_metadata::{DependencyKind, MetadataCommand, Metadata, Package};
 use minimodal_proto::proto::minimodal::{
-    MountProjectRequest, 
-    FileEntry, 
+    ArchiveChunk,
     MountProjectResponse, 
-    mini_modal_client::MiniModalClient
+    DeployAppResponse,
 };
-use tonic::transport::Channel;
-use std::path::PathBuf;
+use basemodules::session::Client;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
 use toml;
-use crate::parse_file::{remove_macro, remove_function};
+use crate::parse_file::{remove_macro, remove_function, expose_remote_functions, RemoteFunction};
+use crate::invoke::{module_path_of, remote_functions};
+use crate::mount_rules::{select_files, MountRules, Reason, SelectedFile};
+use crate::manifest::{parse_manifest, relative_between, renamed_runtime_dependencies, resolve_workspace_inheritance, rewrite_dependency_manifest, PathDependencies};
+pub use crate::manifest::build_cargo_toml;
 
-pub fn build_cargo_toml(
-    cargo_toml_content : &mut Vec<u8>,
-) -> Result<(), Error> {
-    let mut cargo_toml_content_str = std::str::from_utf8_mut(cargo_toml_content).unwrap().to_string();
+/// where the mount index is stored, relative to the mount root
+pub const MOUNT_INDEX_PATH: &str = ".minimodal/targets.json";
 
-    let mut manifest = Manifest::from_str(&cargo_toml_content_str)
-        .expect("Failed to parse Cargo.toml");
+/// A build target of the mounted package, together with the entrypoint
+/// the server generates to call functions defined in it.
+/// All paths are relative to the workspace root.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MountTarget {
+    pub package: String,
+    /// one of lib, bin, example, test or bench
+    pub kind: String,
+    /// the name the crate is compiled under, as in `CARGO_CRATE_NAME`
+    pub crate_name: String,
+    /// the crate root, e.g. src/lib.rs
+    pub root: String,
+    /// the crate root with the minimodal macros and `main` removed
+    pub source: String,
+    /// the generated entrypoint, placed next to the crate root so
+    /// `mod` declarations resolve exactly as in the original crate
+    pub entry: String,
+    /// name of the `[[bin]]` building the entrypoint
+    pub entry_bin: String,
+    /// the remote functions defined in the target, filled in when mounting
+    #[serde(default)]
+    pub functions: Vec<RemoteFunction>,
+}
 
-    let modified_toml = toml::to_string(&manifest)?;
+impl MountTarget {
+    pub fn is_bin(&self) -> bool {
+        self.kind == "bin"
+    }
+}
 
-    // Update the cargo_toml_content with the new TOML string
-    *cargo_toml_content = modified_toml.into_bytes();
-    Ok(())
+pub fn read_mount_index(mount_dir: &Path) -> Result<Vec<MountTarget>, Error> {
+    let content = fs::read(mount_dir.join(MOUNT_INDEX_PATH))
+        .map_err(|e| anyhow!("Failed to read mount index, was the project mounted? {}", e))?;
+    Ok(serde_json::from_slice(&content)?)
 }
 
-pub fn handle_main_rs(work_space_root : PathBuf) -> Result<Vec<u8>, Error> {
-    let main_rs_path = work_space_root.join("src/main.rs");
-    let content = if main_rs_path.exists() {
-        match fs::read(&main_rs_path) {
-            Ok(content) => {
-                Ok(content)
-            },
-            Err(e) => {
-                Err(anyhow::anyhow!(format!("Failed to read src/main.rs: {}", e)))
+/// finds the target a function was compiled in,
+/// `bin_name` is only set for functions defined in a binary
+pub fn find_target<'a>(
+    targets: &'a [MountTarget], 
+    package: &str, 
+    crate_name: &str, 
+    bin_name: &str
+) -> Result<&'a MountTarget, Error> {
+    let mut candidates = targets.iter()
+        .filter(|target| target.package == package && target.crate_name == crate_name);
+
+    // a lib and a bin of the same package usually share the crate name
+    candidates.find(|target| target.is_bin() != bin_name.is_empty())
+        .ok_or(anyhow!(
+            "crate {} of package {} was not mounted, mounted targets: {:?}", 
+            crate_name, 
+            package, 
+            targets.iter().map(|target| format!("{}:{}", target.package, target.crate_name)).collect::<Vec<_>>()
+        ))
+}
+
+fn target_kind(kinds: &[String]) -> Option<&'static str> {
+    kinds.iter().find_map(|kind| match kind.as_str() {
+        "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" => Some("lib"),
+        "bin" => Some("bin"),
+        "example" => Some("example"),
+        "test" => Some("test"),
+        "bench" => Some("bench"),
+        // proc macros and build scripts can't contain remote functions
+        _ => None,
+    })
+}
+
+fn relative_path(path: &Path, root: &Path) -> Result<String, Error> {
+    let relative_path = path.strip_prefix(root)
+        .map_err(|_| anyhow!("{} is not inside the workspace {}", path.display(), root.display()))?;
+    Ok(relative_path.to_string_lossy().to_string())
+}
+
+/// lists the targets of `package` which can define remote functions
+pub fn mount_targets(package: &Package, workspace_root: &Path) -> Result<Vec<MountTarget>, Error> {
+    let mut targets = Vec::new();
+    for target in package.targets.iter() {
+        let kind = match target_kind(&target.kind) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let crate_name = target.name.replace('-', "_");
+        let root = PathBuf::from(target.src_path.as_std_path());
+        let entry_name = format!("__minimodal_{}_{}", kind, crate_name);
+        let entry = root.with_file_name(format!("{}.rs", entry_name));
+
+        targets.push(MountTarget {
+            package: package.name.clone(),
+            kind: kind.to_string(),
+            crate_name: crate_name.clone(),
+            root: relative_path(&root, workspace_root)?,
+            source: format!(".minimodal/targets/{}_{}.rs", kind, crate_name),
+            entry: relative_path(&entry, workspace_root)?,
+            entry_bin: entry_name,
+            functions: Vec::new(),
+        });
+    }
+    Ok(targets)
+}
+
+/// selects the package to mount, defaulting to the root package
+/// the packages depending on `package` through regular or build dependencies
+fn dependents_of(metadata: &Metadata, package: &Package) -> Vec<String> {
+    let mut dependents: Vec<String> = Vec::new();
+    let mut found = true;
+    while found {
+        found = false;
+        for candidate in metadata.packages.iter() {
+            if candidate.name == package.name || dependents.contains(&candidate.name) {
+                continue;
             }
-        } 
-    } else {
-        Err(anyhow::anyhow!("src/main.rs does not exist"))
-    }?;
+            let depends = candidate.dependencies.iter()
+                .filter(|dependency| dependency.kind != DependencyKind::Development)
+                .any(|dependency| dependency.name == package.name || dependents.contains(&dependency.name));
+            if depends {
+                dependents.push(candidate.name.clone());
+                found = true;
+            }
+        }
+    }
+    dependents
+}
+
+pub fn select_package<'a>(metadata: &'a Metadata, package: Option<&str>) -> Result<&'a Package, Error> {
+    match package {
+        Some(name) => metadata.workspace_packages()
+            .into_iter()
+            .find(|candidate| candidate.name == name)
+            .ok_or(anyhow!("package {} is not a member of the workspace {}", name, metadata.workspace_root)),
+        None => metadata.root_package()
+            .ok_or(anyhow!("{} is a virtual workspace, specify the package to mount", metadata.workspace_root)),
+    }
+}
+
+//TODO find a way to avoid manually adding the macro names here
+const MINIMODAL_MACROS: [&str; 3] = ["function", "mount", "function_experiment"];
 
-    let new_content_str = String::from_utf8(content).unwrap();
-    let mut ast = syn::parse_file(&new_content_str).unwrap();
+fn minimodal_macros() -> Vec<String> {
+    MINIMODAL_MACROS.iter().map(|s| s.to_string()).collect()
+}
+
+/// strips the minimodal macros from a source file and exposes the
+/// remote functions and modules to the crate root
+fn strip_source(content: &str, path: &Path, remove_main: bool) -> Result<Vec<u8>, Error> {
+    let mut ast = syn::parse_file(content)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
 
-    //TODO find a way to avoid manually adding the macro names here
-    remove_macro(
-        &mut ast, 
-        vec!["function", "mount", "function_experiment"].iter().map(|s| s.to_string()).collect()
-    );
+    expose_remote_functions(&mut ast, minimodal_macros());
+    remove_macro(&mut ast, minimodal_macros());
 
-    remove_function(
-        &mut ast, 
-        "main"
-    );
+    if remove_main {
+        remove_function(
+            &mut ast, 
+            "main"
+        );
+    }
 
     Ok(prettyplease::unparse(&ast).into_bytes())
 }
 
-pub fn get_project_structure(filter_entries : Vec<String>) -> Result<HashMap<String, Vec<u8>>, Error> {
+/// strips the minimodal macros and `main` from a crate root
+pub fn handle_crate_root(root_path : &Path) -> Result<Vec<u8>, Error> {
+    let content = fs::read_to_string(root_path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", root_path.display(), e))?;
+    strip_source(&content, root_path, true)
+}
+
+/// strips the minimodal macros from a module file, files without remote functions
+/// are left untouched unless they declare a module leading to one (`leads_to_remote`)
+pub fn handle_module_file(path: &Path, content: &[u8], leads_to_remote: bool) -> Result<Option<Vec<u8>>, Error> {
+    let content = match std::str::from_utf8(content) {
+        Ok(content) => content,
+        Err(_) => return Ok(None),
+    };
+    if !leads_to_remote && !MINIMODAL_MACROS.iter().any(|m| content.contains(&format!("#[{}", m))) {
+        return Ok(None);
+    }
+    strip_source(content, path, false).map(Some)
+}
+
+/// placeholder so the shadow package builds before the server
+/// generated any entrypoint
+const ENTRY_PLACEHOLDER: &str = "fn main() {}\n";
+
+/// What a mount uploads, and the files of the workspace it leaves out
+#[derive(Debug)]
+pub struct MountPlan {
+    /// the contents by path relative to the workspace root
+    pub files: HashMap<String, Vec<u8>>,
+    /// the targets of the symlinks inside the workspace, which are mounted as symlinks
+    pub symlinks: HashMap<String, String>,
+    /// the mode bits of the files read from the workspace, the generated ones are 0o644
+    pub modes: HashMap<String, u32>,
+    /// why each of the files and symlinks is mounted
+    pub reasons: HashMap<String, Reason>,
+    /// excluded or over the size threshold
+    pub skipped: Vec<SelectedFile>,
+}
+
+pub fn get_project_structure(
+    rules: &MountRules,
+    package: Option<&str>,
+) -> Result<HashMap<String, Vec<u8>>, Error> {
+    Ok(plan_mount(rules, package)?.files)
+}
+
+/// the files mounting `package` uploads, as selected by the rules, the ignore files and
+/// the `minimodal` metadata of the workspace and the package, with the generated ones
+pub fn plan_mount(
+    rules: &MountRules,
+    package: Option<&str>,
+) -> Result<MountPlan, Error> {
+    plan_mount_in(&std::env::current_dir()?, rules, package)
+}
+
+/// `plan_mount` for the workspace containing `project_dir`
+pub fn plan_mount_in(
+    project_dir: &Path,
+    rules: &MountRules,
+    package: Option<&str>,
+) -> Result<MountPlan, Error> {
     let metadata = MetadataCommand::new()
+        .current_dir(project_dir)
         .exec()?;
+    let workspace_root: PathBuf = metadata.workspace_root.clone().into();
+    let package = select_package(&metadata, package)?;
+    let rules = rules.clone().with_metadata(&[&metadata.workspace_metadata, &package.metadata])?;
 
-    // Walk through project files
-    let walker = WalkBuilder::new(&metadata.workspace_root)
-        .hidden(false)
-        .git_ignore(true)
-        .build();
+    let mut plan = MountPlan {
+        files: HashMap::new(),
+        symlinks: HashMap::new(),
+        modes: HashMap::new(),
+        reasons: HashMap::new(),
+        skipped: Vec::new(),
+    };
+    plan.add_files(&workspace_root, "", &rules)?;
 
-    let mut hashmap : HashMap<String, Vec<u8>> = HashMap::new();
+    let mut targets = mount_targets(package, &workspace_root)?;
+    // read before the sources are stripped of their macros
+    let roots: Vec<PathBuf> = targets.iter().map(|target| workspace_root.join(&target.root)).collect();
+    for target in targets.iter_mut() {
+        let root = workspace_root.join(&target.root);
+        let other_roots: Vec<PathBuf> = roots.iter().filter(|other| **other != root).cloned().collect();
+        target.functions = remote_functions(&workspace_root, target, &other_roots)?;
+    }
 
-    for entry in walker.filter_map(Result::ok).filter_map(|entry| {
-        let relative_path = entry.path().strip_prefix(&metadata.workspace_root).ok()?;
-        let relative_path_str = relative_path.to_string_lossy().to_string();
+    let manifest_path = relative_path(package.manifest_path.as_std_path(), &workspace_root)?;
+    let manifest_dir = Path::new(&manifest_path)
+        .parent()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
 
-        if filter_entries.iter().any(|filter| relative_path_str.starts_with(filter)) {
-            None
-        } else {
-            Some(entry)
-        }
-    }) {
-        let path = entry.path();
-        let relative_path = path.strip_prefix(&metadata.workspace_root)?;
-        if filter_entries.contains(&relative_path.to_string_lossy().to_string()) {
+    // remote functions in submodules are called through their module path, the
+    // files declaring the modules on it, e.g. `mod train;` in src/jobs.rs, expose them
+    let remote_modules: Vec<(PathBuf, &[String])> = targets.iter()
+        .flat_map(|target| {
+            let root = workspace_root.join(&target.root);
+            target.functions.iter()
+                .flat_map(|function| (1..function.modules.len()).map(|len| &function.modules[..len]))
+                .map(move |modules| (root.clone(), modules))
+        })
+        .collect();
+    for (file_path, content) in plan.files.iter_mut() {
+        if !file_path.ends_with(".rs") || !Path::new(file_path).starts_with(&manifest_dir) {
             continue;
         }
-        if entry.file_type().map_or(false, |ft| ft.is_file()) {
-            let path = entry.path();
-            let relative_path = path.strip_prefix(&metadata.workspace_root)?;
-            let content = std::fs::read(path)?;
-            hashmap.insert(relative_path.to_string_lossy().to_string(), content);
+        let path = workspace_root.join(file_path);
+        let leads_to_remote = remote_modules.iter()
+            .any(|(root, modules)| module_path_of(&path, root).is_some_and(|module_path| module_path.as_slice() == *modules));
+        if let Some(stripped) = handle_module_file(&path, content, leads_to_remote)? {
+            *content = stripped;
         }
     }
-    
-    hashmap.insert("src/original_main.rs".to_string(), handle_main_rs(metadata.workspace_root.into())?);
 
+    for target in targets.iter() {
+        plan.add_generated(&target.source, handle_crate_root(&workspace_root.join(&target.root))?);
+        plan.add_generated(&target.entry, ENTRY_PLACEHOLDER.as_bytes().to_vec());
+    }
+    plan.add_generated(MOUNT_INDEX_PATH, serde_json::to_vec_pretty(&targets)?);
 
-    let cargo_toml_content = match hashmap.get_mut(&"Cargo.toml".to_string()) {
-        Some(cargo_toml_content) => cargo_toml_content,
+    let mut cargo_toml_content = match plan.files.get(&manifest_path) {
+        Some(cargo_toml_content) => cargo_toml_content.clone(),
         None => return Err(
             anyhow::anyhow!(
                 format!(
-                    "Cargo.toml not found in the project: used key: \"Cargo.toml\" out of all keys: {:?}", 
-                    hashmap.keys()
+                    "Cargo.toml not found in the project: used key: {:?} out of all keys: {:?}", 
+                    manifest_path,
+                    plan.files.keys()
                 )
             )
         ),
     };
+    let mut manifest = parse_manifest(&cargo_toml_content, &manifest_path)?;
+    let workspace_manifest_path = workspace_root.join("Cargo.toml");
+    let workspace_manifest = parse_manifest(&fs::read(&workspace_manifest_path)?, &workspace_manifest_path.to_string_lossy())?;
+    resolve_workspace_inheritance(&mut manifest, &workspace_manifest, &relative_between(&manifest_dir, ""))?;
 
-    //pass mutable reference of cargo_toml_content to build_cargo_toml
-    build_cargo_toml(cargo_toml_content)?;
-    Ok(hashmap)
+    // the path dependencies from outside the workspace are mounted with it
+    let mut path_dependencies = PathDependencies::new(&workspace_root)?;
+    path_dependencies.rewrite(&mut manifest, &workspace_root.join(&manifest_dir), &manifest_dir)?;
+    cargo_toml_content = toml::to_string(&manifest)?.into_bytes();
+    let mut manifest_paths = vec!["Cargo.toml".to_string()];
+    for member in metadata.workspace_packages() {
+        manifest_paths.push(relative_path(member.manifest_path.as_std_path(), &workspace_root)?);
+    }
+    for path in manifest_paths {
+        let content = match plan.files.get(&path) {
+            Some(content) if path != manifest_path => content,
+            _ => continue,
+        };
+        let mut manifest = parse_manifest(content, &path)?;
+        let dir = Path::new(&path).parent().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+        if path_dependencies.rewrite(&mut manifest, &workspace_root.join(&dir), &dir)? {
+            plan.files.insert(path, toml::to_string(&manifest)?.into_bytes());
+        }
+    }
+    loop {
+        if let Some((source_dir, mount_dir)) = path_dependencies.next_dir() {
+            plan.add_files(&source_dir, &mount_dir, &MountRules::default())?;
+        } else if let Some((source_dir, mount_dir)) = path_dependencies.next_manifest() {
+            let mount_path = format!("{}/Cargo.toml", mount_dir);
+            if let Some(content) = plan.files.get(&mount_path) {
+                let rewritten = rewrite_dependency_manifest(content, &source_dir.join("Cargo.toml"), &mount_path, &mut path_dependencies)?;
+                plan.files.insert(mount_path, rewritten);
+            }
+        } else {
+            break;
+        }
+    }
+
+    // the entrypoints name the runtime dependencies by their crate
+    let renamed = renamed_runtime_dependencies(&manifest);
+    for target in targets.iter() {
+        if let Some(source) = plan.files.get_mut(&target.source) {
+            for (key, name) in renamed.iter() {
+                source.extend_from_slice(format!("\nextern crate {} as {};\n", key, name).as_bytes());
+            }
+        }
+    }
+    build_cargo_toml(&mut cargo_toml_content, &targets, &manifest_dir, &dependents_of(&metadata, package))?;
+    plan.files.insert(manifest_path, cargo_toml_content);
+    Ok(plan)
 }
 
+impl MountPlan {
+    /// adds the files of `root` the rules select, mounted below `prefix`
+    fn add_files(&mut self, root: &Path, prefix: &str, rules: &MountRules) -> Result<(), Error> {
+        for mut file in select_files(root, rules)? {
+            let path = root.join(&file.path);
+            if !prefix.is_empty() {
+                file.path = format!("{}/{}", prefix, file.path);
+            }
+            if !file.reason.is_mounted() {
+                self.skipped.push(file);
+                continue;
+            }
+            let metadata = fs::symlink_metadata(&path)?;
+            if metadata.file_type().is_symlink() {
+                self.symlinks.insert(file.path.clone(), fs::read_link(&path)?.to_string_lossy().replace('\\', "/"));
+            } else {
+                self.files.insert(file.path.clone(), fs::read(&path)?);
+                self.modes.insert(file.path.clone(), file_mode(&metadata));
+            }
+            self.reasons.insert(file.path, file.reason);
+        }
+        Ok(())
+    }
 
-pub async fn mount_project(
-    client: &mut MiniModalClient<Channel>,
-    filter_entries: Vec<String>,
-) -> Result<MountProjectResponse, Error> {
-    let hashmap = get_project_structure(filter_entries.clone())?;
+    fn add_generated(&mut self, path: &str, content: Vec<u8>) {
+        self.files.insert(path.to_string(), content);
+        self.reasons.insert(path.to_string(), Reason::Generated);
+    }
+}
 
-    let files: Vec<FileEntry> = hashmap.into_iter()
-        .map(|(file_path, content)| FileEntry {
-            file_path,
-            content,
-        })
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    DEFAULT_FILE_MODE
+}
+
+/// the mode of the generated files, and of all files mounted from windows
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// the archives are sent in chunks of 1MiB
+pub const ARCHIVE_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// the plan as a zstd compressed tar, with the mode bits of the files and the symlinks
+pub fn build_archive(plan: &MountPlan) -> Result<Vec<u8>, Error> {
+    let encoder = zstd::Encoder::new(Vec::new(), 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    let mut paths: Vec<&String> = plan.files.keys().chain(plan.symlinks.keys()).collect();
+    paths.sort();
+    for path in paths {
+        let mut header = tar::Header::new_gnu();
+        // the mount is the same whenever the files are, the server writes them anew anyway
+        header.set_mtime(0);
+        match plan.symlinks.get(path) {
+            Some(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                builder.append_link(&mut header, path, target)?;
+            },
+            None => {
+                let content = &plan.files[path];
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(plan.modes.get(path).copied().unwrap_or(DEFAULT_FILE_MODE));
+                header.set_size(content.len() as u64);
+                builder.append_data(&mut header, path, content.as_slice())?;
+            },
+        }
+    }
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// the chunks of an archive, the first one naming the app of a deployment
+pub fn archive_chunks(app: &str, archive: &[u8]) -> Vec<ArchiveChunk> {
+    let mut chunks: Vec<ArchiveChunk> = archive.chunks(ARCHIVE_CHUNK_BYTES)
+        .map(|data| ArchiveChunk { app: String::new(), data: data.to_vec() })
         .collect();
+    match chunks.first_mut() {
+        Some(first) => first.app = app.to_string(),
+        None => chunks.push(ArchiveChunk { app: app.to_string(), data: Vec::new() }),
+    }
+    chunks
+}
 
-    let request = MountProjectRequest {
-        files,
-    };
 
-    match client.mount_project(request).await {
+pub async fn mount_project(
+    client: &mut Client,
+    rules: &MountRules,
+    package: Option<&str>,
+) -> Result<MountProjectResponse, Error> {
+    let archive = build_archive(&plan_mount(rules, package)?)?;
+
+    // the mount is traced as part of the call which made it
+    let mut request = tonic::Request::new(tokio_stream::iter(archive_chunks("", &archive)));
+    basemodules::trace_context::inject(&mut request);
+
+    match client.mount_archive(request).await {
         Ok(response) => {
             Ok(response.into_inner())
         },
@@ -144,3 +475,22 @@ pub async fn mount_project(
         }
     }
 }
+
+/// snapshots the project as a new version of `app` on the server
+pub async fn deploy_app(
+    client: &mut Client,
+    app: &str,
+    rules: &MountRules,
+    package: Option<&str>,
+) -> Result<DeployAppResponse, Error> {
+    let archive = build_archive(&plan_mount(rules, package)?)?;
+
+    // the deployment and its builds are traced as part of the call which made it
+    let mut request = tonic::Request::new(tokio_stream::iter(archive_chunks(app, &archive)));
+    basemodules::trace_context::inject(&mut request);
+
+    match client.deploy_archive(request).await {
+        Ok(response) => Ok(response.into_inner()),
+        Err(e) => Err(anyhow::anyhow!("Failed to deploy {}: {}", app, e)),
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Error, anyhow};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, WalkBuilder};
+use serde::Deserialize;
+
+/// ignore files with the syntax of `.gitignore`, in any directory of the workspace
+pub const IGNORE_FILENAME: &str = ".minimodalignore";
+
+/// files over 4MiB are skipped unless they are included
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Which files of the workspace a mount uploads, besides the ignore files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MountRules {
+    /// paths relative to the workspace root which are not mounted
+    pub exclude: Vec<String>,
+    /// globs relative to the workspace root, e.g. `data/*.csv`, of files mounted
+    /// even when they are ignored, excluded or over the size threshold
+    pub include: Vec<String>,
+    /// larger files are skipped, `DEFAULT_MAX_FILE_BYTES` unless set here or in the metadata
+    pub max_file_bytes: Option<u64>,
+}
+
+/// `[package.metadata.minimodal]` or `[workspace.metadata.minimodal]`, e.g.
+/// ```toml
+/// [package.metadata.minimodal]
+/// include = ["data/*.csv"]
+/// exclude = ["notebooks"]
+/// max-file-size = 1048576
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct MetadataRules {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    max_file_size: Option<u64>,
+}
+
+impl MountRules {
+    /// adds the rules of the `minimodal` key of the workspace and package metadata,
+    /// a size threshold set on the rules wins over the one of the package, which wins
+    /// over the one of the workspace
+    pub fn with_metadata(mut self, metadata: &[&serde_json::Value]) -> Result<MountRules, Error> {
+        let mut max_file_bytes = None;
+        for metadata in metadata {
+            let rules = match metadata.get("minimodal") {
+                Some(rules) => MetadataRules::deserialize(rules)
+                    .map_err(|e| anyhow!("invalid [metadata.minimodal]: {}", e))?,
+                None => continue,
+            };
+            self.include.extend(rules.include);
+            self.exclude.extend(rules.exclude);
+            max_file_bytes = rules.max_file_size.or(max_file_bytes);
+        }
+        self.max_file_bytes = self.max_file_bytes.or(max_file_bytes);
+        Ok(self)
+    }
+}
+
+/// Why a file of the workspace is mounted or skipped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// no .gitignore or .minimodalignore ignores it
+    NotIgnored,
+    /// it matches the include glob
+    Included(String),
+    /// the mount writes it: crate roots without the macros, entrypoints and the index
+    Generated,
+    /// skipped, it is below the excluded path
+    Excluded(String),
+    /// skipped, it is larger than the threshold
+    TooLarge(u64),
+    /// skipped, a symlink leading out of the workspace
+    LinksOutside,
+}
+
+impl Reason {
+    pub fn is_mounted(&self) -> bool {
+        matches!(self, Reason::NotIgnored | Reason::Included(_) | Reason::Generated)
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NotIgnored => write!(f, "not ignored"),
+            Reason::Included(glob) => write!(f, "included by {}", glob),
+            Reason::Generated => write!(f, "generated"),
+            Reason::Excluded(path) => write!(f, "excluded by {}", path),
+            Reason::TooLarge(max) => write!(f, "larger than {} bytes", max),
+            Reason::LinksOutside => write!(f, "a symlink leading out of the workspace"),
+        }
+    }
+}
+
+/// A file or symlink of the workspace, the ignored ones aren't listed
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedFile {
+    /// relative to the workspace root, separated by `/`
+    pub path: String,
+    pub size: u64,
+    pub reason: Reason,
+}
+
+/// the directories never mounted: `.git` and caches like cargo's `target`, which are tagged
+/// https://bford.info/cachedir/
+fn is_skipped_dir(entry: &DirEntry) -> bool {
+    entry.file_type().is_some_and(|file_type| file_type.is_dir())
+        && (entry.file_name() == ".git" || entry.path().join("CACHEDIR.TAG").is_file())
+}
+
+/// files and symlinks, which are mounted as symlinks rather than the files they point to
+fn is_mountable(entry: &DirEntry) -> bool {
+    entry.file_type().is_some_and(|file_type| file_type.is_file() || file_type.is_symlink())
+}
+
+/// a relative symlink whose target stays inside the workspace, judged by its path
+/// as the target needn't be mounted, the server checks where it resolves
+fn links_inside(link: &Path, workspace_root: &Path) -> bool {
+    let target = match std::fs::read_link(link) {
+        Ok(target) if target.is_relative() => target,
+        _ => return false,
+    };
+    let mut resolved = link.parent().unwrap_or(workspace_root).to_path_buf();
+    for component in target.components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::ParentDir => { resolved.pop(); },
+            _ => {},
+        }
+    }
+    resolved.starts_with(workspace_root)
+}
+
+fn relative(path: &Path, workspace_root: &Path) -> Option<String> {
+    let relative = path.strip_prefix(workspace_root).ok()?;
+    Some(relative.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// the leading directories of a glob without wildcards, the include
+/// walk only enters the directories on the way to or below them
+fn literal_prefix(glob: &str) -> PathBuf {
+    glob.split('/')
+        .take_while(|component| !component.contains(['*', '?', '[', '{']))
+        .collect()
+}
+
+struct Includes {
+    globs: Vec<String>,
+    set: GlobSet,
+    prefixes: Vec<PathBuf>,
+}
+
+impl Includes {
+    fn new(globs: &[String]) -> Result<Includes, Error> {
+        let mut set = GlobSetBuilder::new();
+        for glob in globs {
+            // `*` stays within a directory as in .gitignore, `**` crosses them
+            let compiled: Glob = GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| anyhow!("invalid include glob {}: {}", glob, e))?;
+            set.add(compiled);
+        }
+        Ok(Includes {
+            globs: globs.to_vec(),
+            set: set.build()?,
+            prefixes: globs.iter().map(|glob| literal_prefix(glob)).collect(),
+        })
+    }
+
+    /// the first glob matching the path
+    fn matching(&self, path: &str) -> Option<&str> {
+        self.set.matches(path).first().map(|index| self.globs[*index].as_str())
+    }
+
+    /// a glob may match below the directory, caches are only entered
+    /// for the globs naming them
+    fn may_match_below(&self, dir: &Path, is_skipped: bool) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            prefix.starts_with(dir) || (!is_skipped && dir.starts_with(prefix))
+        })
+    }
+}
+
+/// the files of the workspace with why each is mounted or skipped, sorted by path
+pub fn select_files(workspace_root: &Path, rules: &MountRules) -> Result<Vec<SelectedFile>, Error> {
+    let includes = Includes::new(&rules.include)?;
+    let max_file_bytes = rules.max_file_bytes.unwrap_or(DEFAULT_MAX_FILE_BYTES);
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+
+    let walker = WalkBuilder::new(workspace_root)
+        .hidden(false)
+        .git_ignore(true)
+        .add_custom_ignore_filename(IGNORE_FILENAME)
+        .filter_entry(|entry| !is_skipped_dir(entry))
+        .build();
+    for entry in walker {
+        let entry = entry?;
+        if !is_mountable(&entry) {
+            continue;
+        }
+        let path = match relative(entry.path(), workspace_root) {
+            Some(path) => path,
+            None => continue,
+        };
+        let size = entry.metadata()?.len();
+        let excluded = rules.exclude.iter().find(|exclude| Path::new(&path).starts_with(exclude));
+        let is_symlink = entry.path_is_symlink();
+        let reason = match (includes.matching(&path), excluded) {
+            _ if is_symlink && !links_inside(entry.path(), workspace_root) => Reason::LinksOutside,
+            (Some(glob), _) => Reason::Included(glob.to_string()),
+            (None, Some(exclude)) => Reason::Excluded(exclude.clone()),
+            (None, None) if size > max_file_bytes => Reason::TooLarge(max_file_bytes),
+            (None, None) => Reason::NotIgnored,
+        };
+        seen.insert(path.clone());
+        files.push(SelectedFile { path, size, reason });
+    }
+
+    // the included files the ignore files hide
+    if !rules.include.is_empty() {
+        let includes = Arc::new(includes);
+        let root = workspace_root.to_path_buf();
+        let filter = includes.clone();
+        let walker = WalkBuilder::new(workspace_root)
+            .standard_filters(false)
+            .filter_entry(move |entry| {
+                if !entry.file_type().is_some_and(|file_type| file_type.is_dir()) {
+                    return true;
+                }
+                let dir = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                entry.file_name() != ".git" && filter.may_match_below(dir, is_skipped_dir(entry))
+            })
+            .build();
+        for entry in walker {
+            let entry = entry?;
+            if !is_mountable(&entry) {
+                continue;
+            }
+            let path = match relative(entry.path(), workspace_root) {
+                Some(path) if !seen.contains(&path) => path,
+                _ => continue,
+            };
+            if let Some(glob) = includes.matching(&path) {
+                let size = entry.metadata()?.len();
+                let reason = if entry.path_is_symlink() && !links_inside(entry.path(), workspace_root) {
+                    Reason::LinksOutside
+                } else {
+                    Reason::Included(glob.to_string())
+                };
+                files.push(SelectedFile { path, size, reason });
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
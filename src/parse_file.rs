@@ -1,5 +1,7 @@
 // for removing macro attributes and items from a string
-use syn::{visit_mut::VisitMut, Item, File};
+use syn::{visit_mut::VisitMut, Item, File, Visibility, parse_quote};
+use minimodal_proto::proto::minimodal::BuildOptions;
+use serde::{Serialize, Deserialize};
 
 pub struct MacroRemover {
     target_macros: Vec<String>,
@@ -46,4 +48,177 @@ pub fn remove_function(ast: &mut File, function_name: &str) -> () {
             true
         }
     });
-}
\ No newline at end of file
+}
+
+/// Makes the functions marked with one of `target_macros` and every module
+/// reachable from the crate root, so a generated entrypoint can call
+/// functions defined in private submodules.
+pub struct RemoteFunctionExposer {
+    target_macros: Vec<String>,
+}
+
+impl VisitMut for RemoteFunctionExposer {
+    fn visit_item_mut(&mut self, i: &mut Item) {
+        match i {
+            Item::Fn(item_fn) => {
+                let is_remote = item_fn.attrs.iter().any(|attr| self.target_macros.iter().any(|m| attr.path().is_ident(m)));
+                if is_remote && matches!(item_fn.vis, Visibility::Inherited) {
+                    item_fn.vis = parse_quote!(pub(crate));
+                }
+            },
+            Item::Mod(item_mod) => {
+                if matches!(item_mod.vis, Visibility::Inherited) {
+                    item_mod.vis = parse_quote!(pub(crate));
+                }
+            },
+            _ => {},
+        }
+        syn::visit_mut::visit_item_mut(self, i);
+    }
+}
+
+pub fn expose_remote_functions(ast: &mut File, target_macros: Vec<String>) {
+    let mut exposer = RemoteFunctionExposer { target_macros };
+    exposer.visit_file_mut(ast);
+}
+/// A function marked with one of the minimodal macros, as found in a source file
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RemoteFunction {
+    /// the inline modules the function is declared in, inside its file
+    pub modules: Vec<String>,
+    pub name: String,
+    /// the argument names, `arg{i}` for arguments bound to a pattern
+    pub args: Vec<String>,
+    pub is_async: bool,
+    /// the function has type or const parameters
+    pub is_generic: bool,
+    /// some argument is a reference or `impl Trait`
+    pub borrows: bool,
+    pub secrets: Vec<String>,
+    pub profile: String,
+    pub features: Vec<String>,
+    pub rustflags: String,
+    /// a cron expression, see `#[function(schedule = "0 3 * * *")]`
+    #[serde(default)]
+    pub schedule: String,
+    /// e.g. `15m`, see `#[function(period = "15m")]`
+    #[serde(default)]
+    pub period: String,
+    /// exposed over HTTP once deployed, see `#[function(web_endpoint)]`
+    #[serde(default)]
+    pub web_endpoint: bool,
+}
+
+impl RemoteFunction {
+    pub fn build_options(&self) -> BuildOptions {
+        BuildOptions {
+            profile: self.profile.clone(),
+            features: self.features.clone(),
+            rustflags: self.rustflags.clone(),
+        }
+    }
+
+    /// the path of the function below the crate root, e.g. `jobs::train`
+    pub fn path(&self) -> String {
+        self.modules.iter()
+            .chain(std::iter::once(&self.name))
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("::")
+    }
+}
+
+fn string_list(input: syn::parse::ParseStream) -> syn::Result<Vec<String>> {
+    let array: syn::ExprArray = input.parse()?;
+    array.elems.iter()
+        .map(|elem| match elem {
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => Ok(lit.value()),
+            elem => Err(syn::Error::new_spanned(elem, "expected a string literal")),
+        })
+        .collect()
+}
+
+/// reads the options of `#[function(...)]`, see `MacroArgs` of the macros crate
+fn parse_function_options(attr: &syn::Attribute, function: &mut RemoteFunction) -> syn::Result<()> {
+    if matches!(attr.meta, syn::Meta::Path(_)) {
+        return Ok(());
+    }
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("secrets") {
+            function.secrets = string_list(meta.value()?)?;
+        } else if meta.path.is_ident("features") {
+            function.features = string_list(meta.value()?)?;
+        } else if meta.path.is_ident("profile") {
+            function.profile = meta.value()?.parse::<syn::LitStr>()?.value();
+        } else if meta.path.is_ident("rustflags") {
+            function.rustflags = meta.value()?.parse::<syn::LitStr>()?.value();
+        } else if meta.path.is_ident("schedule") {
+            function.schedule = meta.value()?.parse::<syn::LitStr>()?.value();
+        } else if meta.path.is_ident("period") {
+            function.period = meta.value()?.parse::<syn::LitStr>()?.value();
+        } else if meta.path.is_ident("web_endpoint") {
+            function.web_endpoint = if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::LitBool>()?.value()
+            } else {
+                true
+            };
+        } else if meta.input.peek(syn::Token![=]) {
+            meta.value()?.parse::<syn::Expr>()?;
+        }
+        Ok(())
+    })
+}
+
+fn collect_remote_functions(
+    items: &[Item], 
+    modules: &[String], 
+    target_macros: &[String],
+    functions: &mut Vec<RemoteFunction>,
+) -> syn::Result<()> {
+    for item in items {
+        match item {
+            Item::Fn(item_fn) => {
+                let attr = match item_fn.attrs.iter().find(|attr| target_macros.iter().any(|m| attr.path().is_ident(m))) {
+                    Some(attr) => attr,
+                    None => continue,
+                };
+                let mut function = RemoteFunction {
+                    modules: modules.to_vec(),
+                    name: item_fn.sig.ident.to_string(),
+                    is_async: item_fn.sig.asyncness.is_some(),
+                    is_generic: item_fn.sig.generics.type_params().next().is_some()
+                        || item_fn.sig.generics.const_params().next().is_some(),
+                    ..Default::default()
+                };
+                for (i, arg) in item_fn.sig.inputs.iter().enumerate() {
+                    if let syn::FnArg::Typed(pat_ty) = arg {
+                        function.args.push(match &*pat_ty.pat {
+                            syn::Pat::Ident(pat_ident) if pat_ident.by_ref.is_none() && pat_ident.subpat.is_none() => pat_ident.ident.to_string(),
+                            _ => format!("arg{}", i),
+                        });
+                        function.borrows |= matches!(&*pat_ty.ty, syn::Type::Reference(_) | syn::Type::ImplTrait(_));
+                    }
+                }
+                parse_function_options(attr, &mut function)?;
+                functions.push(function);
+            },
+            Item::Mod(item_mod) => {
+                if let Some((_, content)) = &item_mod.content {
+                    let mut modules = modules.to_vec();
+                    modules.push(item_mod.ident.to_string());
+                    collect_remote_functions(content, &modules, target_macros, functions)?;
+                }
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+/// lists the functions marked with one of `target_macros`, including
+/// those in inline modules
+pub fn find_remote_functions(ast: &File, target_macros: &[String]) -> syn::Result<Vec<RemoteFunction>> {
+    let mut functions = Vec::new();
+    collect_remote_functions(&ast.items, &[], target_macros, &mut functions)?;
+    Ok(functions)
+}
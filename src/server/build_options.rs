@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+use anyhow::{Error, anyhow};
+use sha2::{Digest, Sha256};
+use minimodal_proto::proto::minimodal::BuildOptions;
+
+fn is_default(options: &BuildOptions) -> bool {
+    (options.profile.is_empty() || options.profile == "dev")
+        && options.features.is_empty()
+        && options.rustflags.is_empty()
+}
+
+pub fn validate(options: &BuildOptions) -> Result<(), Error> {
+    let valid_name = |name: &str| {
+        !name.is_empty()
+            && !name.starts_with('-')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/'))
+    };
+    if !options.profile.is_empty() && !valid_name(&options.profile) {
+        return Err(anyhow!("invalid profile {:?}", options.profile));
+    }
+    if let Some(feature) = options.features.iter().find(|feature| !valid_name(feature)) {
+        return Err(anyhow!("invalid feature {:?}", feature));
+    }
+    Ok(())
+}
+
+/// Key identifying the build artifacts for a set of options,
+/// builds with different options never share a target dir,
+/// stable across toolchains since it names directories kept between runs
+pub fn cache_key(options: &BuildOptions) -> String {
+    let mut features = options.features.clone();
+    features.sort();
+    features.dedup();
+
+    let encoded = serde_json::json!([options.profile, features, options.rustflags]).to_string();
+    let digest = Sha256::digest(encoded.as_bytes());
+    digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// the cargo target dir used for `options` relative to the project dir,
+/// the default build uses the project's regular target dir
+pub fn target_dir(options: &BuildOptions) -> PathBuf {
+    let target_dir = PathBuf::from("target");
+    if is_default(options) {
+        target_dir
+    } else {
+        target_dir.join(format!("minimodal-{}", cache_key(options)))
+    }
+}
+
+/// the directory cargo places the artifacts of the profile in
+fn profile_dir(options: &BuildOptions) -> &str {
+    match options.profile.as_str() {
+        "" | "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    }
+}
+
+/// where cargo puts the executable `bin`, relative to the project dir
+pub fn executable_path(options: &BuildOptions, bin: &str) -> PathBuf {
+    target_dir(options)
+        .join(profile_dir(options))
+        .join(format!("{}{}", bin, std::env::consts::EXE_SUFFIX))
+}
+
+/// a copy of an entrypoint made for a single call, see `copied_executable_path`
+fn is_copied_executable(path: &Path) -> bool {
+    let name = match path.file_stem().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    // `<bin>-<uuid>`, the uuid being 36 characters long
+    name.starts_with("__minimodal_")
+        && name.len() > 37
+        && name.is_char_boundary(name.len() - 37)
+        && name[name.len() - 37..].starts_with('-')
+        && uuid::Uuid::parse_str(&name[name.len() - 36..]).is_ok()
+}
+
+/// where the executable `built` is copied to for a single call,
+/// so the next build can't replace it while it runs
+pub fn copied_executable_path(built: &Path, bin: &str) -> PathBuf {
+    built.with_file_name(format!("{}-{}{}", bin, uuid::Uuid::new_v4(), std::env::consts::EXE_SUFFIX))
+}
+
+/// the copies of entrypoints left behind by calls which were
+/// cancelled or crashed, in every target dir of the project
+pub fn leftover_executables(project_dir: &Path) -> Vec<PathBuf> {
+    // target/<profile> and target/minimodal-<key>/<profile>
+    walkdir::WalkDir::new(project_dir.join("target"))
+        .max_depth(3)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && is_copied_executable(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// extra arguments passed to `cargo run` / `cargo build`
+pub fn cargo_args(options: &BuildOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    if !options.profile.is_empty() {
+        args.push("--profile".to_string());
+        args.push(options.profile.clone());
+    }
+    if !options.features.is_empty() {
+        args.push("--features".to_string());
+        args.push(options.features.join(","));
+    }
+    args
+}
+
+/// environment variables needed for the build
+pub fn cargo_envs(options: &BuildOptions) -> Vec<(String, String)> {
+    let mut envs = vec![(
+        "CARGO_TARGET_DIR".to_string(),
+        target_dir(options).to_string_lossy().to_string(),
+    )];
+    if !options.rustflags.is_empty() {
+        envs.push(("RUSTFLAGS".to_string(), options.rustflags.clone()));
+    }
+    envs
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{Error, anyhow};
+use serde::{Serialize, Deserialize};
+use minimodal_proto::proto::minimodal::BuildOptions;
+use crate::mount::MountTarget;
+use crate::parse_file::RemoteFunction;
+use crate::server::build_options;
+use crate::server::dispatcher::dispatcher_code;
+use crate::server::schedule::Schedule;
+
+/// where the dispatchers of a version are kept, relative to the version dir
+const BIN_DIR: &str = ".minimodal/bin";
+
+/// A function prebuilt into one of the dispatchers of a version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeployedFunction {
+    pub package: String,
+    pub crate_name: String,
+    pub is_bin: bool,
+    /// the path below the crate root, e.g. `jobs::train`
+    pub path: String,
+    pub secrets: Vec<String>,
+    /// the dispatcher calling the function, relative to the version dir
+    pub executable: String,
+    /// a cron expression, the scheduler runs the function while its version is active
+    #[serde(default)]
+    pub schedule: String,
+    /// e.g. `15m`, the alternative to `schedule`
+    #[serde(default)]
+    pub period: String,
+    /// the names of the arguments, in order
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// exposed as `POST /apps/<app>/<function>`
+    #[serde(default)]
+    pub web_endpoint: bool,
+}
+
+impl DeployedFunction {
+    /// e.g. `my_crate::jobs::train`
+    pub fn name(&self) -> String {
+        format!("{}::{}", self.crate_name, self.path)
+    }
+
+    /// `None` for functions which are only run on request
+    pub fn schedule(&self) -> Result<Option<Schedule>, Error> {
+        Schedule::parse(&self.schedule, &self.period)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub version: u32,
+    /// seconds since the unix epoch
+    pub created_at: u64,
+    pub functions: Vec<DeployedFunction>,
+    /// the functions which can't be prebuilt, with the reason
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct App {
+    pub active_version: u32,
+    pub versions: Vec<Deployment>,
+}
+
+impl App {
+    pub fn active(&self) -> Option<&Deployment> {
+        self.versions.iter().find(|deployment| deployment.version == self.active_version)
+    }
+}
+
+/// Stores apps under `deployments_dir`, one directory per app holding
+/// an `app.json` with its versions and one snapshot directory per version,
+/// so deployments survive restarts of the server.
+pub struct DeploymentStore {
+    deployments_dir: PathBuf,
+}
+
+impl DeploymentStore {
+    pub fn new(deployments_dir: impl Into<PathBuf>) -> Result<DeploymentStore, Error> {
+        let deployments_dir = deployments_dir.into();
+        fs::create_dir_all(&deployments_dir)?;
+        // builds run in the version dirs, paths handed to cargo must be absolute
+        let deployments_dir = fs::canonicalize(&deployments_dir)?;
+        Ok(DeploymentStore { deployments_dir })
+    }
+
+    /// holds the versions of `app` and the cargo target dirs they share
+    pub fn app_dir(&self, app: &str) -> Result<PathBuf, Error> {
+        validate_app_name(app)?;
+        Ok(self.deployments_dir.join(app))
+    }
+
+    pub fn version_dir(&self, app: &str, version: u32) -> Result<PathBuf, Error> {
+        Ok(self.app_dir(app)?.join(format!("v{}", version)))
+    }
+
+    pub fn get(&self, app: &str) -> Result<Option<App>, Error> {
+        let path = self.app_dir(app)?.join("app.json");
+        match fs::read(&path) {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, app: &str, content: &App) -> Result<(), Error> {
+        let path = self.app_dir(app)?.join("app.json");
+        fs::create_dir_all(self.app_dir(app)?)?;
+        // never leave the versions of an app half written
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(content)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// the version the next deployment of `app` gets, versions start at 1
+    pub fn next_version(&self, app: &str) -> Result<u32, Error> {
+        let app = self.get(app)?.unwrap_or_default();
+        Ok(app.versions.iter().map(|deployment| deployment.version).max().unwrap_or(0) + 1)
+    }
+
+    /// records a built version and makes it the active one
+    pub fn add(&self, app: &str, deployment: Deployment) -> Result<(), Error> {
+        let mut content = self.get(app)?.unwrap_or_default();
+        if content.versions.iter().any(|existing| existing.version == deployment.version) {
+            return Err(anyhow!("version {} of {} already exists", deployment.version, app));
+        }
+        content.active_version = deployment.version;
+        content.versions.push(deployment);
+        self.write(app, &content)
+    }
+
+    /// activates `version` of `app`, 0 being the version before the active one,
+    /// and returns the activated version
+    pub fn rollback(&self, app: &str, version: u32) -> Result<u32, Error> {
+        let mut content = self.get(app)?.ok_or(anyhow!("app {} is not deployed", app))?;
+        let version = if version == 0 {
+            content.versions.iter()
+                .map(|deployment| deployment.version)
+                .filter(|version| *version < content.active_version)
+                .max()
+                .ok_or(anyhow!("{} has no version before {}", app, content.active_version))?
+        } else {
+            version
+        };
+        if !content.versions.iter().any(|deployment| deployment.version == version) {
+            return Err(anyhow!("{} has no version {}", app, version));
+        }
+        content.active_version = version;
+        self.write(app, &content)?;
+        Ok(version)
+    }
+
+    /// every deployed app, by name
+    pub fn list(&self) -> Result<Vec<(String, App)>, Error> {
+        let mut apps = Vec::new();
+        for entry in fs::read_dir(&self.deployments_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if validate_app_name(&name).is_err() {
+                continue;
+            }
+            if let Some(app) = self.get(&name)? {
+                apps.push((name, app));
+            }
+        }
+        apps.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(apps)
+    }
+
+    /// the function of the active version of `app` exposed as a web endpoint
+    /// under `function`, its name or its path below the crate root
+    pub fn find_endpoint(&self, app: &str, function: &str) -> Result<(PathBuf, DeployedFunction), Error> {
+        let content = self.get(app)?.ok_or(anyhow!("app {} is not deployed", app))?;
+        let deployment = content.active()
+            .ok_or(anyhow!("the active version {} of {} is missing", content.active_version, app))?;
+        let mut endpoints = deployment.functions.iter()
+            .filter(|deployed| deployed.web_endpoint && (deployed.name() == function || deployed.path == function));
+        let endpoint = endpoints.next()
+            .ok_or(anyhow!("{} has no web endpoint {}", app, function))?;
+        if let Some(other) = endpoints.next() {
+            return Err(anyhow!("{} is ambiguous in {}, use {} or {}", function, app, endpoint.name(), other.name()));
+        }
+        Ok((self.version_dir(app, deployment.version)?, endpoint.clone()))
+    }
+
+    /// the function `path` of the active version of `app`, and the
+    /// directory of that version. `bin_name` is only set for functions
+    /// defined in a binary
+    pub fn find_function(
+        &self,
+        app: &str,
+        package: &str,
+        crate_name: &str,
+        bin_name: &str,
+        path: &str,
+    ) -> Result<(PathBuf, DeployedFunction), Error> {
+        let content = self.get(app)?.ok_or(anyhow!("app {} is not deployed", app))?;
+        let deployment = content.active()
+            .ok_or(anyhow!("the active version {} of {} is missing", content.active_version, app))?;
+        let function = deployment.functions.iter()
+            .find(|function| function.package == package
+                && function.crate_name == crate_name
+                && function.is_bin != bin_name.is_empty()
+                && function.path == path)
+            .ok_or(anyhow!("{}::{} is not deployed in version {} of {}", crate_name, path, deployment.version, app))?;
+        Ok((self.version_dir(app, deployment.version)?, function.clone()))
+    }
+}
+
+fn validate_app_name(name: &str) -> Result<(), Error> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid app name {:?}: only [a-zA-Z0-9_-] are allowed", name))
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// why `function` can't be prebuilt, the wire types of these are only
+/// known to the macro at the call site
+fn not_deployable(function: &RemoteFunction) -> Option<&'static str> {
+    if function.is_generic {
+        Some("generic functions are built for each call")
+    } else if function.borrows {
+        Some("functions taking references or `impl Trait` are built for each call")
+    } else {
+        None
+    }
+}
+
+/// Builds the functions of the project snapshot in `version_dir`, one
+/// dispatcher per target and set of build options. Only libraries and
+/// binaries are deployed, tests and examples are not. The cargo target dirs
+/// live in `app_dir` so the versions of an app share their dependencies.
+/// Returns the deployed functions and the ones which were skipped.
+pub async fn build_functions(
+    app_dir: &Path,
+    version_dir: &Path,
+    targets: &[MountTarget],
+) -> Result<(Vec<DeployedFunction>, Vec<String>), Error> {
+    let mut deployed = Vec::new();
+    let mut skipped = Vec::new();
+
+    for target in targets.iter().filter(|target| target.kind == "lib" || target.is_bin()) {
+        let mut groups: BTreeMap<String, (BuildOptions, Vec<RemoteFunction>)> = BTreeMap::new();
+        for function in target.functions.iter() {
+            let name = format!("{}::{}", target.crate_name, function.path());
+            let schedule = Schedule::parse(&function.schedule, &function.period)
+                .map_err(|e| anyhow!("{}: {}", name, e))?;
+            if let Some(reason) = not_deployable(function) {
+                if schedule.is_some() {
+                    return Err(anyhow!("{} is scheduled but can't be deployed: {}", name, reason));
+                }
+                if function.web_endpoint {
+                    return Err(anyhow!("{} is a web endpoint but can't be deployed: {}", name, reason));
+                }
+                skipped.push(format!("{}: {}", name, reason));
+                continue;
+            }
+            if schedule.is_some() && !function.args.is_empty() {
+                return Err(anyhow!("{} is scheduled, scheduled functions can't take arguments", name));
+            }
+            let options = function.build_options();
+            build_options::validate(&options)?;
+            groups.entry(build_options::cache_key(&options))
+                .or_insert_with(|| (options, Vec::new()))
+                .1
+                .push(function.clone());
+        }
+        if groups.is_empty() {
+            continue;
+        }
+
+        let original_code = fs::read_to_string(version_dir.join(&target.source))?;
+        for (key, (options, functions)) in groups {
+            let entry_path = version_dir.join(&target.entry);
+            fs::write(&entry_path, dispatcher_code(&original_code, &functions))?;
+
+            let cargo_target_dir = app_dir.join(build_options::target_dir(&options));
+            let build_output = tokio::process::Command::new("cargo")
+                .args(["build", "--package", &target.package, "--bin", &target.entry_bin])
+                .args(build_options::cargo_args(&options))
+                .current_dir(version_dir)
+                .envs(build_options::cargo_envs(&options))
+                .env("CARGO_TARGET_DIR", &cargo_target_dir)
+                .kill_on_drop(true)
+                .output()
+                .await?;
+            if !build_output.status.success() {
+                return Err(anyhow!(
+                    "cargo build of {} failed: {}",
+                    target.crate_name,
+                    String::from_utf8_lossy(&build_output.stderr)
+                ));
+            }
+
+            let built = app_dir.join(build_options::executable_path(&options, &target.entry_bin));
+            let executable = Path::new(BIN_DIR)
+                .join(format!("{}-{}{}", target.entry_bin, key, std::env::consts::EXE_SUFFIX));
+            fs::create_dir_all(version_dir.join(BIN_DIR))?;
+            fs::copy(&built, version_dir.join(&executable))?;
+
+            for function in functions {
+                deployed.push(DeployedFunction {
+                    package: target.package.clone(),
+                    crate_name: target.crate_name.clone(),
+                    is_bin: target.is_bin(),
+                    path: function.path(),
+                    secrets: function.secrets.clone(),
+                    executable: executable.to_string_lossy().to_string(),
+                    schedule: function.schedule.clone(),
+                    period: function.period.clone(),
+                    args: function.args.clone(),
+                    web_endpoint: function.web_endpoint,
+                });
+            }
+        }
+    }
+    Ok((deployed, skipped))
+}
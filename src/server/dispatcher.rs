@@ -0,0 +1,69 @@
+use crate::parse_file::RemoteFunction;
+use crate::utilities::declare_value_from_inputs;
+
+/// prints the result of a function between markers the server looks for
+pub const PRINT_RESULT: &str = r#"// Custom macro to print the result
+macro_rules! print_result {
+    ($result:expr) => {
+        let json_result = match $result {
+            Ok(value) => serde_json::json!({ "success": value }),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+        println!("RESULT_START{}RESULT_END", json_result);
+    }
+}
+"#;
+
+/// The entrypoint of a deployed target: the target's code and a `main`
+/// calling any of `functions`, chosen by the call read from stdin,
+/// e.g. `{"function": "jobs::train", "inputs": {"epochs": 3}}`.
+/// The functions must not be generic or borrow their arguments,
+/// the types of their arguments are inferred from the call.
+pub fn dispatcher_code(original_code: &str, functions: &[RemoteFunction]) -> String {
+    let arms: Vec<String> = functions.iter()
+        .map(|function| {
+            let declarations: Vec<String> = function.args.iter()
+                .map(|arg| declare_value_from_inputs(arg, None, false))
+                .collect();
+            format!(
+                r#"        "{path}" => {{
+            {declarations}
+            print_result!({path}({args}){await_call});
+        }},"#,
+                path = function.path(),
+                declarations = declarations.join("\n            "),
+                args = function.args.join(", "),
+                await_call = if function.is_async { ".await" } else { "" },
+            )
+        })
+        .collect();
+
+    format!(
+        r#"//imports
+
+{original_code}
+
+{print_result}
+// dispatches the call read from stdin to one of the deployed functions
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {{
+    let mut call = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut call)?;
+    let call: serde_json::Value = serde_json::from_str(&call)?;
+    let function = call.get("function")
+        .and_then(|function| function.as_str())
+        .ok_or("the call names no function")?;
+    let inputs = call.get("inputs").cloned().unwrap_or(serde_json::Value::Null);
+
+    match function {{
+{arms}
+        function => return Err(format!("{{}} is not deployed", function).into()),
+    }}
+    Ok(())
+}}
+"#,
+        original_code = original_code,
+        print_result = PRINT_RESULT,
+        arms = arms.join("\n"),
+    )
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde_json::{Value, json};
+use minimodal_proto::proto::minimodal::run_function_response::Response as RunFunctionResult;
+use crate::invoke::named_inputs;
+use crate::server::deployments::DeploymentStore;
+use crate::server::jobs::Jobs;
+use crate::server::metrics::Metrics;
+use crate::server::secrets::SecretStore;
+use crate::server::server::{start_deployed_job, DeployedJob};
+use crate::server::tokens::{Scope, TokenStore};
+
+/// What the web endpoints need from the server
+#[derive(Clone)]
+pub struct HttpState {
+    pub deployments: Arc<DeploymentStore>,
+    pub secrets: Arc<SecretStore>,
+    pub jobs: Arc<Jobs>,
+    pub metrics: Arc<Metrics>,
+    /// the tokens of the server, the calls need one with access to the app
+    pub tokens: Option<Arc<TokenStore>>,
+}
+
+/// Exposes the deployed `#[function(web_endpoint)]`s as
+/// `POST /apps/<app>/<function>`, where `<function>` is the name of the
+/// function, e.g. `my_crate::jobs::train`, or its path below the crate root.
+/// The body is a JSON object keyed by argument name or an array of the
+/// arguments, a successful call answers `{"result": ..., "job_id": ...}`
+/// and a failed one `{"error": {"kind": ..., "message": ..., "job_id": ...}}`.
+/// With tokens, the calls need an `Authorization: Bearer <token>` header.
+pub fn router(state: HttpState) -> Router {
+    Router::new()
+        .route("/apps/:app/:function", post(call_endpoint))
+        .with_state(state)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response {
+    (status, [(header::CONTENT_TYPE, "application/json")], body.to_string()).into_response()
+}
+
+fn error_response(status: StatusCode, kind: &str, message: &str, job_id: Option<&str>) -> Response {
+    json_response(status, json!({
+        "error": {
+            "kind": kind,
+            "message": message,
+            "job_id": job_id,
+        }
+    }))
+}
+
+async fn call_endpoint(
+    State(state): State<HttpState>,
+    Path((app, function)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let scope = match &state.tokens {
+        None => Scope::unrestricted(),
+        Some(tokens) => {
+            let authorization = headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+            match tokens.authenticate(authorization) {
+                Ok(scope) => scope,
+                Err(status) => return error_response(StatusCode::UNAUTHORIZED, "unauthenticated", status.message(), None),
+            }
+        },
+    };
+    if let Err(status) = scope.require_app(&app) {
+        return error_response(StatusCode::FORBIDDEN, "permission_denied", status.message(), None);
+    }
+    if state.jobs.is_closed() {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "shutting_down", "the server is shutting down", None);
+    }
+    let (version_dir, function) = match state.deployments.find_endpoint(&app, &function) {
+        Ok(endpoint) => endpoint,
+        Err(e) => return error_response(StatusCode::NOT_FOUND, "not_found", &e.to_string(), None),
+    };
+
+    // functions without arguments can be called with an empty body
+    let input = if body.iter().all(u8::is_ascii_whitespace) {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(input) => input,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, "invalid_json", &e.to_string(), None),
+        }
+    };
+    let inputs = match named_inputs(&function.name(), &function.args, input) {
+        Ok(inputs) => inputs,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, "invalid_arguments", &e.to_string(), None),
+    };
+
+    let DeployedJob { job_id, mut rx, .. } = start_deployed_job(
+        &app,
+        version_dir,
+        function,
+        Value::Object(inputs).to_string(),
+        "http",
+        &state.secrets,
+        &state.jobs,
+        &state.metrics,
+    );
+    let job = Some(job_id.as_str());
+    while let Some(response) = rx.recv().await {
+        let result = match response {
+            Ok(response) => match response.response {
+                Some(RunFunctionResult::Result(result)) => result,
+                _ => continue,
+            },
+            Err(status) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "cancelled", status.message(), job),
+        };
+        if !result.success {
+            // errors returned by the function are sent as JSON strings
+            let message = match serde_json::from_str::<Value>(&result.message) {
+                Ok(Value::String(message)) => message,
+                _ => result.message,
+            };
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "function_failed", &message, job);
+        }
+        return match serde_json::from_str::<Value>(&result.message) {
+            Ok(value) => json_response(StatusCode::OK, json!({ "result": value, "job_id": job_id })),
+            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "invalid_result", &e.to_string(), job),
+        };
+    }
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, "internal", "the function ended without a result", job)
+}
@@ -0,0 +1,397 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, Utc};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::AbortHandle;
+use tonic::Status;
+use minimodal_proto::proto::minimodal::{JobInfo, RunFunctionResponse, TaskResult};
+
+/// how long the results of submitted jobs are kept by default
+pub const DEFAULT_RESULT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// how many finished jobs which were not submitted are kept, with their logs
+pub const DEFAULT_JOB_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    /// waiting for the entrypoint of its target, built by another job
+    Queued,
+    Building,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Building => "building",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// What is known about a job, as listed by `ListJobs` and `GetJob`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobRecord {
+    pub id: String,
+    /// e.g. `my_crate::jobs::train`, or `prod/my_crate::jobs::train`
+    /// for a function deployed in the app prod
+    pub function: String,
+    pub status: JobStatus,
+    /// the mount the function is built from, or the deployed version
+    /// it runs, e.g. `prod/v3`
+    pub mount_id: String,
+    /// the address of the client, `scheduler` or `http`
+    pub caller: String,
+    pub created_at: DateTime<Utc>,
+    /// when it left the queue
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+fn seconds(time: Option<DateTime<Utc>>) -> u64 {
+    time.map_or(0, |time| time.timestamp().max(0) as u64)
+}
+
+impl JobRecord {
+    /// the app of a deployed function, `None` for a mounted one
+    pub fn app(&self) -> Option<&str> {
+        self.function.split_once('/').map(|(app, _)| app)
+    }
+
+    pub fn info(&self) -> JobInfo {
+        JobInfo {
+            id: self.id.clone(),
+            function: self.function.clone(),
+            status: self.status.to_string(),
+            mount_id: self.mount_id.clone(),
+            caller: self.caller.clone(),
+            created_at: seconds(Some(self.created_at)),
+            started_at: seconds(self.started_at),
+            finished_at: seconds(self.finished_at),
+        }
+    }
+}
+
+struct Job {
+    record: JobRecord,
+    logs: Vec<String>,
+    abort: Option<AbortHandle>,
+    /// the caller's stream, told when the job is cancelled
+    tx: Option<mpsc::Sender<Result<RunFunctionResponse, Status>>>,
+    /// the copy of the entrypoint the job runs
+    executable: Option<PathBuf>,
+    result: Option<TaskResult>,
+    /// woken when the job finishes or is cancelled
+    finished: Arc<Notify>,
+    /// the job was submitted, its result is kept for the retention period
+    retain_result: bool,
+}
+
+impl Job {
+    fn end(&mut self, status: JobStatus) {
+        self.record.status = status;
+        self.record.finished_at = Some(Utc::now());
+        self.finished.notify_waiters();
+    }
+}
+
+/// Every call of `RunFunction` and `SubmitFunction`, kept in start order
+/// until the finished ones are collected or expire
+pub struct Jobs {
+    jobs: Mutex<Vec<Job>>,
+    result_retention: Duration,
+    job_history: usize,
+    /// no new jobs are started, the server is shutting down
+    closed: AtomicBool,
+}
+
+impl Default for Jobs {
+    fn default() -> Jobs {
+        Jobs::with_result_retention(DEFAULT_RESULT_RETENTION)
+    }
+}
+
+impl Jobs {
+    pub fn new() -> Jobs {
+        Jobs::default()
+    }
+
+    pub fn with_result_retention(result_retention: Duration) -> Jobs {
+        Jobs {
+            jobs: Mutex::new(Vec::new()),
+            result_retention,
+            job_history: DEFAULT_JOB_HISTORY,
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// keeps at most `job_history` finished jobs which were not submitted
+    pub fn with_job_history(mut self, job_history: usize) -> Jobs {
+        self.job_history = job_history;
+        self
+    }
+
+    /// tells the callers of `start` to refuse new jobs
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn with_job<T>(&self, id: &str, f: impl FnOnce(&mut Job) -> T) -> Option<T> {
+        self.jobs.lock()
+            .unwrap()
+            .iter_mut()
+            .find(|job| job.record.id == id)
+            .map(f)
+    }
+
+    /// registers a queued job and returns its id
+    pub fn start(
+        &self,
+        function: String,
+        mount_id: String,
+        caller: String,
+        tx: mpsc::Sender<Result<RunFunctionResponse, Status>>,
+    ) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.jobs.lock().unwrap().push(Job {
+            record: JobRecord {
+                id: id.clone(),
+                function,
+                status: JobStatus::Queued,
+                mount_id,
+                caller,
+                created_at: Utc::now(),
+                started_at: None,
+                finished_at: None,
+            },
+            logs: Vec::new(),
+            abort: None,
+            tx: Some(tx),
+            executable: None,
+            result: None,
+            finished: Arc::new(Notify::new()),
+            retain_result: false,
+        });
+        id
+    }
+
+    /// keeps the result of the job for the retention period
+    /// once it finished, collect_finished leaves it alone until then
+    pub fn retain_result(&self, id: &str) {
+        self.with_job(id, |job| job.retain_result = true);
+    }
+
+    pub fn set_abort_handle(&self, id: &str, abort: AbortHandle) {
+        self.with_job(id, |job| job.abort = Some(abort));
+    }
+
+    pub fn set_executable(&self, id: &str, executable: Option<PathBuf>) {
+        self.with_job(id, |job| job.executable = executable);
+    }
+
+    pub fn log(&self, id: &str, line: &str) {
+        self.with_job(id, |job| job.logs.push(line.to_string()));
+    }
+
+    /// moves an unfinished job to `Building` or `Running`,
+    /// returns the job when its status changed
+    pub fn transition(&self, id: &str, status: JobStatus) -> Option<JobRecord> {
+        self.with_job(id, |job| {
+            if job.record.status.is_finished() || status.is_finished() || job.record.status == status {
+                return None;
+            }
+            job.record.status = status;
+            job.record.started_at.get_or_insert_with(Utc::now);
+            Some(job.record.clone())
+        })
+        .flatten()
+    }
+
+    /// a cancelled job stays cancelled, returns the job when it finished it
+    pub fn finish(&self, id: &str, success: bool) -> Option<JobRecord> {
+        self.with_job(id, |job| {
+            let finished = !job.record.status.is_finished();
+            if finished {
+                job.end(if success { JobStatus::Succeeded } else { JobStatus::Failed });
+            }
+            job.abort = None;
+            job.tx = None;
+            job.executable = None;
+            finished.then(|| job.record.clone())
+        })
+        .flatten()
+    }
+
+    /// finishes the job with the result sent to its caller
+    pub fn finish_with_result(&self, id: &str, result: &TaskResult) -> Option<JobRecord> {
+        self.with_job(id, |job| {
+            if !job.record.status.is_finished() {
+                job.result = Some(result.clone());
+            }
+        });
+        self.finish(id, result.success)
+    }
+
+    /// aborts a job which has not finished, which kills its build or process
+    pub fn cancel(&self, id: &str) -> Result<(), Error> {
+        self.with_job(id, |job| {
+            if job.record.status.is_finished() {
+                return Err(anyhow!("job {} already {}", id, job.record.status));
+            }
+            if let Some(abort) = job.abort.take() {
+                abort.abort();
+            }
+            // the stream ends with the cancellation rather than an event
+            if let Some(tx) = job.tx.take() {
+                let _ = tx.try_send(Err(Status::cancelled(format!("job {} was cancelled", id))));
+            }
+            job.end(JobStatus::Cancelled);
+            Ok(())
+        })
+        .unwrap_or_else(|| Err(anyhow!("job {} not found", id)))
+    }
+
+    /// every job, in start order
+    pub fn list(&self) -> Vec<JobRecord> {
+        self.jobs.lock()
+            .unwrap()
+            .iter()
+            .map(|job| job.record.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobRecord> {
+        self.with_job(id, |job| job.record.clone())
+    }
+
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        self.with_job(id, |job| job.record.status)
+    }
+
+    pub fn logs(&self, id: &str) -> Option<Vec<String>> {
+        self.with_job(id, |job| job.logs.clone())
+    }
+
+    /// the status of the job and its result once it succeeded or failed
+    pub fn result(&self, id: &str) -> Option<(JobStatus, Option<TaskResult>)> {
+        self.with_job(id, |job| (job.record.status, job.result.clone()))
+    }
+
+    /// the result of the job, waiting up to `timeout` for it to finish
+    pub async fn wait(&self, id: &str, timeout: Duration) -> Option<(JobStatus, Option<TaskResult>)> {
+        let finished = self.with_job(id, |job| job.finished.clone())?;
+        // created before looking at the status so a job finishing
+        // in between still wakes it
+        let notified = finished.notified();
+        if !self.status(id)?.is_finished() {
+            let _ = tokio::time::timeout(timeout, notified).await;
+        }
+        self.result(id)
+    }
+
+    /// finished within the retention period
+    fn is_recent(&self, job: &Job, now: DateTime<Utc>) -> bool {
+        // a clock set back keeps the job rather than dropping it
+        job.record.finished_at.is_some_and(|finished_at| {
+            (now - finished_at).to_std().map_or(true, |age| age < self.result_retention)
+        })
+    }
+
+    fn is_retained(&self, job: &Job, now: DateTime<Utc>) -> bool {
+        job.retain_result && self.is_recent(job, now)
+    }
+
+    /// forgets the finished jobs and returns their ids,
+    /// except the submitted ones whose results are still kept
+    pub fn collect_finished(&self) -> Vec<String> {
+        let now = Utc::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        let (keep, finished): (Vec<Job>, Vec<Job>) = jobs.drain(..)
+            .partition(|job| !job.record.status.is_finished() || self.is_retained(job, now));
+        *jobs = keep;
+        finished.into_iter().map(|job| job.record.id).collect()
+    }
+
+    /// forgets the finished jobs older than the retention period, and those which were not
+    /// submitted beyond the `job_history` most recent ones, and returns their ids
+    pub fn expire_results(&self) -> Vec<String> {
+        let now = Utc::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut history = 0;
+        let (mut keep, mut expired) = (Vec::new(), Vec::new());
+        // newest first, the history keeps the most recent jobs
+        for job in jobs.drain(..).rev() {
+            let kept = if !job.record.status.is_finished() {
+                true
+            } else if job.retain_result {
+                self.is_retained(&job, now)
+            } else {
+                history += 1;
+                history <= self.job_history && self.is_recent(&job, now)
+            };
+            if kept {
+                keep.push(job);
+            } else {
+                expired.push(job.record.id);
+            }
+        }
+        keep.reverse();
+        expired.reverse();
+        *jobs = keep;
+        expired
+    }
+
+    /// the ids of the jobs which have not finished
+    pub fn unfinished(&self) -> Vec<String> {
+        self.jobs.lock()
+            .unwrap()
+            .iter()
+            .filter(|job| !job.record.status.is_finished())
+            .map(|job| job.record.id.clone())
+            .collect()
+    }
+
+    /// waits up to `timeout` for the unfinished jobs to finish, then cancels
+    /// the others, which kills their builds and processes, and returns their ids
+    pub async fn drain(&self, timeout: Duration) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        for id in self.unfinished() {
+            self.wait(&id, deadline.saturating_duration_since(tokio::time::Instant::now())).await;
+        }
+        self.unfinished()
+            .into_iter()
+            .filter(|id| self.cancel(id).is_ok())
+            .collect()
+    }
+
+    pub fn executables_in_use(&self) -> Vec<PathBuf> {
+        self.jobs.lock()
+            .unwrap()
+            .iter()
+            .filter(|job| !job.record.status.is_finished())
+            .filter_map(|job| job.executable.clone())
+            .collect()
+    }
+}
@@ -1,9 +1,13 @@
 use tokio;
-use tonic::transport::Server;
 use std::env;
 use std::process::Command;
-use minimodal_proto::proto::minimodal::mini_modal_server::MiniModalServer;
-use minimodal_rs::server::server::MiniModalService;
+use minimodal_rs::server::server::{serve, ServeOptions, DEFAULT_SHUTDOWN_TIMEOUT};
+use minimodal_rs::server::schedule::parse_period;
+use minimodal_rs::server::jobs::DEFAULT_RESULT_RETENTION;
+use minimodal_rs::server::telemetry;
+use minimodal_rs::server::tls::ServerTls;
+use minimodal_rs::server::mounts::MountQuotas;
+use tracing::info;
 
 // Function to kill process using the port
 fn kill_process_on_port(port: u16) -> Result<(), std::io::Error> {
@@ -14,7 +18,7 @@ fn kill_process_on_port(port: u16) -> Result<(), std::io::Error> {
     if !output.stdout.is_empty() {
         let pid = String::from_utf8_lossy(&output.stdout).trim().to_string();
         Command::new("kill").arg("-9").arg(&pid).output()?;
-        println!("Killed process {} using port {}", pid, port);
+        info!("Killed process {} using port {}", pid, port);
     }
 
     Ok(())
@@ -22,27 +26,95 @@ fn kill_process_on_port(port: u16) -> Result<(), std::io::Error> {
 
 // run server
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let addr = "[::1]:50051".parse()?;
 
+    let args: Vec<String> = env::args().collect();
+    // e.g. http://localhost:4317
+    let otlp_endpoint = args.iter().position(|arg| arg == "-otlp-endpoint")
+        .and_then(|index| args.get(index + 1));
+    telemetry::init(otlp_endpoint.map(|s| s.as_str()))?;
+    if let Some(otlp_endpoint) = otlp_endpoint {
+        info!("📡 Exporting traces to {}", otlp_endpoint);
+    }
+
     // Kill process on port 50051 if active
     kill_process_on_port(50051)?;
 
-    let args: Vec<String> = env::args().collect();
     let dirname = args.iter().position(|arg| arg == "-dirname")
         .and_then(|index| args.get(index + 1))
         .map(|s| s.to_string())
         .unwrap_or_else(|| "src/server/shadow_dir".to_string());
-    println!("🔧 Shadow dir: {}", dirname);
-    let service = MiniModalService::new(dirname);
+    info!("🔧 Shadow dir: {}", dirname);
+    let secrets_dir = args.iter().position(|arg| arg == "-secrets-dir")
+        .and_then(|index| args.get(index + 1))
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "src/server/secrets".to_string());
+    info!("🔐 Secrets dir: {}", secrets_dir);
+    let deployments_dir = args.iter().position(|arg| arg == "-deployments-dir")
+        .and_then(|index| args.get(index + 1))
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "src/server/deployments".to_string());
+    info!("🚀 Deployments dir: {}", deployments_dir);
+    let http_addr = match args.iter().position(|arg| arg == "-http-addr").and_then(|index| args.get(index + 1)) {
+        Some(http_addr) => Some(http_addr.parse()?),
+        None => None,
+    };
+    // e.g. 30m or 7d
+    let result_retention = match args.iter().position(|arg| arg == "-result-retention").and_then(|index| args.get(index + 1)) {
+        Some(result_retention) => parse_period(result_retention)?,
+        None => DEFAULT_RESULT_RETENTION,
+    };
+    info!("🗄️ Results of submitted calls are kept for {:?}", result_retention);
+    // how long the jobs may run on after SIGTERM or SIGINT
+    let shutdown_timeout = match args.iter().position(|arg| arg == "-shutdown-timeout").and_then(|index| args.get(index + 1)) {
+        Some(shutdown_timeout) => parse_period(shutdown_timeout)?,
+        None => DEFAULT_SHUTDOWN_TIMEOUT,
+    };
+    let metrics_addr = match args.iter().position(|arg| arg == "-metrics-addr").and_then(|index| args.get(index + 1)) {
+        Some(metrics_addr) => Some(metrics_addr.parse()?),
+        None => None,
+    };
+    let tokens_file = args.iter().position(|arg| arg == "-tokens-file")
+        .and_then(|index| args.get(index + 1))
+        .map(|s| s.to_string());
+    if let Some(tokens_file) = &tokens_file {
+        info!("🔑 Tokens file: {}", tokens_file);
+    }
 
-    println!("🎬 Starting up minimodal server");
-    println!(" Listening on {}", addr);
+    let flag = |name: &str| args.iter().position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .map(std::path::PathBuf::from);
+    let tls = match (flag("-tls-cert"), flag("-tls-key")) {
+        (Some(cert), Some(key)) => Some(ServerTls { cert, key, client_ca: flag("-tls-client-ca") }),
+        (None, None) if flag("-tls-client-ca").is_some() => return Err("-tls-client-ca needs -tls-cert and -tls-key".into()),
+        (None, None) => None,
+        _ => return Err("-tls-cert and -tls-key must be given together".into()),
+    };
+    let defaults = MountQuotas::default();
+    let quota = |name: &str, default: usize| match args.iter().position(|arg| arg == name).and_then(|index| args.get(index + 1)) {
+        Some(quota) => quota.parse::<usize>().map_err(|e| format!("invalid {} {}: {}", name, quota, e)),
+        None => Ok(default),
+    };
+    let mount_quotas = MountQuotas {
+        max_files: quota("-max-mount-files", defaults.max_files)?,
+        max_file_bytes: quota("-max-mount-file-bytes", defaults.max_file_bytes)?,
+        max_total_bytes: quota("-max-mount-bytes", defaults.max_total_bytes)?,
+    };
+    info!("📦 Mounts may have {} files of {} bytes, {} bytes in total",
+        mount_quotas.max_files, mount_quotas.max_file_bytes, mount_quotas.max_total_bytes);
+
+    info!("🎬 Starting up minimodal server");
+    info!(" Listening on {}", addr);
+    if let Some(http_addr) = &http_addr {
+        info!("🌐 Web endpoints on http://{}", http_addr);
+    }
+    if let Some(metrics_addr) = &metrics_addr {
+        info!("📈 Metrics on http://{}/metrics", metrics_addr);
+    }
 
-    Server::builder()
-        .add_service(MiniModalServer::new(service))
-        .serve(addr)
-        .await?;
+    let options = ServeOptions { http_addr, metrics_addr, result_retention, tokens_file, tls, shutdown_timeout, mount_quotas };
+    serve(addr, dirname, secrets_dir, deployments_dir, options).await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use axum::{
+    Router,
+    extract::State,
+    http::header,
+    response::IntoResponse,
+    routing::get,
+};
+use crate::server::jobs::{Jobs, JobStatus};
+
+/// A metric which only goes up
+pub struct Counter {
+    pub name: &'static str,
+    pub help: &'static str,
+}
+
+/// A metric counting observations in buckets of upper bounds
+pub struct Histogram {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub buckets: &'static [f64],
+}
+
+const SECONDS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+pub const MOUNT_BYTES: Histogram = Histogram {
+    name: "minimodal_mount_bytes",
+    help: "Size of the files of each mount",
+    buckets: &[1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9],
+};
+pub const MOUNT_FILES: Histogram = Histogram {
+    name: "minimodal_mount_files",
+    help: "Number of files of each mount",
+    buckets: &[10.0, 100.0, 1000.0, 10000.0, 100000.0],
+};
+pub const BUILD_DURATION: Histogram = Histogram {
+    name: "minimodal_build_duration_seconds",
+    help: "Duration of the cargo builds of mounted functions",
+    buckets: SECONDS,
+};
+pub const BUILDS: Counter = Counter {
+    name: "minimodal_builds_total",
+    help: "Successful builds of mounted functions, a cache hit compiled nothing",
+};
+pub const DEPLOY_DURATION: Histogram = Histogram {
+    name: "minimodal_deploy_duration_seconds",
+    help: "Duration of the deployments, builds included",
+    buckets: SECONDS,
+};
+pub const RUN_DURATION: Histogram = Histogram {
+    name: "minimodal_run_duration_seconds",
+    help: "Duration of the runs of the functions, builds excluded",
+    buckets: SECONDS,
+};
+pub const JOBS_FINISHED: Counter = Counter {
+    name: "minimodal_jobs_finished_total",
+    help: "Finished jobs by function and status",
+};
+
+const COUNTERS: &[&Counter] = &[&BUILDS, &JOBS_FINISHED];
+const HISTOGRAMS: &[&Histogram] = &[&MOUNT_BYTES, &MOUNT_FILES, &BUILD_DURATION, &DEPLOY_DURATION, &RUN_DURATION];
+
+/// label names and values of a sample, e.g. `[("function", "jobs::train")]`
+type Labels = Vec<(&'static str, String)>;
+
+struct Buckets {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Samples {
+    /// by metric name and labels
+    counters: BTreeMap<(&'static str, Labels), f64>,
+    histograms: BTreeMap<(&'static str, Labels), Buckets>,
+}
+
+/// The counters and histograms of the server,
+/// exported in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    samples: Mutex<Samples>,
+}
+
+fn labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels.iter().map(|(name, value)| (*name, value.to_string())).collect()
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// `{a="1",b="2"}` with `extra` appended, nothing without labels
+fn format_labels(labels: &Labels, extra: Option<(&str, String)>) -> String {
+    let pairs: Vec<String> = labels.iter()
+        .map(|(name, value)| (*name, value.clone()))
+        .chain(extra)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(&value)))
+        .collect();
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn increment(&self, counter: &Counter, label_values: &[(&'static str, &str)]) {
+        let mut samples = self.samples.lock().unwrap();
+        *samples.counters.entry((counter.name, labels(label_values))).or_insert(0.0) += 1.0;
+    }
+
+    pub fn observe(&self, histogram: &Histogram, label_values: &[(&'static str, &str)], value: f64) {
+        let mut samples = self.samples.lock().unwrap();
+        let buckets = samples.histograms
+            .entry((histogram.name, labels(label_values)))
+            .or_insert_with(|| Buckets { counts: vec![0; histogram.buckets.len()], sum: 0.0, count: 0 });
+        for (count, bound) in buckets.counts.iter_mut().zip(histogram.buckets) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        buckets.sum += value;
+        buckets.count += 1;
+    }
+
+    /// the metrics in the Prometheus text format, with the `gauges`
+    /// measured at the time of the scrape as name, help and value
+    pub fn render(&self, gauges: &[(&str, &str, f64)]) -> String {
+        let samples = self.samples.lock().unwrap();
+        let mut text = String::new();
+        for (name, help, value) in gauges {
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+        }
+        for counter in COUNTERS {
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} counter", counter.name, counter.help, counter.name);
+            for ((_, labels), value) in samples.counters.range((counter.name, Vec::new())..).take_while(|((name, _), _)| *name == counter.name) {
+                let _ = writeln!(text, "{}{} {}", counter.name, format_labels(labels, None), value);
+            }
+        }
+        for histogram in HISTOGRAMS {
+            let name = histogram.name;
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} histogram", name, histogram.help, name);
+            for ((_, labels), buckets) in samples.histograms.range((name, Vec::new())..).take_while(|((other, _), _)| *other == name) {
+                // the counts are cumulative, each bucket counts the values up to its bound
+                for (count, bound) in buckets.counts.iter().zip(histogram.buckets) {
+                    let _ = writeln!(text, "{}_bucket{} {}", name, format_labels(labels, Some(("le", bound.to_string()))), count);
+                }
+                let _ = writeln!(text, "{}_bucket{} {}", name, format_labels(labels, Some(("le", "+Inf".to_string()))), buckets.count);
+                let _ = writeln!(text, "{}_sum{} {}", name, format_labels(labels, None), buckets.sum);
+                let _ = writeln!(text, "{}_count{} {}", name, format_labels(labels, None), buckets.count);
+            }
+        }
+        text
+    }
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    metrics: Arc<Metrics>,
+    jobs: Arc<Jobs>,
+}
+
+/// Serves the metrics as `GET /metrics`, with the depth of the
+/// queue and the number of active workers taken from the jobs
+pub fn router(metrics: Arc<Metrics>, jobs: Arc<Jobs>) -> Router {
+    Router::new()
+        .route("/metrics", get(scrape))
+        .with_state(MetricsState { metrics, jobs })
+}
+
+async fn scrape(State(state): State<MetricsState>) -> impl IntoResponse {
+    let jobs = state.jobs.list();
+    let count = |statuses: &[JobStatus]| jobs.iter().filter(|job| statuses.contains(&job.status)).count() as f64;
+    let text = state.metrics.render(&[
+        ("minimodal_jobs_queued", "Jobs waiting for the build of another job", count(&[JobStatus::Queued])),
+        ("minimodal_jobs_active", "Jobs building or running", count(&[JobStatus::Building, JobStatus::Running])),
+    ]);
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text)
+}
@@ -1 +1,15 @@
 pub mod server;
+pub mod secrets;
+pub mod build_options;
+pub mod type_names;
+pub mod jobs;
+pub mod dispatcher;
+pub mod deployments;
+pub mod schedule;
+pub mod scheduler;
+pub mod http;
+pub mod metrics;
+pub mod telemetry;
+pub mod tokens;
+pub mod tls;
+pub mod mounts;
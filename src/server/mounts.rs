@@ -0,0 +1,355 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tonic::Status;
+use minimodal_proto::proto::minimodal::FileEntry;
+
+/// The limits of a mount or a deployment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MountQuotas {
+    pub max_files: usize,
+    pub max_file_bytes: usize,
+    pub max_total_bytes: usize,
+}
+
+impl Default for MountQuotas {
+    fn default() -> MountQuotas {
+        MountQuotas {
+            max_files: 10_000,
+            max_file_bytes: 16 * 1024 * 1024,
+            max_total_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl MountQuotas {
+    /// the largest request the server decodes: twice a mount within the quotas, with room
+    /// for the paths, so a mount over them is told which quota it exceeds, rather than
+    /// being cut off by the transport as `OutOfRange`
+    pub fn max_message_bytes(&self) -> usize {
+        self.max_total_bytes.saturating_mul(2).saturating_add(self.max_files.saturating_mul(4 * 1024))
+    }
+}
+
+/// the path of a mounted file relative to the mount, which must stay inside of it:
+/// not empty, not absolute and without `..`
+#[allow(clippy::result_large_err)]
+pub fn validate_path(file_path: &str) -> Result<PathBuf, Status> {
+    let invalid = |reason: &str| Status::invalid_argument(format!("invalid path {:?}: {}", file_path, reason));
+    if file_path.contains('\0') {
+        return Err(invalid("contains a NUL byte"));
+    }
+    // `\` separates on windows and is rejected everywhere so a mount means the same on every server
+    if file_path.contains('\\') {
+        return Err(invalid("contains a backslash"));
+    }
+    let mut path = PathBuf::new();
+    for component in Path::new(file_path).components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => {},
+            Component::ParentDir => return Err(invalid("contains `..`")),
+            Component::RootDir | Component::Prefix(_) => return Err(invalid("is absolute")),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(invalid("is empty"));
+    }
+    Ok(path)
+}
+
+/// A file or symlink of a mount, at a validated path
+#[derive(Debug, Clone, PartialEq)]
+pub enum MountEntry {
+    /// `mode` holds the permission bits, files sent without are written with the default ones
+    File { path: PathBuf, mode: Option<u32>, content: Vec<u8> },
+    /// the target is relative, and must resolve inside the mount if it exists
+    Symlink { path: PathBuf, target: PathBuf },
+}
+
+impl MountEntry {
+    pub fn path(&self) -> &Path {
+        match self {
+            MountEntry::File { path, .. } | MountEntry::Symlink { path, .. } => path,
+        }
+    }
+
+    /// the bytes of a file, nothing for a symlink
+    pub fn bytes(&self) -> usize {
+        match self {
+            MountEntry::File { content, .. } => content.len(),
+            MountEntry::Symlink { .. } => 0,
+        }
+    }
+}
+
+/// rejects the paths leaving the mount and the mounts exceeding the quotas
+#[allow(clippy::result_large_err)]
+pub fn check_files(files: &[FileEntry], quotas: &MountQuotas) -> Result<(), Status> {
+    if files.len() > quotas.max_files {
+        return Err(Status::resource_exhausted(format!(
+            "the mount has {} files, at most {} are allowed", files.len(), quotas.max_files,
+        )));
+    }
+    let mut total_bytes = 0usize;
+    for file in files {
+        validate_path(&file.file_path)?;
+        if file.content.len() > quotas.max_file_bytes {
+            return Err(Status::resource_exhausted(format!(
+                "{} has {} bytes, at most {} are allowed per file", file.file_path, file.content.len(), quotas.max_file_bytes,
+            )));
+        }
+        total_bytes += file.content.len();
+    }
+    if total_bytes > quotas.max_total_bytes {
+        return Err(Status::resource_exhausted(format!(
+            "the mount has {} bytes, at most {} are allowed", total_bytes, quotas.max_total_bytes,
+        )));
+    }
+    Ok(())
+}
+
+/// counts the entries of a mount against the quotas as they are unpacked
+struct QuotaCheck<'a> {
+    quotas: &'a MountQuotas,
+    files: usize,
+    total_bytes: usize,
+}
+
+impl QuotaCheck<'_> {
+    #[allow(clippy::result_large_err)]
+    fn add(&mut self, path: &Path, bytes: u64) -> Result<(), Status> {
+        self.files += 1;
+        if self.files > self.quotas.max_files {
+            return Err(Status::resource_exhausted(format!(
+                "the mount has more than {} files, at most {} are allowed", self.quotas.max_files, self.quotas.max_files,
+            )));
+        }
+        if bytes > self.quotas.max_file_bytes as u64 {
+            return Err(Status::resource_exhausted(format!(
+                "{} has {} bytes, at most {} are allowed per file", path.display(), bytes, self.quotas.max_file_bytes,
+            )));
+        }
+        self.total_bytes = self.total_bytes.saturating_add(bytes as usize);
+        if self.total_bytes > self.quotas.max_total_bytes {
+            return Err(Status::resource_exhausted(format!(
+                "the mount has more than {} bytes, at most {} are allowed", self.quotas.max_total_bytes, self.quotas.max_total_bytes,
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// the files and symlinks of a zstd compressed tar, checked against the quotas before any
+/// is written: regular files keep their permission bits, without setuid, setgid and sticky,
+/// symlinks must be relative, directories are created as needed and other entries rejected
+#[allow(clippy::result_large_err)]
+pub fn read_archive(archive: &[u8], quotas: &MountQuotas) -> Result<Vec<MountEntry>, Status> {
+    let invalid = |e: std::io::Error| Status::invalid_argument(format!("invalid archive: {}", e));
+    let decoder = zstd::Decoder::new(archive).map_err(invalid)?;
+    // the headers are read into memory as well, so the whole archive is bounded
+    let mut archive = tar::Archive::new(decoder.take(quotas.max_message_bytes() as u64));
+    let mut check = QuotaCheck { quotas, files: 0, total_bytes: 0 };
+    let mut entries = Vec::new();
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        let file_path = entry.path().map_err(invalid)?.to_string_lossy().to_string();
+        let path = validate_path(&file_path)?;
+        match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                check.add(&path, entry.size())?;
+                let mode = entry.header().mode().map_err(invalid)? & 0o777;
+                let mut content = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut content).map_err(invalid)?;
+                entries.push(MountEntry::File { path, mode: Some(mode), content });
+            },
+            tar::EntryType::Symlink => {
+                check.add(&path, 0)?;
+                let target = entry.link_name().map_err(invalid)?
+                    .ok_or_else(|| Status::invalid_argument(format!("the symlink {:?} has no target", file_path)))?
+                    .to_path_buf();
+                if !target.is_relative() || target.as_os_str().is_empty() {
+                    return Err(Status::invalid_argument(format!(
+                        "the symlink {:?} must have a relative target, not {}", file_path, target.display(),
+                    )));
+                }
+                if !links_inside(&path, &target) {
+                    return Err(Status::invalid_argument(format!(
+                        "the symlink {:?} leads out of the mount to {}", file_path, target.display(),
+                    )));
+                }
+                entries.push(MountEntry::Symlink { path, target });
+            },
+            tar::EntryType::Directory => {},
+            other => return Err(Status::invalid_argument(format!(
+                "{:?} is a {:?}, only files, directories and symlinks can be mounted", file_path, other,
+            ))),
+        }
+    }
+    Ok(entries)
+}
+
+/// the relative `target` of the symlink at `path`, relative to the mount,
+/// resolved lexically against the symlink's directory stays inside the mount
+fn links_inside(path: &Path, target: &Path) -> bool {
+    let mut depth = path.components().count() - 1;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir if depth == 0 => return false,
+            Component::ParentDir => depth -= 1,
+            _ => {},
+        }
+    }
+    true
+}
+
+/// `path` with the symlinks of its existing part followed and the rest resolved
+/// lexically, where a dangling symlink leads to once its target is created
+fn resolve_existing(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => { resolved.pop(); },
+            Component::CurDir => {},
+            component => resolved.push(component),
+        }
+        if let Ok(canonical) = resolved.canonicalize() {
+            resolved = canonical;
+        }
+    }
+    resolved
+}
+
+/// the existing part of `path` must resolve inside `root`, a symlink left
+/// in the directory must not redirect a write outside of it
+#[allow(clippy::result_large_err)]
+fn check_inside(root: &Path, path: &Path, file_path: &str) -> Result<(), Status> {
+    let escape = || Status::invalid_argument(format!("invalid path {:?}: a symlink leads out of the mount", file_path));
+    let existing = path.ancestors()
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .unwrap_or(root);
+    let resolved = match existing.canonicalize() {
+        Ok(resolved) => resolved,
+        // writing through a dangling symlink creates its target, wherever it is
+        Err(_) if existing.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink()) => return Err(escape()),
+        Err(e) => return Err(Status::internal(format!("failed to resolve {}: {}", existing.display(), e))),
+    };
+    if resolved.starts_with(root) {
+        Ok(())
+    } else {
+        Err(escape())
+    }
+}
+
+/// the entries of a `MountProjectRequest` or `DeployAppRequest`
+#[allow(clippy::result_large_err)]
+pub fn file_entries(files: Vec<FileEntry>) -> Result<Vec<MountEntry>, Status> {
+    files.into_iter()
+        .map(|file| Ok(MountEntry::File { path: validate_path(&file.file_path)?, mode: None, content: file.content }))
+        .collect()
+}
+
+/// writes the files below `dir`, which is created if needed
+#[allow(clippy::result_large_err)]
+pub fn write_files(dir: &Path, files: Vec<FileEntry>) -> Result<(), Status> {
+    fs::create_dir_all(dir)
+        .map_err(|e| Status::internal(format!("failed to create {}: {}", dir.display(), e)))?;
+    let root = dir.canonicalize()
+        .map_err(|e| Status::internal(format!("failed to resolve {}: {}", dir.display(), e)))?;
+    for file_entry in files.into_iter() {
+        let file_path = root.join(validate_path(&file_entry.file_path)?);
+        check_inside(&root, &file_path, &file_entry.file_path)?;
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Status::internal(format!("failed to create directories: {}", e)))?;
+        }
+        fs::write(&file_path, file_entry.content)
+            .map_err(|e| Status::internal(format!("failed to write {}: {}", file_entry.file_path, e)))?;
+    }
+    Ok(())
+}
+
+/// replaces what a previous mount left at the path, a symlink
+/// would otherwise be written through
+#[allow(clippy::result_large_err)]
+fn remove_previous(path: &Path) -> Result<(), Status> {
+    let removed = match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(_) => return Ok(()),
+    };
+    removed.map_err(|e| Status::internal(format!("failed to replace {}: {}", path.display(), e)))
+}
+
+#[cfg(unix)]
+#[allow(clippy::result_large_err)]
+fn create_symlink(target: &Path, path: &Path) -> Result<(), Status> {
+    std::os::unix::fs::symlink(target, path)
+        .map_err(|e| Status::internal(format!("failed to create the symlink {}: {}", path.display(), e)))
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::result_large_err)]
+fn create_symlink(_target: &Path, path: &Path) -> Result<(), Status> {
+    Err(Status::unimplemented(format!("{}: symlinks are only mounted on unix servers", path.display())))
+}
+
+/// writes the files and then the symlinks of an archive below `dir`, which is created if
+/// needed, replacing what is at their paths rather than writing through a symlink left
+/// there; each symlink must resolve inside of it once all are written, even when dangling,
+/// or none of the entries is left behind
+#[allow(clippy::result_large_err)]
+pub fn write_entries(dir: &Path, entries: Vec<MountEntry>) -> Result<(), Status> {
+    fs::create_dir_all(dir)
+        .map_err(|e| Status::internal(format!("failed to create {}: {}", dir.display(), e)))?;
+    let root = dir.canonicalize()
+        .map_err(|e| Status::internal(format!("failed to resolve {}: {}", dir.display(), e)))?;
+    let (files, symlinks): (Vec<MountEntry>, Vec<MountEntry>) = entries.into_iter()
+        .partition(|entry| matches!(entry, MountEntry::File { .. }));
+    let mut written_files = Vec::new();
+    let mut written_symlinks = Vec::new();
+    for entry in files.into_iter().chain(symlinks) {
+        let name = entry.path().to_string_lossy().to_string();
+        let file_path = root.join(entry.path());
+        if let Some(parent) = file_path.parent() {
+            check_inside(&root, parent, &name)?;
+            fs::create_dir_all(parent)
+                .map_err(|e| Status::internal(format!("failed to create directories: {}", e)))?;
+        }
+        remove_previous(&file_path)?;
+        match entry {
+            MountEntry::File { mode, content, .. } => {
+                fs::write(&file_path, content)
+                    .map_err(|e| Status::internal(format!("failed to write {}: {}", name, e)))?;
+                #[cfg(unix)]
+                if let Some(mode) = mode {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&file_path, fs::Permissions::from_mode(mode & 0o777))
+                        .map_err(|e| Status::internal(format!("failed to set the mode of {}: {}", name, e)))?;
+                }
+                #[cfg(not(unix))]
+                let _ = mode;
+                written_files.push(file_path);
+            },
+            MountEntry::Symlink { target, .. } => {
+                create_symlink(&target, &file_path)?;
+                written_symlinks.push((file_path, target, name));
+            },
+        }
+    }
+    // a symlink may lead through the others, so they are resolved once all exist,
+    // a dangling one as far as its target exists
+    let escaping = written_symlinks.iter().find(|(file_path, target, _)| {
+        let parent = file_path.parent().unwrap_or(&root);
+        !resolve_existing(&parent.join(target)).starts_with(&root)
+    });
+    if let Some((_, _, name)) = escaping {
+        // the symlinks first, so removing the files doesn't go through them
+        for file_path in written_symlinks.iter().map(|(file_path, _, _)| file_path).chain(&written_files) {
+            let _ = fs::remove_file(file_path);
+        }
+        return Err(Status::invalid_argument(format!("invalid path {:?}: the symlink leads out of the mount", name)));
+    }
+    Ok(())
+}
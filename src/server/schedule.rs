@@ -0,0 +1,186 @@
+use std::time::Duration;
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+
+/// When a deployed function is triggered, from
+/// `#[function(schedule = "0 3 * * *")]` or `#[function(period = "15m")]`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Cron(Cron),
+    Every(Duration),
+}
+
+impl Schedule {
+    /// `None` when neither is given, both can't be given at once
+    pub fn parse(schedule: &str, period: &str) -> Result<Option<Schedule>, Error> {
+        match (schedule.trim(), period.trim()) {
+            ("", "") => Ok(None),
+            (schedule, "") => Ok(Some(Schedule::Cron(Cron::parse(schedule)?))),
+            ("", period) => Ok(Some(Schedule::Every(parse_period(period)?))),
+            _ => Err(anyhow!("a function has either a schedule or a period, not both")),
+        }
+    }
+
+    /// the first time the function is due strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Cron(cron) => cron.next_after(after),
+            Schedule::Every(period) => ChronoDuration::from_std(*period)
+                .ok()
+                .and_then(|period| after.checked_add_signed(period)),
+        }
+    }
+}
+
+/// a duration like `30s`, `15m`, `1h30m` or `1d`
+pub fn parse_period(period: &str) -> Result<Duration, Error> {
+    let invalid = || anyhow!("invalid period {:?}, expected e.g. 30s, 15m, 1h30m or 1d", period);
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in period.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        seconds = value.checked_mul(unit)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// A standard five field cron expression, `minute hour day-of-month month
+/// day-of-week`, evaluated in UTC. Fields take `*`, values, ranges `a-b`,
+/// steps `*/n` or `a-b/n` and lists of those separated by commas.
+/// Sunday is 0 or 7.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    /// whether the day of month and day of week fields are restricted,
+    /// when both are a day matching either of them is due
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, Error> {
+    let invalid = || anyhow!("invalid cron field {:?}, values go from {} to {}", field, min, max);
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse::<u32>().map_err(|_| invalid())?,
+                    end.parse::<u32>().map_err(|_| invalid())?,
+                ),
+                // `5/15` runs from 5 to the end of the range
+                None => {
+                    let start = range.parse::<u32>().map_err(|_| invalid())?;
+                    (start, if part.contains('/') { max } else { start })
+                },
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort();
+    values.dedup();
+    Ok(values)
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Cron, Error> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow!(
+                "invalid schedule {:?}, expected five fields: minute hour day-of-month month day-of-week",
+                expression
+            ));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // 7 is another name for sunday
+        if weekdays.contains(&7) {
+            weekdays.retain(|weekday| *weekday != 7);
+            if !weekdays.contains(&0) {
+                weekdays.insert(0, 0);
+            }
+        }
+        Ok(Cron {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = self.days.contains(&time.day());
+        let weekday = self.weekdays.contains(&time.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    /// the first matching minute strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after
+            .with_second(0)?
+            .with_nanosecond(0)?
+            .checked_add_signed(ChronoDuration::minutes(1))?;
+        // every field repeats within a few years, expressions like
+        // `0 0 30 2 *` never match
+        let give_up = time.checked_add_signed(ChronoDuration::days(5 * 366))?;
+        while time < give_up {
+            if !self.months.contains(&time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+                continue;
+            }
+            if !self.day_matches(&time) {
+                time = time.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+                continue;
+            }
+            if !self.hours.contains(&time.hour()) {
+                time = time.with_minute(0)?.checked_add_signed(ChronoDuration::hours(1))?;
+                continue;
+            }
+            if !self.minutes.contains(&time.minute()) {
+                time = time.checked_add_signed(ChronoDuration::minutes(1))?;
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use crate::server::jobs::JobStatus;
+use crate::server::schedule::Schedule;
+
+/// the runs kept in the history, the oldest are forgotten first
+const MAX_RUNS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+    /// the run was due while the previous one was still running
+    Skipped,
+    Job(JobStatus),
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStatus::Skipped => write!(f, "skipped"),
+            RunStatus::Job(status) => write!(f, "{}", status),
+        }
+    }
+}
+
+/// A trigger of a scheduled function, with the outcome of its job
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledRun {
+    pub app: String,
+    /// e.g. `my_crate::jobs::nightly`
+    pub function: String,
+    pub version: u32,
+    /// empty for skipped runs
+    pub job_id: String,
+    pub scheduled_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    pub logs: Vec<String>,
+}
+
+struct Entry {
+    schedule: Schedule,
+    next: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// by app and function
+    entries: HashMap<(String, String), Entry>,
+    /// the app and function of the runs started and not finished yet,
+    /// kept apart from the history which forgets the oldest runs
+    running: HashSet<(String, String)>,
+    runs: Vec<ScheduledRun>,
+}
+
+/// Decides when the scheduled functions of the active versions are due
+/// and keeps the history of their runs. A function is never run twice at
+/// the same time, a run due while the previous one is running is skipped.
+#[derive(Default)]
+pub struct Scheduler {
+    state: Mutex<State>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    /// the time the function was due at if it is due at `now`. A function
+    /// seen for the first time or whose schedule changed is first due
+    /// after `now`, runs missed while the server was down are not made up
+    pub fn due(&self, app: &str, function: &str, schedule: &Schedule, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.entry((app.to_string(), function.to_string()))
+            .or_insert_with(|| Entry { schedule: schedule.clone(), next: schedule.next_after(now) });
+        if entry.schedule != *schedule {
+            *entry = Entry { schedule: schedule.clone(), next: schedule.next_after(now) };
+        }
+        match entry.next {
+            Some(next) if next <= now => {
+                entry.next = schedule.next_after(now);
+                Some(next)
+            },
+            _ => None,
+        }
+    }
+
+    /// forgets the functions which are no longer scheduled
+    pub fn retain(&self, scheduled: &[(String, String)]) {
+        self.state.lock().unwrap().entries.retain(|key, _| scheduled.contains(key));
+    }
+
+    pub fn is_running(&self, app: &str, function: &str) -> bool {
+        self.state.lock().unwrap().running.contains(&(app.to_string(), function.to_string()))
+    }
+
+    fn push(&self, run: ScheduledRun) {
+        let mut state = self.state.lock().unwrap();
+        state.runs.push(run);
+        if state.runs.len() > MAX_RUNS {
+            let excess = state.runs.len() - MAX_RUNS;
+            state.runs.drain(..excess);
+        }
+    }
+
+    pub fn start(&self, app: &str, function: &str, version: u32, job_id: &str, scheduled_at: DateTime<Utc>) {
+        self.state.lock().unwrap().running.insert((app.to_string(), function.to_string()));
+        self.push(ScheduledRun {
+            app: app.to_string(),
+            function: function.to_string(),
+            version,
+            job_id: job_id.to_string(),
+            scheduled_at,
+            finished_at: None,
+            status: RunStatus::Job(JobStatus::Running),
+            logs: Vec::new(),
+        });
+    }
+
+    pub fn skip(&self, app: &str, function: &str, version: u32, scheduled_at: DateTime<Utc>) {
+        self.push(ScheduledRun {
+            app: app.to_string(),
+            function: function.to_string(),
+            version,
+            job_id: String::new(),
+            scheduled_at,
+            finished_at: Some(scheduled_at),
+            status: RunStatus::Skipped,
+            logs: vec!["the previous run was still running".to_string()],
+        });
+    }
+
+    /// records the outcome of the job of a run of `function` of `app`
+    pub fn finish(&self, app: &str, function: &str, job_id: &str, status: JobStatus, logs: Vec<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.running.remove(&(app.to_string(), function.to_string()));
+        if let Some(run) = state.runs.iter_mut().find(|run| run.job_id == job_id) {
+            run.status = RunStatus::Job(status);
+            run.finished_at = Some(Utc::now());
+            run.logs = logs;
+        }
+    }
+
+    /// the most recent runs first, empty `app` or `function` match any,
+    /// a `limit` of 0 returns every run
+    pub fn runs(&self, app: &str, function: &str, limit: usize) -> Vec<ScheduledRun> {
+        let state = self.state.lock().unwrap();
+        let runs = state.runs
+            .iter()
+            .rev()
+            .filter(|run| (app.is_empty() || run.app == app) && (function.is_empty() || run.function == function))
+            .cloned();
+        if limit == 0 {
+            runs.collect()
+        } else {
+            runs.take(limit).collect()
+        }
+    }
+}
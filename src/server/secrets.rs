@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Error, anyhow};
+
+/// Stores secrets as json files under `secrets_dir`,
+/// one file per secret, readable only by the server user.
+pub struct SecretStore {
+    secrets_dir: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(secrets_dir: impl Into<PathBuf>) -> Result<SecretStore, Error> {
+        let secrets_dir = secrets_dir.into();
+        fs::create_dir_all(&secrets_dir)?;
+        restrict_permissions(&secrets_dir, 0o700)?;
+        Ok(SecretStore { secrets_dir })
+    }
+
+    fn secret_path(&self, name: &str) -> Result<PathBuf, Error> {
+        validate_secret_name(name)?;
+        Ok(self.secrets_dir.join(format!("{}.json", name)))
+    }
+
+    /// creates or overwrites the secret `name`
+    pub fn create(&self, name: &str, values: HashMap<String, String>) -> Result<(), Error> {
+        for key in values.keys() {
+            validate_env_key(key)?;
+        }
+        let path = self.secret_path(name)?;
+        let content = serde_json::to_vec(&values)?;
+
+        // write to a temporary file first so a secret is never readable by others
+        // and never left half written
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)?;
+        restrict_permissions(&tmp_path, 0o600)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<HashMap<String, String>, Error> {
+        let path = self.secret_path(name)?;
+        let content = fs::read(&path)
+            .map_err(|_| anyhow!("secret {} does not exist", name))?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// returns the name and keys of every secret, never the values
+    pub fn list(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+        let mut secrets = Vec::new();
+        for entry in fs::read_dir(&self.secrets_dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let name = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue,
+            };
+            let mut keys: Vec<String> = self.get(&name)?.into_keys().collect();
+            keys.sort();
+            secrets.push((name, keys));
+        }
+        secrets.sort();
+        Ok(secrets)
+    }
+
+    /// merges the requested secrets into a single set of environment variables
+    pub fn resolve(&self, names: &[String]) -> Result<HashMap<String, String>, Error> {
+        let mut envs = HashMap::new();
+        for name in names {
+            envs.extend(self.get(name)?);
+        }
+        Ok(envs)
+    }
+}
+
+fn validate_secret_name(name: &str) -> Result<(), Error> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid secret name {:?}: only [a-zA-Z0-9_-] are allowed", name))
+    }
+}
+
+fn validate_env_key(key: &str) -> Result<(), Error> {
+    let valid = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid secret key {:?}: keys must be valid environment variable names", key))
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn restrict_permissions(path: &std::path::Path, mode: u32) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn restrict_permissions(_path: &std::path::Path, _mode: u32) -> Result<(), Error> {
+    Ok(())
+}
+
+/// replaces every secret value occuring in `line` with `*****`
+pub fn redact(line: &str, secret_values: &[String]) -> String {
+    let mut line = line.to_string();
+    for value in secret_values.iter().filter(|value| !value.is_empty()) {
+        line = line.replace(value.as_str(), "*****");
+    }
+    line
+}
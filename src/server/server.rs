@@ -1,46 +1,146 @@
-use crate::utilities::{_declare_values_from_json, write_bin_file};
+use crate::utilities::declare_value_from_inputs;
+use crate::mount::{read_mount_index, find_target};
 use std::fs;
 use std::pin::Pin;
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use tonic::service::interceptor::InterceptedService;
 use minimodal_proto::proto::minimodal::{
     MountProjectResponse,
     MountProjectRequest,
-    FileEntry,
+    ArchiveChunk,
     RunFunctionRequest, 
+    NameAndType,
+    generic_arg,
     RunFunctionResponse,
+    CreateSecretRequest,
+    CreateSecretResponse,
+    ListSecretsRequest,
+    ListSecretsResponse,
+    SecretInfo,
+    ListJobsRequest,
+    ListJobsResponse,
+    GetJobRequest,
+    GetJobResponse,
+    GetJobLogsRequest,
+    GetJobLogsResponse,
+    CancelJobRequest,
+    CancelJobResponse,
+    CollectGarbageRequest,
+    CollectGarbageResponse,
+    DeployAppRequest,
+    DeployAppResponse,
+    AppVersion,
+    RollbackAppRequest,
+    RollbackAppResponse,
+    ListAppsRequest,
+    ListAppsResponse,
+    AppInfo,
+    ListScheduledRunsRequest,
+    ListScheduledRunsResponse,
+    ScheduledRun,
+    SubmitFunctionResponse,
+    GetResultRequest,
+    GetResultResponse,
 };
 use minimodal_proto::proto::minimodal::run_function_response::Response as RunFunctionResult;
 use minimodal_proto::proto::minimodal::TaskResult;
 use minimodal_proto::proto::minimodal::mount_project_response::Result as MountProjectResult;
+use minimodal_proto::proto::minimodal::create_secret_response::Result as CreateSecretResult;
+use minimodal_proto::proto::minimodal::cancel_job_response::Result as CancelJobResult;
+use minimodal_proto::proto::minimodal::deploy_app_response::Result as DeployAppResult;
+use minimodal_proto::proto::minimodal::rollback_app_response::Result as RollbackAppResult;
 use minimodal_proto::proto::minimodal::mini_modal_server::{
     MiniModal, MiniModalServer
 };
 use base64; // Added for base64 decoding
 use base64::{Engine as _, alphabet, engine::{self, general_purpose}};
-use std::process::Command;
-use std::path::Path;
+use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
 use serde_json::{Value, json};
 use futures::stream::Stream;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use duct::cmd;
 use std::io::{BufRead, BufReader, Lines};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::server::secrets::{SecretStore, redact};
+use crate::server::build_options;
+use crate::server::type_names::{resolve_const_value, resolve_type_name};
+use crate::server::jobs::{Jobs, DEFAULT_RESULT_RETENTION};
+use crate::server::dispatcher::PRINT_RESULT;
+use crate::server::deployments::{self, DeploymentStore, Deployment, DeployedFunction};
+use crate::server::scheduler::Scheduler;
+use crate::server::http;
+use crate::server::metrics::{self, Metrics};
+use crate::server::jobs::{JobStatus, JobRecord};
+use crate::server::tokens::{self, Authenticator, Scope, TokenStore};
+use crate::server::tls::ServerTls;
+use crate::server::mounts::{self, MountEntry, MountQuotas, write_entries, write_files};
+use tonic_health::ServingStatus;
+use basemodules::trace_context;
+use tracing::{Instrument, Span, error, info, info_span, warn};
+/// the longest a `GetResult` call waits, callers waiting longer ask again
+const MAX_RESULT_WAIT: Duration = Duration::from_secs(60);
+/// how long a shutting down server waits for its jobs by default
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct MiniModalService {
     project_dir_path: String,
     tx: Option<mpsc::Sender<Result<RunFunctionResponse, Status>>>,
+    secrets: Arc<SecretStore>,
+    entry_locks: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    jobs: Arc<Jobs>,
+    deployments: Arc<DeploymentStore>,
+    app_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    scheduler: Arc<Scheduler>,
+    /// the id of the last mount, empty until the first one
+    mount_id: Mutex<String>,
+    metrics: Arc<Metrics>,
+    mount_quotas: MountQuotas,
 }
 
 impl MiniModalService {
-    pub fn new(project_dir_path: String) -> MiniModalService {
+    pub fn new(project_dir_path: String, secrets_dir: String, deployments_dir: String) -> MiniModalService {
+        MiniModalService::with_result_retention(project_dir_path, secrets_dir, deployments_dir, DEFAULT_RESULT_RETENTION)
+    }
+
+    /// keeps the results of submitted calls for `result_retention`
+    pub fn with_result_retention(
+        project_dir_path: String,
+        secrets_dir: String,
+        deployments_dir: String,
+        result_retention: Duration,
+    ) -> MiniModalService {
+        let secrets = SecretStore::new(secrets_dir)
+            .expect("Failed to open secrets dir");
+        let deployments = DeploymentStore::new(deployments_dir)
+            .expect("Failed to open deployments dir");
         let service = MiniModalService {
             project_dir_path,
             tx: None,
+            secrets: Arc::new(secrets),
+            entry_locks: Mutex::new(HashMap::new()),
+            jobs: Arc::new(Jobs::with_result_retention(result_retention)),
+            deployments: Arc::new(deployments),
+            app_locks: Mutex::new(HashMap::new()),
+            scheduler: Arc::new(Scheduler::new()),
+            mount_id: Mutex::new(String::new()),
+            metrics: Arc::new(Metrics::new()),
+            mount_quotas: MountQuotas::default(),
         };
         // build shadow dir
         service.build_shadow_dir();
         service
     }
 
+    /// limits the files, the size of each file and the total size of mounts and deployments
+    pub fn with_mount_quotas(self, mount_quotas: MountQuotas) -> MiniModalService {
+        MiniModalService { mount_quotas, ..self }
+    }
+
     // store the shadow cargo project in server/project
     pub fn build_shadow_dir(&self) {
         let shadow_dir = self.project_dir_path.clone();
@@ -52,74 +152,892 @@ impl MiniModalService {
                 .expect("Failed to create shadow cargo project");
         }
     }
+
+    /// lock serializing the builds of a mounted target's entrypoint
+    fn entry_lock(&self, req: &RunFunctionRequest) -> Arc<tokio::sync::Mutex<()>> {
+        let key = PathBuf::from(&req.package).join(&req.crate_name).join(&req.bin_name);
+        self.entry_locks.lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .clone()
+    }
+
+    /// lock serializing the deployments of an app
+    fn app_lock(&self, app: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.app_locks.lock()
+            .unwrap()
+            .entry(app.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// the job `job_id` if the scope allows it
+    #[allow(clippy::result_large_err)]
+    fn job_in_scope(&self, scope: &Scope, job_id: &str) -> Result<JobRecord, Status> {
+        let job = self.jobs.get(job_id)
+            .ok_or_else(|| Status::not_found(format!("job {} not found", job_id)))?;
+        scope.require_job(&job)?;
+        Ok(job)
+    }
+
+    /// runs the call as a job, its logs and result are sent to the receiver.
+    /// The job is traced as a child of the caller's span when given its `traceparent`,
+    /// the scope must allow mounted functions or the app of a deployed one
+    #[allow(clippy::result_large_err)]
+    fn start_job(
+        &self,
+        req: RunFunctionRequest,
+        caller: String,
+        traceparent: Option<String>,
+        scope: &Scope,
+    ) -> Result<(String, mpsc::Receiver<Result<RunFunctionResponse, Status>>), Status> {
+        if self.jobs.is_closed() {
+            return Err(Status::unavailable("the server is shutting down"));
+        }
+        validate_fields(&req.field_types)?;
+        if req.app.is_empty() {
+            scope.require_mount()?;
+            scope.require_secrets(&req.secrets)?;
+        } else {
+            scope.require_app(&req.app)?;
+        }
+        // a deployed function runs with the secrets it was deployed with,
+        // which the token deploying it had access to
+        let deployed = if req.app.is_empty() {
+            None
+        } else {
+            let path = function_path(&req, &[]);
+            Some(self.deployments.find_function(&req.app, &req.package, &req.crate_name, &req.bin_name, &path)
+                .map_err(|e| Status::not_found(e.to_string()))?)
+        };
+        let secrets = deployed.as_ref().map_or(&req.secrets, |(_, function)| &function.secrets);
+        let secret_envs = self.secrets.resolve(secrets)
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+
+        let entry_lock = self.entry_lock(&req);
+
+        let (tx, rx) = mpsc::channel(100);
+        let function = format!("{}::{}", req.module_path, req.function_id);
+        let function = if req.app.is_empty() { function } else { format!("{}/{}", req.app, function) };
+        let mount_id = match &deployed {
+            Some((version_dir, _)) => deployment_id(&req.app, version_dir),
+            None => self.mount_id.lock().unwrap().clone(),
+        };
+        let job_id = self.jobs.start(function.clone(), mount_id, caller, tx.clone());
+        let span = info_span!("job", %job_id, %function);
+        if let Some(traceparent) = &traceparent {
+            trace_context::set_parent(&span, traceparent);
+        }
+        let redactions: Vec<String> = secret_envs.values().cloned().collect();
+        let logger = Logger::new(
+            tx, 
+            self.project_dir_path.clone(), 
+            redactions,
+            self.jobs.clone(),
+            self.metrics.clone(),
+            job_id.clone(),
+        );
+
+        let handle = tokio::spawn(async move {
+            let result = match logger.start().await {
+                Ok(_) => match deployed {
+                    Some((version_dir, function)) => process_deployed_function(req, version_dir, function, secret_envs, &logger).await,
+                    None => process_function(req, secret_envs, entry_lock, &logger).await,
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                send_error(&logger, e).await;
+            }
+        }.instrument(span));
+        // cancelling the job drops the task, which kills its child processes
+        self.jobs.set_abort_handle(&job_id, handle.abort_handle());
+        Ok((job_id, rx))
+    }
+
+    /// replaces the mounted project with what `write` writes into the mount directory
+    #[allow(clippy::result_large_err)]
+    fn mount(
+        &self,
+        traceparent: Option<String>,
+        files: usize,
+        bytes: usize,
+        write: impl FnOnce(&Path) -> Result<(), Status>,
+    ) -> Result<Response<MountProjectResponse>, Status> {
+        let mount_id = uuid::Uuid::new_v4().to_string();
+        let span = info_span!("mount", %mount_id, files, bytes);
+        if let Some(traceparent) = &traceparent {
+            trace_context::set_parent(&span, traceparent);
+        }
+        let _entered = span.enter();
+        self.metrics.observe(&metrics::MOUNT_BYTES, &[], bytes as f64);
+        self.metrics.observe(&metrics::MOUNT_FILES, &[], files as f64);
+        write(Path::new(&self.project_dir_path))?;
+        *self.mount_id.lock().unwrap() = mount_id.clone();
+        info!("📂 Mounted {} files, {} bytes", files, bytes);
+
+        Ok(Response::new(MountProjectResponse {
+            result: Some(MountProjectResult::Success("Mounted project".to_string())),
+            mount_id,
+        }))
+    }
+
+    /// deploys the entries as a new version of `app`, traced as a child
+    /// of the caller's span when given its `traceparent`
+    async fn deploy_entries(
+        &self,
+        app: &str,
+        entries: Vec<MountEntry>,
+        scope: &Scope,
+        traceparent: Option<String>,
+    ) -> DeployAppResponse {
+        let span = info_span!("deploy", %app);
+        if let Some(traceparent) = &traceparent {
+            trace_context::set_parent(&span, traceparent);
+        }
+        let started = Instant::now();
+        let deployment = self.deploy(app, entries, scope).instrument(span).await;
+        self.metrics.observe(&metrics::DEPLOY_DURATION, &[("app", app)], started.elapsed().as_secs_f64());
+        let result = match deployment {
+            Ok(deployment) => DeployAppResult::Success(app_version(&deployment)),
+            Err(e) => DeployAppResult::Error(e.to_string()),
+        };
+        DeployAppResponse { result: Some(result) }
+    }
+
+    async fn deploy(&self, app: &str, entries: Vec<MountEntry>, scope: &Scope) -> Result<Deployment, anyhow::Error> {
+        let app_lock = self.app_lock(app);
+        let _guard = app_lock.lock().await;
+
+        let version = self.deployments.next_version(app)?;
+        let version_dir = self.deployments.version_dir(app, version)?;
+        // left behind by a deployment which failed to build
+        if version_dir.exists() {
+            fs::remove_dir_all(&version_dir)?;
+        }
+        info!("🚀 Deploying version {} of {} to {}", version, app, version_dir.display());
+
+        let built = async {
+            write_entries(&version_dir, entries)?;
+            let targets = read_mount_index(&version_dir)?;
+            // the deployed functions resolve their secrets whoever calls them
+            for function in targets.iter().flat_map(|target| target.functions.iter()) {
+                scope.require_secrets(&function.secrets)
+                    .map_err(|status| anyhow::anyhow!("{}: {}", function.name, status.message()))?;
+            }
+            deployments::build_functions(&self.deployments.app_dir(app)?, &version_dir, &targets).await
+        }.await;
+        let (functions, skipped) = match built {
+            Ok(built) => built,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&version_dir);
+                return Err(e);
+            },
+        };
+
+        let deployment = Deployment { version, created_at: deployments::now(), functions, skipped };
+        self.deployments.add(app, deployment.clone())?;
+        Ok(deployment)
+    }
+}
+
+/// the archive sent in chunks after `first`, cut off past `max_bytes`
+async fn receive_archive(
+    first: ArchiveChunk,
+    chunks: &mut Streaming<ArchiveChunk>,
+    max_bytes: usize,
+) -> Result<Vec<u8>, Status> {
+    let mut archive = first.data;
+    while let Some(chunk) = chunks.message().await? {
+        if archive.len() + chunk.data.len() > max_bytes {
+            return Err(Status::resource_exhausted(format!(
+                "the archive has more than {} bytes, at most {} are allowed", max_bytes, max_bytes,
+            )));
+        }
+        archive.extend_from_slice(&chunk.data);
+    }
+    Ok(archive)
+}
+
+fn app_version(deployment: &Deployment) -> AppVersion {
+    AppVersion {
+        version: deployment.version,
+        created_at: deployment.created_at,
+        functions: deployment.functions.iter().map(DeployedFunction::name).collect(),
+        skipped: deployment.skipped.clone(),
+        schedules: deployment.functions.iter()
+            .filter(|function| !function.schedule.is_empty() || !function.period.is_empty())
+            .map(|function| if function.schedule.is_empty() {
+                format!("{}: every {}", function.name(), function.period)
+            } else {
+                format!("{}: {}", function.name(), function.schedule)
+            })
+            .collect(),
+        web_endpoints: deployment.functions.iter()
+            .filter(|function| function.web_endpoint)
+            .map(DeployedFunction::name)
+            .collect(),
+    }
+}
+
+/// Triggers the scheduled functions of the active version of every app,
+/// checking once a second which of them are due
+async fn run_scheduler(
+    deployments: Arc<DeploymentStore>,
+    secrets: Arc<SecretStore>,
+    jobs: Arc<Jobs>,
+    scheduler: Arc<Scheduler>,
+    metrics: Arc<Metrics>,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        if jobs.is_closed() {
+            return;
+        }
+        let apps = match deployments.list() {
+            Ok(apps) => apps,
+            Err(e) => {
+                error!("🔥 Failed to list apps for the scheduler: {}", e);
+                continue;
+            },
+        };
+        let now = chrono::Utc::now();
+        let mut scheduled = Vec::new();
+        for (app, content) in apps {
+            let deployment = match content.active() {
+                Some(deployment) => deployment,
+                None => continue,
+            };
+            for function in deployment.functions.iter() {
+                // the schedule was validated when the version was deployed
+                let schedule = match function.schedule() {
+                    Ok(Some(schedule)) => schedule,
+                    _ => continue,
+                };
+                let name = function.name();
+                scheduled.push((app.clone(), name.clone()));
+
+                let scheduled_at = match scheduler.due(&app, &name, &schedule, now) {
+                    Some(scheduled_at) => scheduled_at,
+                    None => continue,
+                };
+                if scheduler.is_running(&app, &name) {
+                    info!("⏭️ Skipping {} of {}, the previous run is still running", name, app);
+                    scheduler.skip(&app, &name, deployment.version, scheduled_at);
+                    continue;
+                }
+                let version_dir = match deployments.version_dir(&app, deployment.version) {
+                    Ok(version_dir) => version_dir,
+                    Err(_) => continue,
+                };
+                start_scheduled_run(
+                    &app,
+                    deployment.version,
+                    version_dir,
+                    function.clone(),
+                    scheduled_at,
+                    &secrets,
+                    &jobs,
+                    &scheduler,
+                    &metrics,
+                );
+            }
+        }
+        scheduler.retain(&scheduled);
+    }
+}
+
+/// A deployed function running as a job, started by the server itself
+/// rather than by a `RunFunction` call
+pub(crate) struct DeployedJob {
+    pub job_id: String,
+    pub run: tokio::task::JoinHandle<()>,
+    /// the logs and result of the job, the job stops when it is dropped
+    pub rx: mpsc::Receiver<Result<RunFunctionResponse, Status>>,
+}
+
+/// identifies a deployed version in the jobs running it, e.g. prod/v3
+fn deployment_id(app: &str, version_dir: &Path) -> String {
+    let version = version_dir.file_name().unwrap_or_default().to_string_lossy();
+    format!("{}/{}", app, version)
+}
+
+/// runs the deployed `function` of `app` with the call inputs
+/// serialized as a json object keyed by argument name
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn start_deployed_job(
+    app: &str,
+    version_dir: PathBuf,
+    function: DeployedFunction,
+    serialized_inputs: String,
+    caller: &str,
+    secrets: &SecretStore,
+    jobs: &Arc<Jobs>,
+    metrics: &Arc<Metrics>,
+) -> DeployedJob {
+    let (tx, rx) = mpsc::channel(100);
+    let name = format!("{}/{}", app, function.name());
+    let job_id = jobs.start(
+        name.clone(),
+        deployment_id(app, &version_dir),
+        caller.to_string(),
+        tx.clone(),
+    );
+    let span = info_span!("job", %job_id, function = %name);
+
+    let secret_envs = secrets.resolve(&function.secrets);
+    let redactions: Vec<String> = secret_envs.as_ref()
+        .map(|envs| envs.values().cloned().collect())
+        .unwrap_or_default();
+    let logger = Logger::new(
+        tx,
+        version_dir.to_string_lossy().to_string(),
+        redactions,
+        jobs.clone(),
+        metrics.clone(),
+        job_id.clone(),
+    );
+    let req = RunFunctionRequest {
+        app: app.to_string(),
+        serialized_inputs,
+        ..Default::default()
+    };
+
+    let run = tokio::spawn(async move {
+        let result = match (logger.start().await, secret_envs) {
+            (Err(e), _) => Err(e),
+            (Ok(_), Ok(secret_envs)) => process_deployed_function(req, version_dir, function, secret_envs, &logger).await,
+            (Ok(_), Err(e)) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            send_error(&logger, e).await;
+        }
+    }.instrument(span));
+    jobs.set_abort_handle(&job_id, run.abort_handle());
+    DeployedJob { job_id, run, rx }
+}
+
+/// runs a scheduled function as a job, nobody listens to its stream,
+/// the outcome and logs are recorded in the history of the scheduler
+#[allow(clippy::too_many_arguments)]
+fn start_scheduled_run(
+    app: &str,
+    version: u32,
+    version_dir: PathBuf,
+    function: DeployedFunction,
+    scheduled_at: chrono::DateTime<chrono::Utc>,
+    secrets: &SecretStore,
+    jobs: &Arc<Jobs>,
+    scheduler: &Arc<Scheduler>,
+    metrics: &Arc<Metrics>,
+) {
+    let name = function.name();
+    info!("⏰ Running {} of {} scheduled at {}", name, app, scheduled_at);
+
+    let DeployedJob { job_id, run, mut rx } = start_deployed_job(app, version_dir, function, "{}".to_string(), "scheduler", secrets, jobs, metrics);
+    scheduler.start(app, &name, version, &job_id, scheduled_at);
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+    // a cancelled run never finishes by itself
+    let jobs = jobs.clone();
+    let scheduler = scheduler.clone();
+    let app = app.to_string();
+    tokio::spawn(async move {
+        let _ = run.await;
+        let status = jobs.status(&job_id).unwrap_or(JobStatus::Failed);
+        scheduler.finish(&app, &name, &job_id, status, jobs.logs(&job_id).unwrap_or_default());
+    });
+}
+
+/// the arguments are pasted into the generated entrypoint, so each must
+/// be an identifier passed by value or by reference
+#[allow(clippy::result_large_err)]
+fn validate_fields(fields: &[NameAndType]) -> Result<(), Status> {
+    for field in fields {
+        if syn::parse_str::<syn::Ident>(&field.name).is_err() {
+            return Err(Status::invalid_argument(format!("invalid argument name {:?}", field.name)));
+        }
+        if !matches!(field.borrow.as_str(), "" | "&" | "&mut ") {
+            return Err(Status::invalid_argument(format!("invalid borrow {:?} of the argument {}", field.borrow, field.name)));
+        }
+    }
+    Ok(())
+}
+
+/// the address of the client making the request
+fn caller<T>(request: &Request<T>) -> String {
+    request.remote_addr().map_or_else(|| "unknown".to_string(), |addr| addr.to_string())
+}
+
+/// forgets the expired jobs and the results of submitted calls once a minute,
+/// until the server shuts down
+async fn expire_results(jobs: Arc<Jobs>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        if jobs.is_closed() {
+            return;
+        }
+        for job_id in jobs.expire_results() {
+            info!("🧹 Forgot job {}", job_id);
+        }
+    }
+}
+
+/// reports an error which ended a job as its result
+async fn send_error(logger: &Logger, e: Box<dyn std::error::Error + Send + Sync>) {
+    let _ = logger.send(RunFunctionResponse {
+        response: Some(RunFunctionResult::Result(TaskResult {
+            success: false,
+            message: redact(&format!("Error: {}", e), &logger.redactions),
+        })),
+    }).await;
+}
+
+/// What the server offers besides the MiniModal service
+pub struct ServeOptions {
+    /// serves the web endpoints of the deployed functions
+    pub http_addr: Option<std::net::SocketAddr>,
+    /// serves the Prometheus metrics
+    pub metrics_addr: Option<std::net::SocketAddr>,
+    pub result_retention: Duration,
+    /// the tokens the requests must carry, anyone may call the server without
+    pub tokens_file: Option<String>,
+    /// serves the MiniModal service over TLS, plain HTTP/2 without
+    pub tls: Option<ServerTls>,
+    /// how long the jobs may run on once the server is shutting down, the rest is cancelled
+    pub shutdown_timeout: Duration,
+    pub mount_quotas: MountQuotas,
+}
+
+impl Default for ServeOptions {
+    fn default() -> ServeOptions {
+        ServeOptions {
+            http_addr: None,
+            metrics_addr: None,
+            result_retention: DEFAULT_RESULT_RETENTION,
+            tokens_file: None,
+            tls: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            mount_quotas: MountQuotas::default(),
+        }
+    }
+}
+
+/// resolves on SIGTERM or SIGINT
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => { terminate.recv().await; },
+            Err(e) => {
+                error!("🔥 Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await
+            },
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate => {},
+    }
+}
+
+/// serves the MiniModal service on `addr`, and the web endpoints
+/// and the metrics on the addresses of the options, until SIGTERM or SIGINT
+pub async fn serve(
+    addr: std::net::SocketAddr, 
+    project_dir_path: String, 
+    secrets_dir: String,
+    deployments_dir: String,
+    options: ServeOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    serve_with_shutdown(addr, project_dir_path, secrets_dir, deployments_dir, options, shutdown_signal()).await
+}
+
+/// serves until `signal` resolves, then refuses new jobs and reports the service
+/// as not serving, waits up to the shutdown timeout for the running jobs, cancels
+/// the others, which kills their processes, and returns once the open calls ended
+pub async fn serve_with_shutdown(
+    addr: std::net::SocketAddr,
+    project_dir_path: String,
+    secrets_dir: String,
+    deployments_dir: String,
+    options: ServeOptions,
+    signal: impl std::future::Future<Output = ()> + Send,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let ServeOptions { http_addr, metrics_addr, result_retention, tokens_file, tls, shutdown_timeout, mount_quotas } = options;
+    let mut builder = Server::builder();
+    if let Some(tls) = &tls {
+        builder = builder.tls_config(tls.config()?)?;
+        match &tls.client_ca {
+            Some(client_ca) => info!("🔒 Serving over TLS, clients must present a certificate signed by {}", client_ca.display()),
+            None => info!("🔒 Serving over TLS"),
+        }
+    }
+    let tokens = match tokens_file {
+        Some(tokens_file) => Some(Arc::new(TokenStore::load(tokens_file)?)),
+        None => {
+            warn!("🔓 No tokens file, anyone reaching {} can run code on the server", addr);
+            None
+        },
+    };
+    let service = MiniModalService::with_result_retention(project_dir_path, secrets_dir, deployments_dir, result_retention)
+        .with_mount_quotas(mount_quotas);
+    tokio::spawn(expire_results(service.jobs.clone()));
+    tokio::spawn(run_scheduler(
+        service.deployments.clone(),
+        service.secrets.clone(),
+        service.jobs.clone(),
+        service.scheduler.clone(),
+        service.metrics.clone(),
+    ));
+    if let Some(http_addr) = http_addr {
+        let router = http::router(http::HttpState {
+            deployments: service.deployments.clone(),
+            secrets: service.secrets.clone(),
+            jobs: service.jobs.clone(),
+            metrics: service.metrics.clone(),
+            tokens: tokens.clone(),
+        });
+        let listener = tokio::net::TcpListener::bind(http_addr).await?;
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                error!("🔥 The web endpoints stopped: {}", e);
+            }
+        });
+    }
+    if let Some(metrics_addr) = metrics_addr {
+        let router = metrics::router(service.metrics.clone(), service.jobs.clone());
+        let listener = tokio::net::TcpListener::bind(metrics_addr).await?;
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                error!("🔥 The metrics endpoint stopped: {}", e);
+            }
+        });
+    }
+    // health checks and reflection need no token
+    let (mut health, health_service) = tonic_health::server::health_reporter();
+    health.set_serving::<MiniModalServer<MiniModalService>>().await;
+    let reflection_service = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(minimodal_proto::proto::minimodal::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1()?;
+    let jobs = service.jobs.clone();
+    let shutdown = async move {
+        signal.await;
+        info!("🛑 Shutting down, waiting up to {:?} for the running jobs", shutdown_timeout);
+        jobs.close();
+        health.set_not_serving::<MiniModalServer<MiniModalService>>().await;
+        health.set_service_status("", ServingStatus::NotServing).await;
+        for job_id in jobs.drain(shutdown_timeout).await {
+            warn!("✂️ Cancelled job {}, it was still running at shutdown", job_id);
+        }
+    };
+    builder
+        .add_service(health_service)
+        .add_service(reflection_service)
+        .add_service(InterceptedService::new(
+            // tonic's default of 4MB would cut the mounts off before their quotas
+            MiniModalServer::new(service).max_decoding_message_size(mount_quotas.max_message_bytes()),
+            Authenticator::new(tokens),
+        ))
+        .serve_with_shutdown(addr, shutdown)
+        .await?;
+    info!("👋 Stopped");
+    Ok(())
 }
 
 #[tonic::async_trait]
 impl MiniModal for MiniModalService {
     type RunFunctionStream = Pin<Box<dyn Stream<Item = Result<RunFunctionResponse, Status>> + Send>>;
 
+    #[allow(clippy::result_large_err)]
     async fn mount_project(
         &self,
         request: Request<MountProjectRequest>,
     ) -> Result<Response<MountProjectResponse>, Status> {
+        tokens::scope(&request)?.require_mount()?;
+        let traceparent = trace_context::extract(&request);
         let req = request.into_inner();
-        let project_dir_path = self.project_dir_path.clone();
-        let shadow_dir = format!("{}", project_dir_path);
-
-        for file_entry in req.files.into_iter() {
-            let file_path = file_entry.file_path;
-            let file_path = format!("{}/{}", shadow_dir, file_path);
-            // Create intermediate directories if they don't exist
-            if let Some(parent) = Path::new(&file_path).parent() {
-                fs::create_dir_all(parent)
-                    .map_err(|e| Status::internal(format!("Failed to create directories: {}", e)))?;
-            }
-            
-            match fs::write(file_path, file_entry.content) {
-                Ok(_) => (),
-                Err(e) => return Err(Status::internal(format!("Failed to write file: {}", e))),
-            }
-        }
+        mounts::check_files(&req.files, &self.mount_quotas)?;
+        let files = req.files.len();
+        let bytes: usize = req.files.iter().map(|file| file.content.len()).sum();
+        self.mount(traceparent, files, bytes, |dir| write_files(dir, req.files))
+    }
 
-        Ok(Response::new(MountProjectResponse {
-            result: Some(MountProjectResult::Success("Mounted project".to_string())),
-        }))
+    #[allow(clippy::result_large_err)]
+    async fn mount_archive(
+        &self,
+        request: Request<Streaming<ArchiveChunk>>,
+    ) -> Result<Response<MountProjectResponse>, Status> {
+        tokens::scope(&request)?.require_mount()?;
+        let traceparent = trace_context::extract(&request);
+        let mut chunks = request.into_inner();
+        let first = chunks.message().await?.unwrap_or_default();
+        let archive = receive_archive(first, &mut chunks, self.mount_quotas.max_message_bytes()).await?;
+        let entries = mounts::read_archive(&archive, &self.mount_quotas)?;
+        let files = entries.len();
+        let bytes: usize = entries.iter().map(MountEntry::bytes).sum();
+        self.mount(traceparent, files, bytes, |dir| write_entries(dir, entries))
     }
 
     async fn run_function(
         &self,
         request: Request<RunFunctionRequest>,
     ) -> Result<Response<Self::RunFunctionStream>, Status> {
+        let scope = tokens::scope(&request)?;
+        let caller = caller(&request);
+        let traceparent = trace_context::extract(&request);
+        let (_, rx) = self.start_job(request.into_inner(), caller, traceparent, &scope)?;
+        let stream = ReceiverStream::new(rx);
+        Ok(Response::new(Box::pin(stream) as Self::RunFunctionStream))
+    }
+
+    async fn create_secret(
+        &self,
+        request: Request<CreateSecretRequest>,
+    ) -> Result<Response<CreateSecretResponse>, Status> {
+        let scope = tokens::scope(&request)?;
         let req = request.into_inner();
-        let (tx, rx) = mpsc::channel(100);
-        let logger = Logger::new(tx.clone(), self.project_dir_path.clone());
+        scope.require_secrets(std::slice::from_ref(&req.name))?;
+        let result = match self.secrets.create(&req.name, req.values) {
+            Ok(_) => CreateSecretResult::Success(format!("Created secret {}", req.name)),
+            Err(e) => CreateSecretResult::Error(e.to_string()),
+        };
+        Ok(Response::new(CreateSecretResponse { result: Some(result) }))
+    }
 
-        tokio::spawn(async move {
-            if let Err(e) = process_function(req, logger).await {
-                let _ = tx.send(Ok(RunFunctionResponse {
-                    response: Some(RunFunctionResult::Result(TaskResult {
-                        success: false,
-                        message: format!("Error: {}", e),
-                    })),
-                })).await;
+    async fn list_secrets(
+        &self,
+        request: Request<ListSecretsRequest>,
+    ) -> Result<Response<ListSecretsResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let secrets = self.secrets.list()
+            .map_err(|e| Status::internal(format!("Failed to list secrets: {}", e)))?
+            .into_iter()
+            .filter(|(name, _)| scope.allows_secret(name))
+            .map(|(name, keys)| SecretInfo { name, keys })
+            .collect();
+        Ok(Response::new(ListSecretsResponse { secrets }))
+    }
+
+    async fn list_jobs(
+        &self,
+        request: Request<ListJobsRequest>,
+    ) -> Result<Response<ListJobsResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let jobs = self.jobs.list()
+            .iter()
+            .filter(|job| scope.allows_job(job))
+            .map(JobRecord::info)
+            .collect();
+        Ok(Response::new(ListJobsResponse { jobs }))
+    }
+
+    async fn get_job(
+        &self,
+        request: Request<GetJobRequest>,
+    ) -> Result<Response<GetJobResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let req = request.into_inner();
+        let job = self.job_in_scope(&scope, &req.job_id)?;
+        Ok(Response::new(GetJobResponse { job: Some(job.info()) }))
+    }
+
+    async fn get_job_logs(
+        &self,
+        request: Request<GetJobLogsRequest>,
+    ) -> Result<Response<GetJobLogsResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let req = request.into_inner();
+        self.job_in_scope(&scope, &req.job_id)?;
+        let lines = self.jobs.logs(&req.job_id)
+            .ok_or_else(|| Status::not_found(format!("job {} not found", req.job_id)))?;
+        Ok(Response::new(GetJobLogsResponse { lines }))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobRequest>,
+    ) -> Result<Response<CancelJobResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let req = request.into_inner();
+        if let Some(job) = self.jobs.get(&req.job_id) {
+            scope.require_job(&job)?;
+        }
+        let result = match self.jobs.cancel(&req.job_id) {
+            Ok(_) => {
+                if let Some(job) = self.jobs.get(&req.job_id) {
+                    self.metrics.increment(&metrics::JOBS_FINISHED, &[("function", &job.function), ("status", "cancelled")]);
+                }
+                CancelJobResult::Success(format!("Cancelled job {}", req.job_id))
+            },
+            Err(e) => CancelJobResult::Error(e.to_string()),
+        };
+        Ok(Response::new(CancelJobResponse { result: Some(result) }))
+    }
+
+    async fn collect_garbage(
+        &self,
+        request: Request<CollectGarbageRequest>,
+    ) -> Result<Response<CollectGarbageResponse>, Status> {
+        tokens::scope(&request)?.require_mount()?;
+        let jobs = self.jobs.collect_finished();
+
+        let in_use = self.jobs.executables_in_use();
+        let mut files = Vec::new();
+        for executable in build_options::leftover_executables(Path::new(&self.project_dir_path)) {
+            if in_use.contains(&executable) {
+                continue;
             }
-        });
+            fs::remove_file(&executable)
+                .map_err(|e| Status::internal(format!("Failed to remove {}: {}", executable.display(), e)))?;
+            files.push(executable.to_string_lossy().to_string());
+        }
 
-        let stream = ReceiverStream::new(rx);
-        Ok(Response::new(Box::pin(stream) as Self::RunFunctionStream))
+        Ok(Response::new(CollectGarbageResponse { jobs, files }))
+    }
+
+    async fn deploy_app(
+        &self,
+        request: Request<DeployAppRequest>,
+    ) -> Result<Response<DeployAppResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let traceparent = trace_context::extract(&request);
+        let req = request.into_inner();
+        scope.require_app(&req.app)?;
+        mounts::check_files(&req.files, &self.mount_quotas)?;
+        let entries = mounts::file_entries(req.files)?;
+        Ok(Response::new(self.deploy_entries(&req.app, entries, &scope, traceparent).await))
+    }
+
+    async fn deploy_archive(
+        &self,
+        request: Request<Streaming<ArchiveChunk>>,
+    ) -> Result<Response<DeployAppResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let traceparent = trace_context::extract(&request);
+        let mut chunks = request.into_inner();
+        let first = chunks.message().await?.unwrap_or_default();
+        let app = first.app.clone();
+        // before the rest of the archive is received
+        scope.require_app(&app)?;
+        let archive = receive_archive(first, &mut chunks, self.mount_quotas.max_message_bytes()).await?;
+        let entries = mounts::read_archive(&archive, &self.mount_quotas)?;
+        Ok(Response::new(self.deploy_entries(&app, entries, &scope, traceparent).await))
+    }
+
+    async fn rollback_app(
+        &self,
+        request: Request<RollbackAppRequest>,
+    ) -> Result<Response<RollbackAppResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let req = request.into_inner();
+        scope.require_app(&req.app)?;
+        let app_lock = self.app_lock(&req.app);
+        let _guard = app_lock.lock().await;
+        let result = match self.deployments.rollback(&req.app, req.version) {
+            Ok(version) => RollbackAppResult::Success(format!("Rolled {} back to version {}", req.app, version)),
+            Err(e) => RollbackAppResult::Error(e.to_string()),
+        };
+        Ok(Response::new(RollbackAppResponse { result: Some(result) }))
+    }
+
+    async fn list_apps(
+        &self,
+        request: Request<ListAppsRequest>,
+    ) -> Result<Response<ListAppsResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let apps = self.deployments.list()
+            .map_err(|e| Status::internal(format!("Failed to list apps: {}", e)))?
+            .into_iter()
+            .filter(|(name, _)| scope.allows_app(name))
+            .map(|(name, app)| AppInfo {
+                name,
+                active_version: app.active_version,
+                versions: app.versions.iter().map(app_version).collect(),
+            })
+            .collect();
+        Ok(Response::new(ListAppsResponse { apps }))
+    }
+
+    async fn list_scheduled_runs(
+        &self,
+        request: Request<ListScheduledRunsRequest>,
+    ) -> Result<Response<ListScheduledRunsResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let req = request.into_inner();
+        let limit = if req.limit == 0 { usize::MAX } else { req.limit as usize };
+        // limited after leaving out the runs of the apps out of scope
+        let runs = self.scheduler.runs(&req.app, &req.function, 0)
+            .into_iter()
+            .filter(|run| scope.allows_app(&run.app))
+            .take(limit)
+            .map(|run| ScheduledRun {
+                app: run.app,
+                function: run.function,
+                version: run.version,
+                job_id: run.job_id,
+                scheduled_at: run.scheduled_at.timestamp().max(0) as u64,
+                finished_at: run.finished_at.map_or(0, |finished_at| finished_at.timestamp().max(0) as u64),
+                status: run.status.to_string(),
+                logs: run.logs,
+            })
+            .collect();
+        Ok(Response::new(ListScheduledRunsResponse { runs }))
+    }
+
+    async fn submit_function(
+        &self,
+        request: Request<RunFunctionRequest>,
+    ) -> Result<Response<SubmitFunctionResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let caller = caller(&request);
+        let traceparent = trace_context::extract(&request);
+        let (job_id, mut rx) = self.start_job(request.into_inner(), caller, traceparent, &scope)?;
+        self.jobs.retain_result(&job_id);
+        // nobody listens to the stream, the result is kept by the job
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+        Ok(Response::new(SubmitFunctionResponse { job_id }))
+    }
+
+    async fn get_result(
+        &self,
+        request: Request<GetResultRequest>,
+    ) -> Result<Response<GetResultResponse>, Status> {
+        let scope = tokens::scope(&request)?;
+        let req = request.into_inner();
+        if let Some(job) = self.jobs.get(&req.job_id) {
+            scope.require_job(&job)?;
+        }
+        let timeout = Duration::from_millis(req.timeout_ms).min(MAX_RESULT_WAIT);
+        let (status, result) = self.jobs.wait(&req.job_id, timeout)
+            .await
+            .ok_or_else(|| Status::not_found(format!("job {} not found, its result may have expired", req.job_id)))?;
+        Ok(Response::new(GetResultResponse { status: status.to_string(), result }))
     }
 }
 
-async fn process_function(req: RunFunctionRequest, logger: Logger) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+async fn process_function(
+    req: RunFunctionRequest, 
+    secret_envs: HashMap<String, String>,
+    entry_lock: Arc<tokio::sync::Mutex<()>>,
+    logger: &Logger
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     logger.log(&format!("🏃‍ Running function: {}", req.function_id)).await?;
 
     let project_dir_path = logger.project_dir_path.clone();
     logger.log(&format!("📦 Loading app: {}", project_dir_path)).await?;
 
-    let original_main_file_path = format!("{}/src/original_main.rs", project_dir_path);
-    logger.log(&format!("👉 Reading main file from {}", original_main_file_path)).await?;
+    let targets = read_mount_index(Path::new(&project_dir_path))?;
+    let target = find_target(&targets, &req.package, &req.crate_name, &req.bin_name)?;
+    logger.log(&format!("🎯 Target: {} {} of package {}", target.kind, target.crate_name, target.package)).await?;
 
-    let original_code = fs::read_to_string(&original_main_file_path)?;
+    let original_code = fs::read_to_string(Path::new(&project_dir_path).join(&target.source))?;
 
     let deserialized_inputs: Value = serde_json::from_str(&req.serialized_inputs)?;
     logger.log(&format!("🔍 Deserialized inputs: {:?}", deserialized_inputs)).await?;
@@ -127,22 +1045,132 @@ async fn process_function(req: RunFunctionRequest, logger: Logger) -> Result<(),
     let str_field_types = req.field_types.iter().map(|field| (field.name.clone(), field.ty.clone())).collect::<Vec<(String, String)>>();
     logger.log(&format!("🔍 Field types: {:?}", str_field_types)).await?;
 
-    let let_declarations = _declare_values_from_json(&deserialized_inputs, &str_field_types)
-        .map_err(|e| Status::internal(e.to_string()))?;
+    let let_declarations = req.field_types.iter()
+        .map(|field| {
+            let ty = if field.annotate { Some(resolve_type_name(&field.ty, &target.crate_name)?) } else { None };
+            Ok(declare_value_from_inputs(&field.name, ty.as_deref(), field.borrow == "&mut "))
+        })
+        .collect::<Result<Vec<String>, anyhow::Error>>()?
+        .join("\n");
+    // borrowed arguments are sent by value and borrowed again at the call
+    let call_args = req.field_types.iter()
+        .map(|field| format!("{}{}", field.borrow, field.name))
+        .collect::<Vec<String>>();
 
-    let main_code = format_code(original_code, deserialized_inputs, let_declarations, str_field_types, &req);
+    let generic_args = req.generic_args.iter()
+        .map(|generic_arg| match &generic_arg.arg {
+            Some(generic_arg::Arg::TypeName(type_name)) => resolve_type_name(type_name, &target.crate_name),
+            Some(generic_arg::Arg::ConstValue(value)) => resolve_const_value(value),
+            None => Err(anyhow::anyhow!("a generic argument of {} is missing", req.function_id)),
+        })
+        .collect::<Result<Vec<String>, _>>()?;
+    if !generic_args.is_empty() {
+        logger.log(&format!("🧬 Generic arguments: {:?}", generic_args)).await?;
+    }
 
-    let name = uuid::Uuid::new_v4().to_string();
-    logger.log(&format!("👉 Writing bin file to {}", project_dir_path)).await?;
+    let main_code = format_code(original_code, let_declarations, call_args, generic_args, &req);
 
-    write_bin_file(&name, &main_code, &project_dir_path.clone().into())?;
+    let build_options = req.build_options.clone().unwrap_or_default();
+    build_options::validate(&build_options)?;
+    logger.log(&format!("🔨 Build options: {:?}", build_options)).await?;
 
-    logger.log(&format!("project_dir_path: {}", project_dir_path)).await?;
-    let output = tokio::process::Command::new("cargo")
-        .args(&["run", "--bin", &name])
-        .current_dir(&project_dir_path)
-        .output()
-        .await?;
+    let executable = async {
+        // the entrypoint of a target is shared by all its functions,
+        // hold the lock until the built executable is copied out
+        let _guard = entry_lock.lock().await;
+        logger.transition(JobStatus::Building).await?;
+
+        let entry_path = Path::new(&project_dir_path).join(&target.entry);
+        // only touch the entrypoint when it changed, so cargo can reuse the last build
+        if fs::read_to_string(&entry_path).ok().as_deref() != Some(main_code.as_str()) {
+            logger.log(&format!("👉 Writing entrypoint to {}", entry_path.display())).await?;
+            fs::write(&entry_path, &main_code)?;
+        }
+
+        logger.log(&format!("project_dir_path: {}", project_dir_path)).await?;
+        let build_started = Instant::now();
+        let build_output = tokio::process::Command::new("cargo")
+            .args(["build", "--package", &target.package, "--bin", &target.entry_bin])
+            .args(build_options::cargo_args(&build_options))
+            .current_dir(&project_dir_path)
+            .envs(build_options::cargo_envs(&build_options))
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        logger.metrics.observe(&metrics::BUILD_DURATION, &[("package", &target.package)], build_started.elapsed().as_secs_f64());
+
+        if !build_output.status.success() {
+            let error_message = format!("cargo build failed: {}", String::from_utf8_lossy(&build_output.stderr));
+            logger.log(&format!("🔥 Error: {}", error_message)).await?;
+            return Err(error_message.into());
+        }
+        // cargo reports every crate it compiles
+        let cache = if String::from_utf8_lossy(&build_output.stderr).contains("Compiling ") { "miss" } else { "hit" };
+        logger.metrics.increment(&metrics::BUILDS, &[("package", &target.package), ("cache", cache)]);
+
+        let built = Path::new(&project_dir_path).join(build_options::executable_path(&build_options, &target.entry_bin));
+        let executable = build_options::copied_executable_path(&built, &target.entry_bin);
+        fs::copy(&built, &executable)?;
+        logger.jobs.set_executable(&logger.job_id, Some(executable.clone()));
+        Ok::<PathBuf, Box<dyn std::error::Error + Send + Sync>>(executable)
+    }
+    .instrument(info_span!("build", package = %target.package, bin = %target.entry_bin))
+    .await?;
+
+    let result = run_executable(&executable, Path::new(&project_dir_path), &req.serialized_inputs, &secret_envs, logger)
+        .instrument(info_span!("run"))
+        .await;
+    let _ = fs::remove_file(&executable);
+    result
+}
+
+/// runs the active version of the deployed `function`, nothing is built
+async fn process_deployed_function(
+    req: RunFunctionRequest,
+    version_dir: PathBuf,
+    function: DeployedFunction,
+    secret_envs: HashMap<String, String>,
+    logger: &Logger
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    logger.log(&format!("🏃‍ Running deployed function {} of {}", function.name(), req.app)).await?;
+    logger.log(&format!("📦 Version dir: {}", version_dir.display())).await?;
+
+    let inputs: Value = serde_json::from_str(&req.serialized_inputs)?;
+    let call = json!({ "function": function.path, "inputs": inputs });
+    run_executable(&version_dir.join(&function.executable), &version_dir, &call.to_string(), &secret_envs, logger)
+        .instrument(info_span!("run"))
+        .await
+}
+
+/// runs an entrypoint with `stdin` and sends its result, the entrypoint
+/// gets the trace context of the current span as `TRACEPARENT`
+async fn run_executable(
+    executable: &Path,
+    current_dir: &Path,
+    stdin: &str,
+    secret_envs: &HashMap<String, String>,
+    logger: &Logger
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    logger.transition(JobStatus::Running).await?;
+    let started = Instant::now();
+    // a relative path would be looked up from `current_dir`
+    let mut child = tokio::process::Command::new(executable.canonicalize()?)
+        .current_dir(current_dir)
+        .envs(secret_envs)
+        .envs(trace_context::traceparent(&Span::current()).map(|traceparent| (trace_context::TRACEPARENT_ENV, traceparent)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    if let Some(mut child_stdin) = child.stdin.take() {
+        child_stdin.write_all(stdin.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if let Some(job) = logger.jobs.get(&logger.job_id) {
+        logger.metrics.observe(&metrics::RUN_DURATION, &[("function", &job.function)], started.elapsed().as_secs_f64());
+    }
 
     logger.log(&format!("output: {:?}", output)).await?;
 
@@ -154,8 +1182,9 @@ async fn process_function(req: RunFunctionRequest, logger: Logger) -> Result<(),
     }
 
     if !output.status.success() {
-        let error_message = format!("cargo run failed: {}", stderr);
+        let error_message = format!("function failed: {}", stderr);
         logger.log(&format!("🔥 Error: {}", error_message)).await?;
+        return Err(error_message.into());
     } else {
         let result = stdout
             .split("RESULT_START")
@@ -176,7 +1205,7 @@ async fn process_function(req: RunFunctionRequest, logger: Logger) -> Result<(),
             RunFunctionResponse {
                 response: Some(RunFunctionResult::Result(TaskResult {
                     success: false,
-                    message: error.to_string(),
+                    message: redact(&error.to_string(), &logger.redactions),
                 })),
             }
         } else {
@@ -196,9 +1225,9 @@ async fn process_function(req: RunFunctionRequest, logger: Logger) -> Result<(),
 
 fn format_code(
     original_code: String, 
-    deserialized_inputs: Value, 
     let_declarations: String, 
-    str_field_types: Vec<(String, String)>, 
+    call_args: Vec<String>, 
+    generic_args: Vec<String>,
     req: &RunFunctionRequest
 ) -> String {
     format!(
@@ -206,26 +1235,20 @@ fn format_code(
 
 {original_code}
 
-// Custom macro to print the result
-macro_rules! print_result {{
-    ($result:expr) => {{
-        let json_result = match $result {{
-            Ok(value) => serde_json::json!({{ "success": value }}),
-            Err(e) => serde_json::json!({{ "error": e.to_string() }}),
-        }};
-        println!("RESULT_START{{}}RESULT_END", json_result);
-    }}
-}}
-
+{print_result}
 // the original code
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {{
-    let inputs: serde_json::Value = serde_json::json!({deserialized_inputs});
+    // the serialized inputs are passed on stdin so the entrypoint
+    // does not need to be rebuilt for every call
+    let mut serialized_inputs = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut serialized_inputs)?;
+    let inputs: serde_json::Value = serde_json::from_str(&serialized_inputs)?;
     
     {declarations}
-    let result: {output_type} = match {function_id}(
+    let result = match {function_path}(
         {args}
-    ).await {{
+    ){await_call} {{
         Ok(res) => Ok(res),
         Err(e) => Err(e),
     }};
@@ -235,33 +1258,95 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
 }}
 "#,
         original_code=original_code,
-        deserialized_inputs=deserialized_inputs,
+        print_result=PRINT_RESULT,
         declarations=let_declarations,
-        args=format!("{}", str_field_types.iter().map(|field| format!("{}", field.0)).collect::<Vec<String>>().join(", ")),
-        output_type=req.output_type,
-        function_id=req.function_id,
+        args=call_args.join(", "),
+        function_path=function_path(req, &generic_args),
+        await_call=if req.is_sync { "" } else { ".await" },
     )
 }
 
+/// the path of the function relative to the crate root,
+/// instantiated with the generic arguments of the caller.
+/// `module_path!()` starts with the crate name
+fn function_path(req: &RunFunctionRequest, generic_args: &[String]) -> String {
+    let mut segments: Vec<&str> = req.module_path.split("::").skip(1).collect();
+    segments.push(&req.function_id);
+    let path = segments.join("::");
+    if generic_args.is_empty() {
+        path
+    } else {
+        format!("{}::<{}>", path, generic_args.join(", "))
+    }
+}
+
 struct Logger {
     tx: mpsc::Sender<Result<RunFunctionResponse, Status>>,
     project_dir_path: String,
+    // secret values that must never leave the server
+    redactions: Vec<String>,
+    jobs: Arc<Jobs>,
+    metrics: Arc<Metrics>,
+    job_id: String,
 }
 
 impl Logger {
-    pub fn new(tx: mpsc::Sender<Result<RunFunctionResponse, Status>>, project_dir_path: String) -> Logger {
-        Logger { tx, project_dir_path }
+    pub fn new(
+        tx: mpsc::Sender<Result<RunFunctionResponse, Status>>, 
+        project_dir_path: String,
+        redactions: Vec<String>,
+        jobs: Arc<Jobs>,
+        metrics: Arc<Metrics>,
+        job_id: String,
+    ) -> Logger {
+        Logger { tx, project_dir_path, redactions, jobs, metrics, job_id }
     }
 
     pub async fn log(&self, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        println!("{}", message);
+        let message = redact(message, &self.redactions);
+        let message = message.as_str();
+        info!("{}", message);
+        self.jobs.log(&self.job_id, message);
         self.send(RunFunctionResponse {
             response: Some(RunFunctionResult::LogLine(message.to_string())),
         }).await?;
         Ok(())
     }
 
+    /// tells the caller the id and the status of the job
+    pub async fn start(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.send(RunFunctionResponse {
+            response: Some(RunFunctionResult::JobId(self.job_id.clone())),
+        }).await?;
+        if let Some(job) = self.jobs.get(&self.job_id) {
+            self.send_job(job).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn transition(&self, status: JobStatus) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(job) = self.jobs.transition(&self.job_id, status) {
+            self.send_job(job).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_job(&self, job: JobRecord) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.tx.send(Ok(RunFunctionResponse {
+            response: Some(RunFunctionResult::Job(job.info())),
+        })).await?;
+        Ok(())
+    }
+
+    /// a result finishes the job, the caller is told before getting it
     pub async fn send(&self, response: RunFunctionResponse) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(RunFunctionResult::Result(task_result)) = &response.response {
+            if let Some(job) = self.jobs.finish_with_result(&self.job_id, task_result) {
+                let status = job.status.to_string();
+                self.metrics.increment(&metrics::JOBS_FINISHED, &[("function", &job.function), ("status", &status)]);
+                self.send_job(job).await?;
+            }
+        }
         self.tx.send(Ok(response)).await?;
         Ok(())
     }
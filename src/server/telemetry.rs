@@ -0,0 +1,41 @@
+use anyhow::Error;
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{Resource, runtime, trace::TracerProvider};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{EnvFilter, Layer, filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Logs to stdout, filtered by `RUST_LOG` (`info` by default), and records
+/// the spans with OpenTelemetry so their trace context can be passed on.
+/// The spans are exported to the OTLP collector at `otlp_endpoint`
+/// when given, e.g. `http://localhost:4317`, and dropped otherwise
+pub fn init(otlp_endpoint: Option<&str>) -> Result<(), Error> {
+    let provider = TracerProvider::builder()
+        .with_resource(Resource::new([KeyValue::new("service.name", "minimodal-server")]));
+    let provider = match otlp_endpoint {
+        Some(otlp_endpoint) => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(otlp_endpoint)
+                .build()?;
+            provider.with_batch_exporter(exporter, runtime::Tokio)
+        },
+        None => provider,
+    };
+    let provider = provider.build();
+    let tracer = provider.tracer("minimodal");
+    // keeps the provider, and its exporter, for the life of the server
+    opentelemetry::global::set_tracer_provider(provider);
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // the filter only applies to the printed logs, the spans of the server
+    // are always traced, those of tonic and hyper never are
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(filter))
+        .with(tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(Targets::new().with_target("minimodal_rs", LevelFilter::TRACE)))
+        .try_init()?;
+    Ok(())
+}
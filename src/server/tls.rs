@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Error, anyhow};
+use rcgen::{
+    BasicConstraints,
+    CertificateParams,
+    DnType,
+    ExtendedKeyUsagePurpose,
+    IsCa,
+    KeyPair,
+    KeyUsagePurpose,
+};
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use crate::server::secrets::restrict_permissions;
+
+/// The certificate and key the server presents, and the CA which must
+/// have signed the certificates of the clients for mutual TLS
+#[derive(Debug, Clone)]
+pub struct ServerTls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// clients without a certificate signed by it are rejected
+    pub client_ca: Option<PathBuf>,
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))
+}
+
+impl ServerTls {
+    pub fn config(&self) -> Result<ServerTlsConfig, Error> {
+        let config = ServerTlsConfig::new().identity(Identity::from_pem(read(&self.cert)?, read(&self.key)?));
+        Ok(match &self.client_ca {
+            Some(client_ca) => config.client_ca_root(Certificate::from_pem(read(client_ca)?)),
+            None => config,
+        })
+    }
+}
+
+/// The files written by `generate_dev_ca`
+#[derive(Debug)]
+pub struct DevCa {
+    /// trusted by the clients, and by the server for the client certificates
+    pub ca_cert: PathBuf,
+    pub server_cert: PathBuf,
+    pub server_key: PathBuf,
+    pub client_cert: PathBuf,
+    pub client_key: PathBuf,
+}
+
+fn write_key(path: &Path, key: &KeyPair) -> Result<(), Error> {
+    // never readable by others, even for a moment
+    let tmp_path = path.with_extension("key.tmp");
+    fs::write(&tmp_path, "")?;
+    restrict_permissions(&tmp_path, 0o600)?;
+    fs::write(&tmp_path, key.serialize_pem())?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Writes a self-signed CA to `dir`, with a server certificate for `names`,
+/// `localhost`, `127.0.0.1` and `::1`, and a client certificate, both signed
+/// by it. The key of the CA is not kept, so it cannot sign anything else.
+/// For development only: the certificates are valid until 4096
+pub fn generate_dev_ca(dir: &Path, names: &[String]) -> Result<DevCa, Error> {
+    fs::create_dir_all(dir)?;
+
+    let mut ca_params = CertificateParams::new(Vec::<String>::new())?;
+    ca_params.distinguished_name.push(DnType::CommonName, "minimodal development CA");
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    let ca_key = KeyPair::generate()?;
+    let ca = ca_params.self_signed(&ca_key)?;
+
+    let mut server_names = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+    server_names.extend(names.iter().filter(|name| !server_names.contains(name)).cloned().collect::<Vec<_>>());
+    let mut server_params = CertificateParams::new(server_names)?;
+    server_params.distinguished_name.push(DnType::CommonName, "minimodal server");
+    server_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    let server_key = KeyPair::generate()?;
+    let server = server_params.signed_by(&server_key, &ca, &ca_key)?;
+
+    let mut client_params = CertificateParams::new(Vec::<String>::new())?;
+    client_params.distinguished_name.push(DnType::CommonName, "minimodal client");
+    client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let client_key = KeyPair::generate()?;
+    let client = client_params.signed_by(&client_key, &ca, &ca_key)?;
+
+    let files = DevCa {
+        ca_cert: dir.join("ca.pem"),
+        server_cert: dir.join("server.pem"),
+        server_key: dir.join("server.key"),
+        client_cert: dir.join("client.pem"),
+        client_key: dir.join("client.key"),
+    };
+    fs::write(&files.ca_cert, ca.pem())?;
+    fs::write(&files.server_cert, server.pem())?;
+    write_key(&files.server_key, &server_key)?;
+    fs::write(&files.client_cert, client.pem())?;
+    write_key(&files.client_key, &client_key)?;
+    Ok(files)
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Error, anyhow};
+use serde::Deserialize;
+use tonic::{Request, Status};
+use tonic::service::Interceptor;
+use crate::server::jobs::JobRecord;
+
+/// What a token may do
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// the apps the token may deploy, roll back and call: names,
+    /// or prefixes ending with `*`, e.g. `team-a-*`, or `*` for every app
+    pub namespaces: Vec<String>,
+    /// the token may mount projects, run their functions and manage
+    /// the built executables shared by the mounts
+    pub mount: bool,
+    /// the secrets the token may create, list, run mounted functions with and
+    /// deploy functions using, names or prefixes like the namespaces
+    pub secrets: Vec<String>,
+}
+
+/// a name is matched by one of the patterns, exactly or by a prefix ending with `*`
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    })
+}
+
+impl Scope {
+    /// the scope of every request when the server has no tokens
+    pub fn unrestricted() -> Scope {
+        Scope { namespaces: vec!["*".to_string()], mount: true, secrets: vec!["*".to_string()] }
+    }
+
+    pub fn allows_app(&self, app: &str) -> bool {
+        matches_any(&self.namespaces, app)
+    }
+
+    pub fn allows_secret(&self, name: &str) -> bool {
+        matches_any(&self.secrets, name)
+    }
+
+    /// a job runs a deployed function of an app or a mounted function
+    pub fn allows_job(&self, job: &JobRecord) -> bool {
+        match job.app() {
+            Some(app) => self.allows_app(app),
+            None => self.mount,
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn require_app(&self, app: &str) -> Result<(), Status> {
+        if self.allows_app(app) {
+            Ok(())
+        } else {
+            Err(Status::permission_denied(format!("the token has no access to the app {}", app)))
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn require_mount(&self) -> Result<(), Status> {
+        if self.mount {
+            Ok(())
+        } else {
+            Err(Status::permission_denied("the token may not mount projects"))
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn require_secrets(&self, names: &[String]) -> Result<(), Status> {
+        match names.iter().find(|name| !self.allows_secret(name)) {
+            Some(name) => Err(Status::permission_denied(format!("the token has no access to the secret {}", name))),
+            None => Ok(()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn require_job(&self, job: &JobRecord) -> Result<(), Status> {
+        if self.allows_job(job) {
+            Ok(())
+        } else {
+            Err(Status::permission_denied(format!("the token has no access to the job {}", job.id)))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Token {
+    token: String,
+    #[serde(default)]
+    namespaces: Vec<String>,
+    #[serde(default)]
+    mount: bool,
+    #[serde(default)]
+    secrets: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokensFile {
+    #[serde(default)]
+    tokens: Vec<Token>,
+}
+
+/// The tokens accepted by the server, read from a TOML file, e.g.
+/// ```toml
+/// [[tokens]]
+/// token = "4f0c..."
+/// namespaces = ["prod", "team-a-*"]
+/// mount = true
+/// secrets = ["team-a-*"]
+/// ```
+pub struct TokenStore {
+    tokens: Vec<Token>,
+}
+
+/// compares in a time which does not depend on where the tokens differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl TokenStore {
+    pub fn load(path: impl AsRef<Path>) -> Result<TokenStore, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read the tokens file {}: {}", path.display(), e))?;
+        TokenStore::parse(&content)
+            .map_err(|e| anyhow!("invalid tokens file {}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<TokenStore, Error> {
+        let file: TokensFile = toml::from_str(content)?;
+        for (index, token) in file.tokens.iter().enumerate() {
+            if token.token.len() < 16 {
+                return Err(anyhow!("token {} is shorter than 16 characters", index + 1));
+            }
+            if file.tokens[..index].iter().any(|other| other.token == token.token) {
+                return Err(anyhow!("token {} is listed twice", index + 1));
+            }
+        }
+        Ok(TokenStore { tokens: file.tokens })
+    }
+
+    /// the scope of a token, `None` for an unknown token
+    pub fn scope(&self, token: &str) -> Option<Scope> {
+        self.tokens.iter()
+            .find(|known| constant_time_eq(known.token.as_bytes(), token.as_bytes()))
+            .map(|known| Scope {
+                namespaces: known.namespaces.clone(),
+                mount: known.mount,
+                secrets: known.secrets.clone(),
+            })
+    }
+
+    /// the scope of the token in the `authorization` header of a request, `Bearer <token>`
+    #[allow(clippy::result_large_err)]
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<Scope, Status> {
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing bearer token"))?;
+        self.scope(token.trim()).ok_or_else(|| Status::unauthenticated("invalid token"))
+    }
+}
+
+/// Rejects the requests without a known bearer token and gives the
+/// others the scope of their token, every request is in scope without tokens
+#[derive(Clone)]
+pub struct Authenticator {
+    tokens: Option<Arc<TokenStore>>,
+}
+
+impl Authenticator {
+    pub fn new(tokens: Option<Arc<TokenStore>>) -> Authenticator {
+        Authenticator { tokens }
+    }
+}
+
+impl Interceptor for Authenticator {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let scope = match &self.tokens {
+            None => Scope::unrestricted(),
+            Some(tokens) => {
+                let authorization = request.metadata()
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok());
+                tokens.authenticate(authorization)?
+            },
+        };
+        request.extensions_mut().insert(scope);
+        Ok(request)
+    }
+}
+
+/// the scope the authenticator gave the request
+#[allow(clippy::result_large_err)]
+pub fn scope<T>(request: &Request<T>) -> Result<Scope, Status> {
+    request.extensions()
+        .get::<Scope>()
+        .cloned()
+        .ok_or_else(|| Status::unauthenticated("the request was not authenticated"))
+}
@@ -0,0 +1,83 @@
+use anyhow::{Error, anyhow};
+use syn::{visit_mut::VisitMut, Expr, ExprUnary, Path, PathSegment, Type, Ident, UnOp};
+use proc_macro2::Span;
+
+/// `std::any::type_name` returns the path where a type is defined, which is
+/// not always a path the type can be named by. Maps those definition paths
+/// onto their public re-exports.
+const STD_PATHS: [(&str, &str); 7] = [
+    ("std::collections::hash::map", "std::collections::hash_map"),
+    ("std::collections::hash::set", "std::collections::hash_set"),
+    ("alloc::collections::btree::map", "std::collections::btree_map"),
+    ("alloc::collections::btree::set", "std::collections::btree_set"),
+    // alloc and core are re-exported by std under the same module names
+    ("alloc", "std"),
+    ("core", "std"),
+    ("std", "std"),
+];
+
+struct TypeNameResolver<'a> {
+    crate_name: &'a str,
+}
+
+impl<'a> TypeNameResolver<'a> {
+    fn rewrite_prefix(&self, path: &mut Path) {
+        let segments: Vec<String> = path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+
+        if segments.first().map(String::as_str) == Some(self.crate_name) {
+            // types of the mounted crate are inlined into the entrypoint
+            path.segments[0].ident = Ident::new("crate", Span::call_site());
+            path.leading_colon = None;
+            return;
+        }
+
+        for (from, to) in STD_PATHS.iter() {
+            let from: Vec<&str> = from.split("::").collect();
+            let matches = segments.len() > from.len()
+                && segments.iter().zip(from.iter()).all(|(segment, from)| segment == from);
+            if !matches {
+                continue;
+            }
+            let mut new_segments: Vec<PathSegment> = to.split("::")
+                .map(|segment| PathSegment::from(Ident::new(segment, Span::call_site())))
+                .collect();
+            new_segments.extend(path.segments.iter().skip(from.len()).cloned());
+            path.segments = new_segments.into_iter().collect();
+            path.leading_colon = Some(Default::default());
+            return;
+        }
+    }
+}
+
+impl<'a> VisitMut for TypeNameResolver<'a> {
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        self.rewrite_prefix(path);
+        syn::visit_mut::visit_path_mut(self, path);
+    }
+}
+
+/// Turns the output of `std::any::type_name` into a type that can be named
+/// from the generated entrypoint, where the mounted crate is inlined.
+pub fn resolve_type_name(type_name: &str, crate_name: &str) -> Result<String, Error> {
+    let mut ty: Type = syn::parse_str(type_name)
+        .map_err(|e| anyhow!("type {} can't be named: {}", type_name, e))?;
+
+    let mut resolver = TypeNameResolver { crate_name };
+    resolver.visit_type_mut(&mut ty);
+
+    Ok(quote::quote!(#ty).to_string())
+}
+
+/// Checks the value of a const generic argument is a literal, possibly negated,
+/// as the `{:?}` of the integers, `bool`s and `char`s consts can have.
+pub fn resolve_const_value(value: &str) -> Result<String, Error> {
+    let expr: Expr = syn::parse_str(value)
+        .map_err(|e| anyhow!("const value {} is not a literal: {}", value, e))?;
+    match &expr {
+        Expr::Lit(_) => Ok(quote::quote!(#expr).to_string()),
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr: inner, .. }) if matches!(**inner, Expr::Lit(_)) => {
+            Ok(quote::quote!(#expr).to_string())
+        },
+        _ => Err(anyhow!("const value {} is not a literal", value)),
+    }
+}
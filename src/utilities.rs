@@ -26,6 +26,17 @@ pub fn _declare_values_from_json(
     Ok(values.join("\n"))
 }
 
+/// declares an argument of the entrypoint from its serialized value,
+/// the type is only spelled out when it can't be inferred from the call
+pub fn declare_value_from_inputs(name: &str, ty: Option<&str>, mutable: bool) -> String {
+    format!(
+        "let {mutability}{name}{annotation} = serde_json::from_value(inputs.get(\"{name}\").cloned().ok_or(\"key {name} not found in inputs\")?)?;",
+        mutability = if mutable { "mut " } else { "" },
+        name = name,
+        annotation = ty.map(|ty| format!(": {}", ty)).unwrap_or_default(),
+    )
+}
+
 pub fn serialize_inputs<'a>(
     arg_names: &[&str], 
     arg_values: &[&dyn erased_serde::Serialize]
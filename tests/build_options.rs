@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+use minimodal_proto::proto::minimodal::BuildOptions;
+use minimodal_rs::server::build_options::{cache_key, cargo_args, target_dir, validate};
+
+fn options(profile: &str, features: &[&str], rustflags: &str) -> BuildOptions {
+    BuildOptions {
+        profile: profile.to_string(),
+        features: features.iter().map(|f| f.to_string()).collect(),
+        rustflags: rustflags.to_string(),
+    }
+}
+
+#[test]
+fn test_default_build_uses_regular_target_dir() {
+    assert_eq!(target_dir(&BuildOptions::default()), PathBuf::from("target"));
+    assert_eq!(target_dir(&options("dev", &[], "")), PathBuf::from("target"));
+    assert!(cargo_args(&BuildOptions::default()).is_empty());
+}
+
+#[test]
+fn test_builds_with_different_options_are_cached_separately() {
+    let release = options("release", &[], "");
+    let release_simd = options("release", &["simd"], "");
+    let release_native = options("release", &["simd"], "-C target-cpu=native");
+
+    assert_ne!(target_dir(&release), target_dir(&release_simd));
+    assert_ne!(target_dir(&release_simd), target_dir(&release_native));
+    // feature order does not matter
+    assert_eq!(
+        cache_key(&options("release", &["a", "b"], "")),
+        cache_key(&options("release", &["b", "a"], ""))
+    );
+    // the key names directories kept across server and toolchain upgrades
+    assert_eq!(cache_key(&release), "afb9c12402d222f4");
+}
+
+#[test]
+fn test_cargo_args() {
+    assert_eq!(
+        cargo_args(&options("release", &["simd", "fast"], "")),
+        vec!["--profile", "release", "--features", "simd,fast"]
+    );
+    assert!(validate(&options("release", &["simd"], "-C target-cpu=native")).is_ok());
+    assert!(validate(&options("--release", &[], "")).is_err());
+    assert!(validate(&options("release", &["simd fast"], "")).is_err());
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use minimodal_rs::parse_file::RemoteFunction;
+use minimodal_rs::server::deployments::{DeploymentStore, Deployment, DeployedFunction};
+use minimodal_rs::server::dispatcher::dispatcher_code;
+
+fn deployment(version: u32) -> Deployment {
+    Deployment {
+        version,
+        created_at: 1_700_000_000,
+        functions: vec![DeployedFunction {
+            package: "app".to_string(),
+            crate_name: "app".to_string(),
+            is_bin: false,
+            path: "jobs::train".to_string(),
+            secrets: vec!["db".to_string()],
+            executable: ".minimodal/bin/__minimodal_lib_app-0".to_string(),
+            schedule: String::new(),
+            period: String::new(),
+            args: vec!["epochs".to_string()],
+            web_endpoint: true,
+        }],
+        skipped: Vec::new(),
+    }
+}
+
+#[test]
+fn test_versions_and_rollback() {
+    let dir = std::env::temp_dir().join(format!("minimodal-deployments-{}", uuid::Uuid::new_v4()));
+    let store = DeploymentStore::new(&dir).unwrap();
+
+    assert_eq!(store.next_version("prod").unwrap(), 1);
+    store.add("prod", deployment(1)).unwrap();
+    store.add("prod", deployment(2)).unwrap();
+    assert!(store.add("prod", deployment(2)).is_err());
+    assert_eq!(store.next_version("prod").unwrap(), 3);
+
+    let (version_dir, function) = store.find_function("prod", "app", "app", "", "jobs::train").unwrap();
+    assert_eq!(version_dir, store.version_dir("prod", 2).unwrap());
+    assert_eq!(function.name(), "app::jobs::train");
+    assert!(store.find_function("prod", "app", "app", "app", "jobs::train").is_err());
+    assert_eq!(store.find_endpoint("prod", "app::jobs::train").unwrap().1, function);
+    assert_eq!(store.find_endpoint("prod", "jobs::train").unwrap().1, function);
+    assert!(store.find_endpoint("prod", "jobs::score").is_err());
+
+    assert_eq!(store.rollback("prod", 0).unwrap(), 1);
+    assert!(store.rollback("prod", 0).is_err());
+    assert!(store.rollback("prod", 7).is_err());
+    assert!(store.rollback("staging", 1).is_err());
+
+    // the versions outlive the store
+    let reopened = DeploymentStore::new(&dir).unwrap();
+    let apps = reopened.list().unwrap();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0].0, "prod");
+    assert_eq!(apps[0].1.active_version, 1);
+    assert_eq!(apps[0].1.versions.len(), 2);
+
+    assert!(store.app_dir("../prod").is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dispatcher_code() {
+    let functions = vec![
+        RemoteFunction {
+            modules: vec!["jobs".to_string()],
+            name: "train".to_string(),
+            args: vec!["epochs".to_string()],
+            is_async: true,
+            ..Default::default()
+        },
+        RemoteFunction {
+            name: "ping".to_string(),
+            ..Default::default()
+        },
+    ];
+    let code = dispatcher_code("mod jobs {}", &functions);
+    assert!(code.starts_with("//imports\n\nmod jobs {}"));
+    assert!(code.contains("\"jobs::train\" => {"));
+    assert!(code.contains("let epochs = serde_json::from_value(inputs.get(\"epochs\")"));
+    assert!(code.contains("print_result!(jobs::train(epochs).await);"));
+    assert!(code.contains("print_result!(ping());"));
+    assert!(code.contains("macro_rules! print_result"));
+}
@@ -5,8 +5,8 @@ use macros::function;
 use std::process::{Command, Child};
 use std::time::Duration;
 use tokio;
-use tokio::time::sleep;
 use basemodules::MiniModalError;
+use basemodules::session::DEFAULT_ADDR;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
 use std::future::Future;
@@ -29,7 +29,7 @@ where
 #[fixture]
 async fn server() -> Child {
     let server = test_utils::start_server(None).expect("Failed to start server");
-    sleep(Duration::from_secs(2)).await;
+    test_utils::wait_until_serving(DEFAULT_ADDR, Duration::from_secs(300)).await.expect("the server did not start");
     server
 }
 
@@ -61,6 +61,21 @@ async fn map_fn(a: i32) -> Result<i32, MiniModalError> {
     Ok(a)
 }
 
+#[function]
+fn sync_fn(a: i32, b: i32) -> Result<i32, MiniModalError> {
+    Ok(a + b)
+}
+
+#[function]
+async fn borrowed_args(name: &str, values: &[i32], (a, b): (i32, i32), suffix: impl AsRef<str>) -> Result<String, MiniModalError> {
+    Ok(format!("{}{}{}", name, values.iter().sum::<i32>() + a + b, suffix.as_ref()))
+}
+
+#[function]
+async fn repeat_char<const N: usize, const C: char>(prefix: String) -> Result<String, MiniModalError> {
+    Ok(format!("{}{}", prefix, C.to_string().repeat(N)))
+}
+
 #[rstest]
 #[case::local((lala::<i32>::local, 1))]
 #[case::remote((lala::<i32>::remote, 1))]
@@ -68,6 +83,12 @@ async fn map_fn(a: i32) -> Result<i32, MiniModalError> {
 #[case::local((df_test_deserialize::local, PolarsDataFrame(DataFrame::new(vec![Series::new("col1", vec![1, 2, 3])]).unwrap())))]
 #[case::remote((multi_arg::remote, (1, 2)))]
 #[case::local((multi_arg::local, (1, 2)))]
+#[case::remote((sync_fn::remote, (1, 2)))]
+#[case::local((sync_fn::local, (1, 2)))]
+#[case::remote((borrowed_args::remote, ("sum".to_string(), vec![1, 2], (3, 4), "!".to_string())))]
+#[case::local((borrowed_args::local, ("sum".to_string(), vec![1, 2], (3, 4), "!".to_string())))]
+#[case::remote((repeat_char::<3, 'x'>::remote, "a".to_string()))]
+#[case::local((repeat_char::<3, 'x'>::local, "a".to_string()))]
 #[tokio::test]
 async fn test_function<I, O, F>(#[future] server: Child, #[case] func_input: (F, I))
 where
@@ -75,26 +96,49 @@ where
     O: Serialize + for<'de> Deserialize<'de> + Send + Sync + Debug + 'static,
     F: Fn(I) -> Pin<Box<dyn Future<Output = Result<O, MiniModalError>> + Send + 'static>> + Send + 'static
 {
+    let _server = server.await;
     let result = process_call((func_input.0)(func_input.1)).await;
     assert!(result);
 }
 
+#[rstest]
+#[tokio::test]
+async fn test_call_with_original_parameters(#[future] server: Child) {
+    let _server = server.await;
+    assert_eq!(multi_arg::call(1, 2).local().await.unwrap(), vec![1, 2]);
+    assert_eq!(multi_arg::local_args(1, 2).await.unwrap(), vec![1, 2]);
+    assert_eq!(multi_arg::remote_args(1, 2).await.unwrap(), vec![1, 2]);
+    assert_eq!(
+        borrowed_args::call("sum", &[1, 2], (3, 4), "!").remote().await.unwrap(),
+        "sum10!"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_remote_generic_instantiation(#[future] server: Child) {
+    let _server = server.await;
+    assert_eq!(lala::<String>::remote("a".to_string()).await.unwrap(), vec![1, 2, 3]);
+    assert_eq!(repeat_char::<3, 'x'>::remote("a".to_string()).await.unwrap(), "axxx");
+    assert_eq!(repeat_char::<2, 'y'>::remote_args("b".to_string()).await.unwrap(), "byy");
+}
+
 
 #[rstest]
 #[case::map((map_fn::map, vec![1, 2, 3]))]
 #[tokio::test]
 async fn test_map_fn<F, I, O>(
-    #[case] func_input: (F, I)
+    #[future] server: Child,
+    #[case] func_input: (F, Vec<I>)
 ) 
 where
     I: Serialize + for<'de> Deserialize<'de> + Send + Sync + Debug + 'static,
     O: Serialize + for<'de> Deserialize<'de> + Send + Sync + Debug + 'static,
-    F: Fn(I) -> Result<Vec<Pin<Box<dyn Future<Output = Result<O, MiniModalError>> + Send>>>, MiniModalError> + Send + 'static,
+    F: Fn(Vec<I>) -> Pin<Box<dyn Future<Output = Vec<Result<O, MiniModalError>>> + Send>> + Send + 'static,
 {
-    let results = (func_input.0(func_input.1));
-    match results {
-        Ok(r) => assert!(r.len() == 3),
-        Err(e) => assert!(false),
-    }
+    let _server = server.await;
+    let results = func_input.0(func_input.1).await;
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok), "{:?}", results);
 }
 
@@ -2,9 +2,9 @@
 mod test_utils;
 use std::time::Duration;
 use tokio;
-use tokio::time::sleep;
-use minimodal_proto::proto::minimodal::mini_modal_client::MiniModalClient;
+use basemodules::session::{Session, DEFAULT_ADDR};
 use minimodal_rs::mount::mount_project;
+use minimodal_rs::mount_rules::MountRules;
 
 #[tokio::test]
 async fn test_grpc_server() {
@@ -14,9 +14,12 @@ async fn test_grpc_server() {
         Ok(child) => child,
         Err(e) => panic!("Failed to start server: {}", e),
     };
-    sleep(Duration::from_secs(2)).await;
+    test_utils::wait_until_serving(DEFAULT_ADDR, Duration::from_secs(300)).await.unwrap();
 
-    let mut client = MiniModalClient::connect("http://[::1]:50051").await.unwrap();
-    let req = mount_project(&mut client, vec![".git".to_string(), "minimodal_proto".to_string(), "macros".to_string(), "src/server".to_string()]).await.unwrap();
+    let mut client = Session::new(DEFAULT_ADDR, None).connect().await.unwrap();
+    let req = mount_project(&mut client, &MountRules {
+        exclude: vec!["minimodal_proto".to_string(), "macros".to_string(), "src/server".to_string()],
+        ..MountRules::default()
+    }, None).await.unwrap();
     server.kill().expect("Failed to kill server");
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use std::fs;
+use std::sync::Arc;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+use minimodal_rs::server::deployments::{DeploymentStore, Deployment, DeployedFunction};
+use minimodal_rs::server::http::{router, HttpState};
+use minimodal_rs::server::jobs::Jobs;
+use minimodal_rs::server::metrics::Metrics;
+use minimodal_rs::server::secrets::SecretStore;
+use minimodal_rs::server::tokens::TokenStore;
+
+fn function(path: &str, web_endpoint: bool) -> DeployedFunction {
+    DeployedFunction {
+        package: "app".to_string(),
+        crate_name: "app".to_string(),
+        is_bin: false,
+        path: path.to_string(),
+        secrets: Vec::new(),
+        executable: "dispatcher.sh".to_string(),
+        schedule: String::new(),
+        period: String::new(),
+        args: vec!["a".to_string(), "b".to_string()],
+        web_endpoint,
+    }
+}
+
+/// a deployment whose dispatcher answers with the call it received
+fn state() -> (HttpState, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("minimodal-http-{}", uuid::Uuid::new_v4()));
+    let deployments = DeploymentStore::new(dir.join("deployments")).unwrap();
+    deployments.add("prod", Deployment {
+        version: 1,
+        created_at: 0,
+        functions: vec![function("add", true), function("internal", false)],
+        skipped: Vec::new(),
+    }).unwrap();
+
+    let version_dir = deployments.version_dir("prod", 1).unwrap();
+    fs::create_dir_all(&version_dir).unwrap();
+    let dispatcher = version_dir.join("dispatcher.sh");
+    fs::write(&dispatcher, "#!/bin/sh\ncall=$(cat)\necho \"RESULT_START{\\\"success\\\": $call}RESULT_END\"\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dispatcher, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let state = HttpState {
+        deployments: Arc::new(deployments),
+        secrets: Arc::new(SecretStore::new(dir.join("secrets")).unwrap()),
+        jobs: Arc::new(Jobs::new()),
+        metrics: Arc::new(Metrics::new()),
+        tokens: None,
+    };
+    (state, dir)
+}
+
+async fn post(state: &HttpState, uri: &str, body: &str) -> (StatusCode, Value) {
+    post_with_token(state, uri, body, None).await
+}
+
+async fn post_with_token(state: &HttpState, uri: &str, body: &str, token: Option<&str>) -> (StatusCode, Value) {
+    let mut request = Request::post(uri);
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    let request = request.body(Body::from(body.to_string())).unwrap();
+    let response = router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_call_web_endpoint() {
+    let (state, dir) = state();
+
+    let (status, body) = post(&state, "/apps/prod/app::add", r#"{"a": 1, "b": 2}"#).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], json!({"function": "add", "inputs": {"a": 1, "b": 2}}));
+    let job_id = body["job_id"].as_str().unwrap();
+    let job = &state.jobs.list()[0];
+    assert_eq!(job.id, job_id);
+    assert_eq!((job.caller.as_str(), job.mount_id.as_str()), ("http", "prod/v1"));
+
+    // by path and with the arguments in order
+    let (status, body) = post(&state, "/apps/prod/add", "[3, 4]").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"]["inputs"], json!({"a": 3, "b": 4}));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_web_endpoint_errors() {
+    let (state, dir) = state();
+
+    let (status, body) = post(&state, "/apps/prod/internal", "[1, 2]").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["kind"], "not_found");
+    assert_eq!(post(&state, "/apps/staging/add", "[1, 2]").await.0, StatusCode::NOT_FOUND);
+
+    let (status, body) = post(&state, "/apps/prod/add", "{").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["kind"], "invalid_json");
+
+    let (status, body) = post(&state, "/apps/prod/add", r#"{"a": 1}"#).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["kind"], "invalid_arguments");
+    assert_eq!(body["error"]["message"], "missing argument b of app::add");
+    assert!(state.jobs.list().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_web_endpoints_require_a_token_with_access_to_the_app() {
+    let (mut state, dir) = state();
+    let tokens = "[[tokens]]\ntoken = \"prod-0123456789abcdef\"\nnamespaces = [\"prod\"]\n\n\
+                  [[tokens]]\ntoken = \"staging-0123456789abcdef\"\nnamespaces = [\"staging\"]\n";
+    state.tokens = Some(Arc::new(TokenStore::parse(tokens).unwrap()));
+
+    let (status, body) = post(&state, "/apps/prod/add", "[1, 2]").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["kind"], "unauthenticated");
+    let (status, _) = post_with_token(&state, "/apps/prod/add", "[1, 2]", Some("unknown-0123456789abcdef")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = post_with_token(&state, "/apps/prod/add", "[1, 2]", Some("staging-0123456789abcdef")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["kind"], "permission_denied");
+    assert!(state.jobs.list().is_empty());
+
+    let (status, body) = post_with_token(&state, "/apps/prod/add", "[1, 2]", Some("prod-0123456789abcdef")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"]["inputs"], json!({"a": 1, "b": 2}));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
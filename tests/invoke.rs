@@ -0,0 +1,80 @@
+use std::path::Path;
+use serde_json::json;
+use minimodal_rs::invoke::{module_path_of, build_request};
+use minimodal_rs::mount::MountTarget;
+use minimodal_rs::parse_file::find_remote_functions;
+
+fn lib_target() -> MountTarget {
+    MountTarget {
+        package: "app".to_string(),
+        kind: "lib".to_string(),
+        crate_name: "app".to_string(),
+        root: "src/lib.rs".to_string(),
+        source: ".minimodal/targets/lib_app.rs".to_string(),
+        entry: "src/__minimodal_lib_app.rs".to_string(),
+        entry_bin: "__minimodal_lib_app".to_string(),
+        functions: Vec::new(),
+    }
+}
+
+#[test]
+fn test_module_path_of() {
+    let root = Path::new("/app/src/lib.rs");
+    assert_eq!(module_path_of(root, root), Some(vec![]));
+    assert_eq!(module_path_of(Path::new("/app/src/jobs/train.rs"), root), Some(vec!["jobs".to_string(), "train".to_string()]));
+    assert_eq!(module_path_of(Path::new("/app/src/jobs/mod.rs"), root), Some(vec!["jobs".to_string()]));
+    assert_eq!(module_path_of(Path::new("/other/jobs.rs"), root), None);
+}
+
+#[test]
+fn test_find_remote_functions() {
+    let ast = syn::parse_file(r#"
+        #[function(secrets = ["db"], profile = "release")]
+        async fn train(epochs: u32, (a, b): (i32, i32)) -> Result<u32, MiniModalError> { Ok(epochs) }
+
+        fn helper() {}
+
+        mod jobs {
+            #[function]
+            fn score(name: &str) -> Result<String, MiniModalError> { Ok(name.to_string()) }
+
+            #[function(schedule = "0 3 * * *")]
+            async fn nightly() -> Result<(), MiniModalError> { Ok(()) }
+        }
+    "#).unwrap();
+    let functions = find_remote_functions(&ast, &["function".to_string()]).unwrap();
+    assert_eq!(functions.len(), 3);
+
+    let train = &functions[0];
+    assert_eq!(train.args, vec!["epochs", "arg1"]);
+    assert!(train.is_async);
+    assert_eq!(train.secrets, vec!["db"]);
+    assert_eq!(train.profile, "release");
+
+    let score = &functions[1];
+    assert_eq!(score.modules, vec!["jobs"]);
+    assert!(score.borrows);
+
+    assert_eq!(functions[2].schedule, "0 3 * * *");
+    assert!(functions[2].period.is_empty());
+}
+
+#[test]
+fn test_build_request() {
+    let ast = syn::parse_file(r#"
+        mod jobs {
+            #[function]
+            async fn train(epochs: u32, rate: f64) -> Result<u32, MiniModalError> { Ok(epochs) }
+        }
+    "#).unwrap();
+    let train = find_remote_functions(&ast, &["function".to_string()]).unwrap().remove(0);
+
+    let request = build_request(&lib_target(), &train, json!([3, 0.1])).unwrap();
+    assert_eq!(request.module_path, "app::jobs");
+    assert_eq!(request.function_id, "train");
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&request.serialized_inputs).unwrap(), json!({"epochs": 3, "rate": 0.1}));
+    assert!(!request.is_sync);
+
+    assert!(build_request(&lib_target(), &train, json!({"epochs": 3})).is_err());
+    assert!(build_request(&lib_target(), &train, json!([3])).is_err());
+}
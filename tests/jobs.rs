@@ -0,0 +1,184 @@
+use std::fs;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use basemodules::{FunctionCall, MiniModalError};
+use minimodal_proto::proto::minimodal::TaskResult;
+use minimodal_rs::server::jobs::{Jobs, JobStatus};
+use minimodal_rs::server::build_options::{copied_executable_path, leftover_executables};
+
+#[tokio::test]
+async fn test_job_lifecycle() {
+    let jobs = Jobs::new();
+    let (tx, _rx) = mpsc::channel(10);
+    let id = jobs.start("app::train".to_string(), "mount-1".to_string(), "[::1]:4000".to_string(), tx);
+    jobs.log(&id, "building");
+    let job = jobs.get(&id).unwrap();
+    assert_eq!((job.function.as_str(), job.status), ("app::train", JobStatus::Queued));
+    assert_eq!((job.mount_id.as_str(), job.caller.as_str()), ("mount-1", "[::1]:4000"));
+    assert_eq!((job.started_at, job.finished_at), (None, None));
+    assert_eq!(jobs.list(), vec![job]);
+
+    jobs.finish(&id, true);
+    assert_eq!(jobs.list()[0].status, JobStatus::Succeeded);
+    assert_eq!(jobs.logs(&id), Some(vec!["building".to_string()]));
+    assert!(jobs.cancel(&id).is_err());
+
+    assert_eq!(jobs.collect_finished(), vec![id.clone()]);
+    assert!(jobs.list().is_empty());
+    assert!(jobs.logs(&id).is_none());
+}
+
+#[tokio::test]
+async fn test_cancel_aborts_the_job_and_tells_the_caller() {
+    let jobs = Jobs::new();
+    let (tx, mut rx) = mpsc::channel(10);
+    let id = jobs.start("app::train".to_string(), String::new(), String::new(), tx);
+    let task = tokio::spawn(std::future::pending::<()>());
+    jobs.set_abort_handle(&id, task.abort_handle());
+
+    jobs.cancel(&id).unwrap();
+    assert!(task.await.unwrap_err().is_cancelled());
+    assert_eq!(rx.recv().await.unwrap().unwrap_err().code(), tonic::Code::Cancelled);
+
+    // a late result does not overwrite the cancellation
+    jobs.finish(&id, true);
+    assert_eq!(jobs.list()[0].status, JobStatus::Cancelled);
+    assert!(jobs.cancel("unknown").is_err());
+}
+
+#[tokio::test]
+async fn test_job_states_move_forward() {
+    let jobs = Jobs::new();
+    let (tx, _rx) = mpsc::channel(10);
+    let id = jobs.start("app::train".to_string(), String::new(), String::new(), tx);
+
+    let building = jobs.transition(&id, JobStatus::Building).unwrap();
+    assert_eq!(building.status, JobStatus::Building);
+    let started_at = building.started_at.unwrap();
+    assert!(jobs.transition(&id, JobStatus::Building).is_none());
+    let running = jobs.transition(&id, JobStatus::Running).unwrap();
+    // started when it left the queue
+    assert_eq!(running.started_at, Some(started_at));
+    assert!(jobs.transition(&id, JobStatus::Succeeded).is_none());
+
+    let result = TaskResult { success: false, message: "boom".to_string() };
+    let failed = jobs.finish_with_result(&id, &result).unwrap();
+    assert_eq!(failed.status, JobStatus::Failed);
+    assert!(failed.finished_at.unwrap() >= started_at);
+    assert_eq!(failed.info().status, "failed");
+    assert!(jobs.transition(&id, JobStatus::Running).is_none());
+    assert!(jobs.finish(&id, true).is_none());
+    assert!(jobs.get("unknown").is_none());
+}
+
+#[tokio::test]
+async fn test_wait_for_the_result_of_a_job() {
+    let jobs = std::sync::Arc::new(Jobs::new());
+    let (tx, _rx) = mpsc::channel(10);
+    let id = jobs.start("app::train".to_string(), String::new(), String::new(), tx);
+    assert_eq!(jobs.wait(&id, Duration::ZERO).await, Some((JobStatus::Queued, None)));
+
+    let result = TaskResult { success: true, message: "42".to_string() };
+    let finisher = {
+        let (jobs, id, result) = (jobs.clone(), id.clone(), result.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            jobs.finish_with_result(&id, &result);
+        })
+    };
+    assert_eq!(jobs.wait(&id, Duration::from_secs(10)).await, Some((JobStatus::Succeeded, Some(result))));
+    finisher.await.unwrap();
+    assert!(jobs.wait("unknown", Duration::ZERO).await.is_none());
+}
+
+#[tokio::test]
+async fn test_submitted_results_are_kept_for_the_retention() {
+    let jobs = Jobs::with_result_retention(Duration::from_millis(100));
+    let (tx, _rx) = mpsc::channel(10);
+    let submitted = jobs.start("app::train".to_string(), String::new(), String::new(), tx.clone());
+    jobs.retain_result(&submitted);
+    let streamed = jobs.start("app::train".to_string(), String::new(), String::new(), tx);
+    let result = TaskResult { success: false, message: "boom".to_string() };
+    jobs.finish_with_result(&submitted, &result);
+    jobs.finish_with_result(&streamed, &result);
+
+    assert_eq!(jobs.collect_finished(), vec![streamed]);
+    assert!(jobs.expire_results().is_empty());
+    assert_eq!(jobs.result(&submitted), Some((JobStatus::Failed, Some(result))));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(jobs.expire_results(), vec![submitted.clone()]);
+    assert!(jobs.result(&submitted).is_none());
+}
+
+#[tokio::test]
+async fn test_finished_jobs_expire_by_age_and_count() {
+    let jobs = Jobs::with_result_retention(Duration::from_millis(100)).with_job_history(2);
+    let (tx, _rx) = mpsc::channel(10);
+    let ids: Vec<String> = (0..3)
+        .map(|_| jobs.start("app::tick".to_string(), String::new(), String::new(), tx.clone()))
+        .collect();
+    let running = jobs.start("app::train".to_string(), String::new(), String::new(), tx);
+    for id in ids.iter() {
+        jobs.finish(id, true);
+    }
+
+    // the oldest beyond the history
+    assert_eq!(jobs.expire_results(), vec![ids[0].clone()]);
+    assert_eq!(jobs.list().len(), 3);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(jobs.expire_results(), ids[1..].to_vec());
+    assert_eq!(jobs.list().iter().map(|job| job.id.clone()).collect::<Vec<_>>(), vec![running]);
+}
+
+#[tokio::test]
+async fn test_drain_waits_for_the_jobs_then_cancels_them() {
+    let jobs = std::sync::Arc::new(Jobs::new());
+    let (tx, _rx) = mpsc::channel(10);
+    let quick = jobs.start("app::quick".to_string(), String::new(), String::new(), tx.clone());
+    let slow = jobs.start("app::slow".to_string(), String::new(), String::new(), tx);
+    let task = tokio::spawn(std::future::pending::<()>());
+    jobs.set_abort_handle(&slow, task.abort_handle());
+    let finisher = {
+        let (jobs, quick) = (jobs.clone(), quick.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            jobs.finish(&quick, true);
+        })
+    };
+
+    jobs.close();
+    assert!(jobs.is_closed());
+    assert_eq!(jobs.drain(Duration::from_millis(300)).await, vec![slow.clone()]);
+    assert_eq!(jobs.status(&quick), Some(JobStatus::Succeeded));
+    assert_eq!(jobs.status(&slow), Some(JobStatus::Cancelled));
+    assert!(task.await.unwrap_err().is_cancelled());
+    assert!(jobs.unfinished().is_empty());
+    finisher.await.unwrap();
+}
+
+#[test]
+fn test_function_call_handle_is_serializable() {
+    let call: FunctionCall<Result<i32, MiniModalError>> = FunctionCall::new("1234".to_string());
+    let serialized = serde_json::to_string(&call).unwrap();
+    assert_eq!(serialized, r#"{"job_id":"1234"}"#);
+    let deserialized: FunctionCall<Result<i32, MiniModalError>> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, call);
+    assert_eq!(deserialized.job_id(), "1234");
+}
+
+#[test]
+fn test_leftover_executables() {
+    let project_dir = std::env::temp_dir().join(format!("minimodal-gc-{}", uuid::Uuid::new_v4()));
+    let debug_dir = project_dir.join("target").join("debug");
+    fs::create_dir_all(&debug_dir).unwrap();
+
+    let built = debug_dir.join("__minimodal_lib_app");
+    let copy = copied_executable_path(&built, "__minimodal_lib_app");
+    fs::write(&built, b"").unwrap();
+    fs::write(&copy, b"").unwrap();
+
+    assert_eq!(leftover_executables(&project_dir), vec![copy]);
+    fs::remove_dir_all(&project_dir).unwrap();
+}
@@ -0,0 +1,7 @@
+// the misuses of #[function] are reported on the offending tokens, the
+// expected errors are in tests/ui/*.stderr, `TRYBUILD=overwrite` updates them
+#[test]
+fn test_function_misuse_is_reported() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,246 @@
+use std::fs;
+use minimodal_rs::manifest::{
+    build_cargo_toml,
+    parse_manifest,
+    relative_between,
+    renamed_runtime_dependencies,
+    resolve_workspace_inheritance,
+    rewrite_dependency_manifest,
+    PathDependencies,
+    PATH_DEPENDENCIES_DIR,
+};
+use minimodal_rs::mount::MountTarget;
+
+#[path = "test_utils.rs"]
+mod test_utils;
+use test_utils::{write, TempDir};
+
+fn manifest(content: &str) -> toml::Table {
+    parse_manifest(content.as_bytes(), "Cargo.toml").unwrap()
+}
+
+#[test]
+fn test_relative_between() {
+    assert_eq!(relative_between("crates/app", ""), "../..");
+    assert_eq!(relative_between("", "crates/app"), "crates/app");
+    assert_eq!(relative_between("crates/app", "crates/core"), "../core");
+    assert_eq!(relative_between("crates/app", ".minimodal/deps/helper"), "../../.minimodal/deps/helper");
+    assert_eq!(relative_between("crates/app", "crates/app"), ".");
+}
+
+#[test]
+fn test_resolve_workspace_inheritance() {
+    let workspace = manifest(r#"
+[workspace]
+members = ["crates/app"]
+
+[workspace.package]
+version = "0.3.0"
+edition = "2021"
+readme = "README.md"
+
+[workspace.dependencies]
+serde = { version = "1", features = ["derive"] }
+core = { path = "crates/core" }
+
+[workspace.lints.rust]
+unsafe_code = "forbid"
+"#);
+    let mut member = manifest(r#"
+[package]
+name = "app"
+version.workspace = true
+edition = { workspace = true }
+readme.workspace = true
+
+[dependencies]
+serde = { workspace = true, features = ["rc"], optional = true }
+core.workspace = true
+
+[target.'cfg(unix)'.dev-dependencies]
+serde.workspace = true
+
+[lints]
+workspace = true
+"#);
+    resolve_workspace_inheritance(&mut member, &workspace, "../..").unwrap();
+
+    let package = &member["package"];
+    assert_eq!(package["version"].as_str(), Some("0.3.0"));
+    assert_eq!(package["edition"].as_str(), Some("2021"));
+    assert_eq!(package["readme"].as_str(), Some("../../README.md"));
+
+    let serde = &member["dependencies"]["serde"];
+    assert_eq!(serde["version"].as_str(), Some("1"));
+    let features: Vec<&str> = serde["features"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+    assert_eq!(features, vec!["derive", "rc"]);
+    assert_eq!(serde["optional"].as_bool(), Some(true));
+    assert!(serde.get("workspace").is_none());
+    // paths are relative to the member
+    assert_eq!(member["dependencies"]["core"]["path"].as_str(), Some("../../crates/core"));
+    assert!(member["target"]["cfg(unix)"]["dev-dependencies"]["serde"].get("version").is_some());
+    assert_eq!(member["lints"]["rust"]["unsafe_code"].as_str(), Some("forbid"));
+
+    let mut member = manifest("[package]\nname = \"app\"\nlicense.workspace = true\n");
+    assert!(resolve_workspace_inheritance(&mut member, &workspace, "..").is_err());
+    let mut member = manifest("[dependencies]\nanyhow.workspace = true\n");
+    assert!(resolve_workspace_inheritance(&mut member, &workspace, "..").is_err());
+}
+
+#[test]
+fn test_build_cargo_toml_replaces_targets_and_adds_runtime_dependencies() {
+    let mut cargo_toml = br#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+default-run = "app"
+
+[dependencies]
+tokio = { version = "1.38", features = ["macros", "fs"] }
+
+[[bin]]
+name = "app"
+path = "src/main.rs"
+
+[[example]]
+name = "demo"
+"#.to_vec();
+    let targets = vec![MountTarget {
+        package: "app".to_string(),
+        kind: "bin".to_string(),
+        crate_name: "app".to_string(),
+        root: "src/main.rs".to_string(),
+        source: ".minimodal/targets/bin_app.rs".to_string(),
+        entry: "src/__minimodal_bin_app.rs".to_string(),
+        entry_bin: "__minimodal_bin_app".to_string(),
+        functions: Vec::new(),
+    }];
+    build_cargo_toml(&mut cargo_toml, &targets, "", &[]).unwrap();
+
+    let manifest: toml::Value = toml::from_str(std::str::from_utf8(&cargo_toml).unwrap()).unwrap();
+    let bins = manifest["bin"].as_array().unwrap();
+    assert_eq!(bins.len(), 1);
+    assert_eq!(bins[0]["name"].as_str(), Some("__minimodal_bin_app"));
+    assert!(manifest.get("example").is_none());
+    let package = &manifest["package"];
+    assert!(package.get("default-run").is_none());
+    for auto in ["autobins", "autoexamples", "autotests", "autobenches"] {
+        assert_eq!(package[auto].as_bool(), Some(false), "{}", auto);
+    }
+
+    // the version of the package is kept, the features the entrypoints need are added
+    let tokio = &manifest["dependencies"]["tokio"];
+    assert_eq!(tokio["version"].as_str(), Some("1.38"));
+    let features: Vec<&str> = tokio["features"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+    assert_eq!(features, vec!["macros", "fs", "rt"]);
+    assert!(manifest["dependencies"].get("serde_json").is_some());
+}
+
+#[test]
+fn test_build_cargo_toml_keeps_renamed_runtime_dependencies() {
+    let mut cargo_toml = br#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+rt = { package = "tokio", version = "1", optional = true }
+"#.to_vec();
+    assert_eq!(
+        renamed_runtime_dependencies(&manifest(std::str::from_utf8(&cargo_toml).unwrap())),
+        vec![("rt".to_string(), "tokio".to_string())]
+    );
+    build_cargo_toml(&mut cargo_toml, &[], "", &[]).unwrap();
+
+    // cargo rejects a crate depended on under two names
+    let manifest: toml::Value = toml::from_str(std::str::from_utf8(&cargo_toml).unwrap()).unwrap();
+    let dependencies = &manifest["dependencies"];
+    assert!(dependencies.get("tokio").is_none());
+    assert!(dependencies["rt"].get("optional").is_none());
+    let features: Vec<&str> = dependencies["rt"]["features"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+    assert_eq!(features, vec!["rt", "macros"]);
+}
+
+#[test]
+fn test_build_cargo_toml_makes_optional_runtime_dependencies_required() {
+    let mut cargo_toml = br#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+tokio = { version = "1", optional = true }
+serde_json = { version = "1", optional = true }
+
+[features]
+server = ["dep:tokio", "serde_json?/std"]
+"#.to_vec();
+    build_cargo_toml(&mut cargo_toml, &[], "", &[]).unwrap();
+
+    let manifest: toml::Value = toml::from_str(std::str::from_utf8(&cargo_toml).unwrap()).unwrap();
+    for name in ["tokio", "serde_json"] {
+        assert!(manifest["dependencies"][name].get("optional").is_none(), "{}", name);
+    }
+    // features can't refer to them as optional anymore, the implicit one stays
+    let features = &manifest["features"];
+    let server: Vec<&str> = features["server"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+    assert_eq!(server, vec!["serde_json/std"]);
+    assert!(features.get("tokio").is_none());
+    assert_eq!(features["serde_json"].as_array().map(Vec::len), Some(0));
+}
+
+#[test]
+fn test_path_dependencies_from_outside_are_mounted() {
+    let root = TempDir::new("manifest");
+    write(&root, "ws/Cargo.toml", "[workspace]\nmembers = [\"app\", \"core\"]\n");
+    write(&root, "ws/app/Cargo.toml", "");
+    write(&root, "ws/core/Cargo.toml", "");
+    write(&root, "libs/Cargo.toml", "[workspace]\nmembers = [\"helper\", \"util\"]\n\n[workspace.package]\nversion = \"0.9.0\"\n");
+    write(&root, "libs/helper/Cargo.toml", r#"
+[package]
+name = "helper"
+version.workspace = true
+workspace = ".."
+
+[dependencies]
+util = { path = "../util" }
+core = { path = "../../ws/core" }
+"#);
+    write(&root, "libs/util/Cargo.toml", "[package]\nname = \"util\"\nversion = \"0.1.0\"\n");
+
+    let mut path_dependencies = PathDependencies::new(&root.join("ws")).unwrap();
+    let mut app = manifest(r#"
+[dependencies]
+core = { path = "../core" }
+helper = { path = "../../libs/helper" }
+serde = "1"
+"#);
+    assert!(path_dependencies.rewrite(&mut app, &root.join("ws/app"), "app").unwrap());
+    assert_eq!(app["dependencies"]["core"]["path"].as_str(), Some("../core"));
+    let helper_dir = format!("{}/helper", PATH_DEPENDENCIES_DIR);
+    assert_eq!(app["dependencies"]["helper"]["path"].as_str(), Some(format!("../{}", helper_dir).as_str()));
+
+    let (source, mount_dir) = path_dependencies.next_dir().unwrap();
+    assert_eq!(source, root.join("libs/helper").canonicalize().unwrap());
+    assert_eq!(mount_dir, helper_dir);
+    assert!(path_dependencies.next_dir().is_none());
+
+    // rewriting the manifest of helper finds util, which is mounted next to it
+    let mut manifests = Vec::new();
+    while let Some((source, mount_dir)) = path_dependencies.next_manifest() {
+        let manifest_path = source.join("Cargo.toml");
+        let content = fs::read(&manifest_path).unwrap();
+        let mount_path = format!("{}/Cargo.toml", mount_dir);
+        let rewritten = rewrite_dependency_manifest(&content, &manifest_path, &mount_path, &mut path_dependencies).unwrap();
+        manifests.push((mount_dir, parse_manifest(&rewritten, &mount_path).unwrap()));
+    }
+    let (_, helper) = manifests.iter().find(|(mount_dir, _)| *mount_dir == helper_dir).unwrap();
+    assert_eq!(helper["package"]["version"].as_str(), Some("0.9.0"));
+    assert!(helper["package"].get("workspace").is_none());
+    assert_eq!(helper["dependencies"]["util"]["path"].as_str(), Some("../util"));
+    assert_eq!(helper["dependencies"]["core"]["path"].as_str(), Some("../../../core"));
+    assert_eq!(path_dependencies.next_dir().map(|(_, mount_dir)| mount_dir), Some(format!("{}/util", PATH_DEPENDENCIES_DIR)));
+}
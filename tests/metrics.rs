@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+use minimodal_rs::server::jobs::{Jobs, JobStatus};
+use minimodal_rs::server::metrics::{self, Metrics};
+
+#[test]
+fn test_render_counters_and_histograms() {
+    let metrics = Metrics::new();
+    metrics.increment(&metrics::JOBS_FINISHED, &[("function", "app::train"), ("status", "succeeded")]);
+    metrics.increment(&metrics::JOBS_FINISHED, &[("function", "app::train"), ("status", "succeeded")]);
+    metrics.increment(&metrics::JOBS_FINISHED, &[("function", "say \"hi\""), ("status", "failed")]);
+    metrics.observe(&metrics::RUN_DURATION, &[("function", "app::train")], 0.3);
+    metrics.observe(&metrics::RUN_DURATION, &[("function", "app::train")], 7.0);
+
+    let text = metrics.render(&[("minimodal_jobs_queued", "Queued jobs", 2.0)]);
+    assert!(text.contains("# TYPE minimodal_jobs_queued gauge\nminimodal_jobs_queued 2\n"));
+    assert!(text.contains("# TYPE minimodal_jobs_finished_total counter\n"));
+    assert!(text.contains("minimodal_jobs_finished_total{function=\"app::train\",status=\"succeeded\"} 2\n"));
+    assert!(text.contains("minimodal_jobs_finished_total{function=\"say \\\"hi\\\"\",status=\"failed\"} 1\n"));
+
+    // the buckets are cumulative
+    assert!(text.contains("# TYPE minimodal_run_duration_seconds histogram\n"));
+    assert!(text.contains("minimodal_run_duration_seconds_bucket{function=\"app::train\",le=\"0.1\"} 0\n"));
+    assert!(text.contains("minimodal_run_duration_seconds_bucket{function=\"app::train\",le=\"0.5\"} 1\n"));
+    assert!(text.contains("minimodal_run_duration_seconds_bucket{function=\"app::train\",le=\"10\"} 2\n"));
+    assert!(text.contains("minimodal_run_duration_seconds_bucket{function=\"app::train\",le=\"+Inf\"} 2\n"));
+    assert!(text.contains("minimodal_run_duration_seconds_sum{function=\"app::train\"} 7.3\n"));
+    assert!(text.contains("minimodal_run_duration_seconds_count{function=\"app::train\"} 2\n"));
+    // metrics without samples are still described
+    assert!(text.contains("# TYPE minimodal_mount_bytes histogram\n"));
+    assert!(!text.contains("minimodal_mount_bytes_count"));
+}
+
+#[tokio::test]
+async fn test_scrape_counts_queued_and_active_jobs() {
+    let jobs = Arc::new(Jobs::new());
+    let (tx, _rx) = mpsc::channel(10);
+    jobs.start("app::a".to_string(), String::new(), String::new(), tx.clone());
+    let building = jobs.start("app::b".to_string(), String::new(), String::new(), tx.clone());
+    jobs.transition(&building, JobStatus::Building);
+    let finished = jobs.start("app::c".to_string(), String::new(), String::new(), tx);
+    jobs.finish(&finished, true);
+
+    let response = metrics::router(Arc::new(Metrics::new()), jobs)
+        .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("\nminimodal_jobs_queued 1\n"));
+    assert!(text.contains("\nminimodal_jobs_active 1\n"));
+}
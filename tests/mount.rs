@@ -0,0 +1,135 @@
+use minimodal_rs::mount::{
+    get_project_structure,
+    plan_mount_in,
+    build_cargo_toml,
+    find_target,
+    MountTarget,
+    MOUNT_INDEX_PATH
+};
+use minimodal_rs::mount_rules::MountRules;
+
+#[path = "test_utils.rs"]
+mod test_utils;
+use test_utils::{write, TempDir};
+
+fn target(kind: &str, crate_name: &str, entry: &str) -> MountTarget {
+    MountTarget {
+        package: "app".to_string(),
+        kind: kind.to_string(),
+        crate_name: crate_name.to_string(),
+        root: "".to_string(),
+        source: format!(".minimodal/targets/{}_{}.rs", kind, crate_name),
+        entry: entry.to_string(),
+        entry_bin: format!("__minimodal_{}_{}", kind, crate_name),
+        functions: Vec::new(),
+    }
+}
+
+#[test]
+fn test_project_structure_indexes_all_targets() {
+    let hashmap = get_project_structure(&MountRules::default(), None).unwrap();
+
+    let targets: Vec<MountTarget> = serde_json::from_slice(&hashmap[MOUNT_INDEX_PATH]).unwrap();
+    let lib = find_target(&targets, "minimodal_rs", "minimodal_rs", "").unwrap();
+    assert_eq!(lib.root, "src/lib.rs");
+    assert_eq!(lib.entry, "src/__minimodal_lib_minimodal_rs.rs");
+
+    let server = find_target(&targets, "minimodal_rs", "minimodal_server", "minimodal-server").unwrap();
+    assert_eq!(server.entry, "src/server/__minimodal_bin_minimodal_server.rs");
+
+    for target in targets.iter() {
+        assert!(hashmap.contains_key(&target.source), "missing source of {:?}", target);
+        assert!(hashmap.contains_key(&target.entry), "missing entrypoint of {:?}", target);
+    }
+
+    let manifest = String::from_utf8(hashmap["Cargo.toml"].clone()).unwrap();
+    assert!(manifest.contains("__minimodal_lib_minimodal_rs"));
+}
+
+#[test]
+fn test_build_cargo_toml_adds_entrypoints() {
+    let mut cargo_toml = br#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+serde = "1"
+
+[dev-dependencies]
+rstest = "0.22"
+app-testing = { path = "../testing" }
+"#.to_vec();
+    let original = cargo_toml.clone();
+
+    let targets = vec![
+        target("lib", "app", "crates/app/src/__minimodal_lib_app.rs"),
+        target("test", "integration", "crates/app/tests/__minimodal_test_integration.rs"),
+    ];
+    build_cargo_toml(&mut cargo_toml, &targets, "crates/app", &["app-testing".to_string()]).unwrap();
+
+    let manifest: toml::Value = toml::from_str(std::str::from_utf8(&cargo_toml).unwrap()).unwrap();
+    let bins = manifest["bin"].as_array().unwrap();
+    assert_eq!(bins.len(), 2);
+    assert_eq!(bins[0]["name"].as_str(), Some("__minimodal_lib_app"));
+    assert_eq!(bins[0]["path"].as_str(), Some("src/__minimodal_lib_app.rs"));
+    assert_eq!(bins[1]["path"].as_str(), Some("tests/__minimodal_test_integration.rs"));
+    // tests and examples use dev-dependencies, but not those depending on the package
+    assert!(manifest["dependencies"].get("rstest").is_some());
+    assert!(manifest["dependencies"].get("app-testing").is_none());
+
+    // a library alone doesn't need them
+    let mut cargo_toml = original;
+    build_cargo_toml(&mut cargo_toml, &targets[..1], "crates/app", &[]).unwrap();
+    let manifest: toml::Value = toml::from_str(std::str::from_utf8(&cargo_toml).unwrap()).unwrap();
+    assert!(manifest["dependencies"].get("rstest").is_none());
+}
+
+#[test]
+fn test_find_target_prefers_bin_for_binaries() {
+    let targets = vec![
+        target("lib", "app", "src/__minimodal_lib_app.rs"),
+        target("bin", "app", "src/__minimodal_bin_app.rs"),
+    ];
+    assert_eq!(find_target(&targets, "app", "app", "").unwrap().kind, "lib");
+    assert_eq!(find_target(&targets, "app", "app", "app").unwrap().kind, "bin");
+    assert!(find_target(&targets, "app", "other", "").is_err());
+}
+
+#[test]
+fn test_modules_leading_to_remote_functions_are_exposed() {
+    let root = TempDir::new("nested");
+    write(&root, "Cargo.toml", "[package]\nname = \"nested\"\nversion = \"0.1.0\"\nedition = \"2021\"\n");
+    write(&root, "src/lib.rs", "mod jobs;\nmod utils;\n");
+    write(&root, "src/jobs.rs", "mod train;\n");
+    write(&root, "src/jobs/train.rs", "#[function]\npub fn fit(epochs: i32) -> Result<i32, MiniModalError> {\n    Ok(epochs)\n}\n");
+    write(&root, "src/utils.rs", "mod helpers;\n");
+    write(&root, "src/utils/helpers.rs", "");
+
+    let plan = plan_mount_in(&root, &MountRules::default(), None).unwrap();
+    let jobs = String::from_utf8(plan.files["src/jobs.rs"].clone()).unwrap();
+    assert!(jobs.contains("pub(crate) mod train;"), "{}", jobs);
+    let train = String::from_utf8(plan.files["src/jobs/train.rs"].clone()).unwrap();
+    assert!(!train.contains("#[function]"), "{}", train);
+    let targets: Vec<MountTarget> = serde_json::from_slice(&plan.files[MOUNT_INDEX_PATH]).unwrap();
+    assert_eq!(targets[0].functions[0].path(), "jobs::train::fit");
+    // modules leading to no remote function are left as they are
+    assert_eq!(plan.files["src/utils.rs"], b"mod helpers;\n");
+}
+
+#[test]
+fn test_renamed_runtime_dependencies_are_aliased() {
+    let root = TempDir::new("renamed");
+    write(&root, "Cargo.toml", "[package]\nname = \"renamed\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\nrt = { package = \"tokio\", path = \"tokio\" }\n");
+    write(&root, "src/lib.rs", "#[function]\npub fn fit(epochs: i32) -> Result<i32, MiniModalError> {\n    Ok(epochs)\n}\n");
+    write(&root, "tokio/Cargo.toml", "[package]\nname = \"tokio\"\nversion = \"1.0.0\"\nedition = \"2021\"\n");
+    write(&root, "tokio/src/lib.rs", "");
+
+    let plan = plan_mount_in(&root, &MountRules::default(), None).unwrap();
+    let targets: Vec<MountTarget> = serde_json::from_slice(&plan.files[MOUNT_INDEX_PATH]).unwrap();
+    let source = String::from_utf8(plan.files[&targets[0].source].clone()).unwrap();
+    assert!(source.ends_with("\nextern crate rt as tokio;\n"), "{}", source);
+    let manifest: toml::Value = toml::from_str(std::str::from_utf8(&plan.files["Cargo.toml"]).unwrap()).unwrap();
+    assert!(manifest["dependencies"].get("tokio").is_none());
+}
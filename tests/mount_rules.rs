@@ -0,0 +1,85 @@
+use std::fs;
+use minimodal_rs::mount_rules::{select_files, MountRules, Reason, SelectedFile, IGNORE_FILENAME};
+
+#[path = "test_utils.rs"]
+mod test_utils;
+use test_utils::{write, TempDir};
+
+fn reason<'a>(files: &'a [SelectedFile], path: &str) -> Option<&'a Reason> {
+    files.iter().find(|file| file.path == path).map(|file| &file.reason)
+}
+
+#[test]
+fn test_select_files() {
+    let root = TempDir::new("mount-rules");
+    write(&root, "Cargo.toml", [b'x'; 10]);
+    write(&root, "src/lib.rs", [b'x'; 10]);
+    write(&root, "notebooks/scratch.rs", [b'x'; 10]);
+    write(&root, "data/train.csv", [b'x'; 10]);
+    write(&root, "data/model.bin", [b'x'; 100]);
+    write(&root, "data/nested/test.csv", [b'x'; 10]);
+    write(&root, "logs/run.log", [b'x'; 10]);
+    write(&root, ".git/HEAD", [b'x'; 10]);
+    write(&root, "target/CACHEDIR.TAG", [b'x'; 10]);
+    write(&root, "target/debug/app", [b'x'; 10]);
+    fs::write(root.join(IGNORE_FILENAME), "data/\n*.log\n").unwrap();
+
+    let files = select_files(&root, &MountRules::default()).unwrap();
+    let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+    assert_eq!(paths, vec![IGNORE_FILENAME, "Cargo.toml", "notebooks/scratch.rs", "src/lib.rs"]);
+    assert!(files.iter().all(|file| file.reason == Reason::NotIgnored));
+
+    let rules = MountRules {
+        exclude: vec!["notebooks".to_string()],
+        include: vec!["data/*.csv".to_string(), "data/*.bin".to_string()],
+        max_file_bytes: Some(50),
+    };
+    let files = select_files(&root, &rules).unwrap();
+    assert_eq!(reason(&files, "src/lib.rs"), Some(&Reason::NotIgnored));
+    assert_eq!(reason(&files, "notebooks/scratch.rs"), Some(&Reason::Excluded("notebooks".to_string())));
+    assert_eq!(reason(&files, "data/train.csv"), Some(&Reason::Included("data/*.csv".to_string())));
+    // included despite its size, `*` doesn't cross directories
+    assert_eq!(reason(&files, "data/model.bin"), Some(&Reason::Included("data/*.bin".to_string())));
+    assert_eq!(reason(&files, "data/nested/test.csv"), None);
+    assert_eq!(reason(&files, "logs/run.log"), None);
+    assert_eq!(reason(&files, "target/debug/app"), None);
+
+    let rules = MountRules { max_file_bytes: Some(5), ..MountRules::default() };
+    let files = select_files(&root, &rules).unwrap();
+    assert_eq!(reason(&files, "src/lib.rs"), Some(&Reason::TooLarge(5)));
+    assert!(!Reason::TooLarge(5).is_mounted());
+}
+
+#[test]
+fn test_rules_from_metadata() {
+    let workspace = serde_json::json!({ "minimodal": { "exclude": ["docs"], "max-file-size": 100 } });
+    let package = serde_json::json!({ "minimodal": { "include": ["data/*.csv"], "max-file-size": 200 } });
+    let rules = MountRules { exclude: vec!["notebooks".to_string()], ..MountRules::default() }
+        .with_metadata(&[&workspace, &package, &serde_json::Value::Null])
+        .unwrap();
+    assert_eq!(rules.exclude, vec!["notebooks", "docs"]);
+    assert_eq!(rules.include, vec!["data/*.csv"]);
+    assert_eq!(rules.max_file_bytes, Some(200));
+
+    // the command line wins
+    let rules = MountRules { max_file_bytes: Some(1), ..MountRules::default() }.with_metadata(&[&package]).unwrap();
+    assert_eq!(rules.max_file_bytes, Some(1));
+
+    let invalid = serde_json::json!({ "minimodal": { "includes": ["x"] } });
+    assert!(MountRules::default().with_metadata(&[&invalid]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinks_leading_outside_are_skipped() {
+    let root = TempDir::new("mount-symlinks");
+    write(&root, "scripts/build.sh", [b'x'; 10]);
+    std::os::unix::fs::symlink("scripts/build.sh", root.join("build")).unwrap();
+    std::os::unix::fs::symlink("../../etc", root.join("scripts/etc")).unwrap();
+    std::os::unix::fs::symlink("/etc/passwd", root.join("passwd")).unwrap();
+
+    let files = select_files(&root, &MountRules::default()).unwrap();
+    assert_eq!(reason(&files, "build"), Some(&Reason::NotIgnored));
+    assert_eq!(reason(&files, "scripts/etc"), Some(&Reason::LinksOutside));
+    assert_eq!(reason(&files, "passwd"), Some(&Reason::LinksOutside));
+}
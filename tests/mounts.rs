@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tonic::Code;
+use basemodules::session::Session;
+use minimodal_proto::proto::minimodal::{FileEntry, MountProjectRequest, NameAndType, RunFunctionRequest};
+use minimodal_rs::mount::{archive_chunks, build_archive, MountPlan};
+use minimodal_rs::server::mounts::{check_files, read_archive, validate_path, write_entries, write_files, MountQuotas};
+use minimodal_rs::server::server::{serve, ServeOptions};
+
+#[path = "test_utils.rs"]
+mod test_utils;
+use test_utils::TempDir;
+
+fn file(path: &str, bytes: usize) -> FileEntry {
+    FileEntry { file_path: path.to_string(), content: vec![b'x'; bytes] }
+}
+
+#[test]
+fn test_validate_path() {
+    assert_eq!(validate_path("src/lib.rs").unwrap(), PathBuf::from("src/lib.rs"));
+    assert_eq!(validate_path("./Cargo.toml").unwrap(), PathBuf::from("Cargo.toml"));
+    for path in ["../../etc/passwd", "src/../../x", "/etc/passwd", "", ".", "src\\..\\x", "a\0b"] {
+        let status = validate_path(path).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument, "{:?}", path);
+    }
+}
+
+#[test]
+fn test_quotas() {
+    let quotas = MountQuotas { max_files: 2, max_file_bytes: 10, max_total_bytes: 15 };
+    assert!(check_files(&[file("a", 10), file("b", 5)], &quotas).is_ok());
+
+    let status = check_files(&[file("a", 1), file("b", 1), file("c", 1)], &quotas).unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("3 files"), "{}", status.message());
+    let status = check_files(&[file("a", 11)], &quotas).unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("per file"), "{}", status.message());
+    let status = check_files(&[file("a", 10), file("b", 6)], &quotas).unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("16 bytes"), "{}", status.message());
+    assert_eq!(check_files(&[file("../a", 1)], &quotas).unwrap_err().code(), Code::InvalidArgument);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinks_do_not_lead_out_of_the_mount() {
+    let outside = TempDir::new("outside");
+    let mount = TempDir::new("mount");
+    std::os::unix::fs::symlink(&outside, mount.join("escape")).unwrap();
+    std::os::unix::fs::symlink(outside.join("missing"), mount.join("dangling")).unwrap();
+    fs::create_dir_all(mount.join("inside")).unwrap();
+    std::os::unix::fs::symlink(mount.join("inside"), mount.join("alias")).unwrap();
+
+    let status = write_files(&mount, vec![file("escape/file", 1)]).unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let status = write_files(&mount, vec![file("dangling", 1)]).unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(fs::read_dir(&outside).unwrap().next().is_none());
+
+    // a symlink staying inside is followed
+    write_files(&mount, vec![file("alias/file", 1), file("src/lib.rs", 2)]).unwrap();
+    assert_eq!(fs::read(mount.join("inside/file")).unwrap(), b"x");
+    assert_eq!(fs::read(mount.join("src/lib.rs")).unwrap(), b"xx");
+}
+
+fn plan(files: &[(&str, u32, &[u8])], symlinks: &[(&str, &str)]) -> MountPlan {
+    MountPlan {
+        files: files.iter().map(|(path, _, content)| (path.to_string(), content.to_vec())).collect(),
+        symlinks: symlinks.iter().map(|(path, target)| (path.to_string(), target.to_string())).collect(),
+        modes: files.iter().map(|(path, mode, _)| (path.to_string(), *mode)).collect(),
+        reasons: HashMap::new(),
+        skipped: Vec::new(),
+    }
+}
+
+/// an archive with a single entry of the given type and raw path, which `tar::Builder` refuses to write
+fn raw_archive(path: &str, entry_type: tar::EntryType, link: Option<&str>) -> Vec<u8> {
+    let mut header = tar::Header::new_gnu();
+    header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+    header.set_entry_type(entry_type);
+    header.set_size(0);
+    header.set_mode(0o644);
+    if let Some(link) = link {
+        header.set_link_name(link).unwrap();
+    }
+    header.set_cksum();
+    let mut builder = tar::Builder::new(zstd::Encoder::new(Vec::new(), 0).unwrap());
+    builder.append(&header, std::io::empty()).unwrap();
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_archive_keeps_modes_and_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+    let archive = build_archive(&plan(
+        &[("build.sh", 0o755, b"#!/bin/sh"), ("src/lib.rs", 0o644, b"")],
+        &[("scripts/build", "../build.sh"), ("src/alias.rs", "lib.rs")],
+    )).unwrap();
+    let chunks = archive_chunks("prod", &archive);
+    assert_eq!(chunks[0].app, "prod");
+
+    let entries = read_archive(&archive, &MountQuotas::default()).unwrap();
+    assert_eq!(entries.len(), 4);
+    let mount = TempDir::new("archive");
+    // left by a previous mount, replaced rather than written through
+    fs::create_dir_all(mount.join("src")).unwrap();
+    std::os::unix::fs::symlink("/tmp", mount.join("src/alias.rs")).unwrap();
+    write_entries(&mount, entries).unwrap();
+
+    let mode = fs::metadata(mount.join("build.sh")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o755);
+    assert_eq!(fs::read_link(mount.join("scripts/build")).unwrap(), PathBuf::from("../build.sh"));
+    assert_eq!(fs::read(mount.join("scripts/build")).unwrap(), b"#!/bin/sh");
+    assert_eq!(fs::read_link(mount.join("src/alias.rs")).unwrap(), PathBuf::from("lib.rs"));
+
+    // each symlink stays inside, but the second leads through the first out of the mount
+    let entries = read_archive(&build_archive(&plan(&[], &[("here", "."), ("up", "here/..")])).unwrap(), &MountQuotas::default()).unwrap();
+    let status = write_entries(&mount, entries).unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(fs::symlink_metadata(mount.join("up")).is_err());
+    // the same through a target which does not exist, created wherever it leads by the next write
+    let entries = read_archive(&build_archive(&plan(&[], &[("here", "."), ("up", "here/../missing")])).unwrap(), &MountQuotas::default()).unwrap();
+    let status = write_entries(&mount, entries).unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(fs::symlink_metadata(mount.join("up")).is_err());
+    // nothing of a rejected archive is left, e.g. `e` leads out through `d/l`
+    let entries = read_archive(&build_archive(&plan(
+        &[("d/new.rs", 0o644, b"")],
+        &[("d/l", ".."), ("e", "d/l/..")],
+    )).unwrap(), &MountQuotas::default()).unwrap();
+    let status = write_entries(&mount, entries).unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    for left in ["d/l", "e", "d/new.rs"] {
+        assert!(fs::symlink_metadata(mount.join(left)).is_err(), "{}", left);
+    }
+}
+
+#[test]
+fn test_archive_rejects_unsafe_entries() {
+    let quotas = MountQuotas::default();
+    for archive in [
+        raw_archive("../escaped.rs", tar::EntryType::Regular, None),
+        raw_archive("/etc/passwd", tar::EntryType::Regular, None),
+        raw_archive("link", tar::EntryType::Symlink, Some("/etc/passwd")),
+        raw_archive("src/link", tar::EntryType::Symlink, Some("../../missing")),
+        raw_archive("hard", tar::EntryType::Link, Some("src/lib.rs")),
+        raw_archive("device", tar::EntryType::Char, None),
+        b"not an archive".to_vec(),
+    ] {
+        assert_eq!(read_archive(&archive, &quotas).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    let quotas = MountQuotas { max_files: 2, max_file_bytes: 10, max_total_bytes: 15 };
+    let archive = build_archive(&plan(&[("a", 0o644, &[0; 11])], &[])).unwrap();
+    assert_eq!(read_archive(&archive, &quotas).unwrap_err().code(), Code::ResourceExhausted);
+    let archive = build_archive(&plan(&[("a", 0o644, &[0; 10]), ("b", 0o644, &[0; 6])], &[])).unwrap();
+    assert_eq!(read_archive(&archive, &quotas).unwrap_err().code(), Code::ResourceExhausted);
+    let archive = build_archive(&plan(&[("a", 0o644, b""), ("b", 0o644, b"")], &[("c", "a")])).unwrap();
+    assert_eq!(read_archive(&archive, &quotas).unwrap_err().code(), Code::ResourceExhausted);
+}
+
+#[tokio::test]
+async fn test_server_rejects_invalid_mounts() {
+    let dir = TempDir::new("mounts");
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let options = ServeOptions {
+        mount_quotas: MountQuotas { max_files: 10, max_file_bytes: 5 * 1024 * 1024, max_total_bytes: 6 * 1024 * 1024 },
+        ..ServeOptions::default()
+    };
+    let server = tokio::spawn(serve(
+        format!("127.0.0.1:{}", port).parse().unwrap(),
+        dir.join("shadow").to_string_lossy().to_string(),
+        dir.join("secrets").to_string_lossy().to_string(),
+        dir.join("deployments").to_string_lossy().to_string(),
+        options,
+    ));
+    let addr = format!("http://127.0.0.1:{}", port);
+    let mut client = None;
+    for _ in 0..50 {
+        if let Ok(connected) = Session::new(&addr, None).connect().await {
+            client = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut client = client.expect("the server did not start");
+
+    let status = client.mount_project(MountProjectRequest { files: vec![file("../escaped.rs", 1)] }).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(!dir.join("escaped.rs").exists());
+    let status = client.mount_project(MountProjectRequest { files: vec![file("/tmp/absolute.rs", 1)] }).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // larger than tonic's default limit of 4MB, but within the quotas
+    let files = vec![file("big.bin", 5 * 1024 * 1024)];
+    assert!(client.mount_project(MountProjectRequest { files }).await.is_ok());
+    let files = vec![file("a.bin", 4 * 1024 * 1024), file("b.bin", 4 * 1024 * 1024)];
+    let status = client.mount_project(MountProjectRequest { files }).await.unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+
+    // archives are streamed in chunks below the message limit
+    let archive = build_archive(&plan(&[("run.sh", 0o755, b"#!/bin/sh")], &[("run", "run.sh")])).unwrap();
+    let chunks = tokio_stream::iter(archive_chunks("", &archive));
+    assert!(client.mount_archive(chunks).await.is_ok());
+    assert_eq!(fs::read(dir.join("shadow/run")).unwrap(), b"#!/bin/sh");
+    let chunks = tokio_stream::iter(archive_chunks("", &raw_archive("../escaped.rs", tar::EntryType::Regular, None)));
+    assert_eq!(client.mount_archive(chunks).await.unwrap_err().code(), Code::InvalidArgument);
+
+    // the arguments of a call are pasted into the entrypoint built from the mount
+    for (name, borrow) in [("x); std::process::exit(1); (", ""), ("x", "&*"), ("x", "std::mem::drop(")] {
+        let field = NameAndType { name: name.to_string(), borrow: borrow.to_string(), ..Default::default() };
+        let run = RunFunctionRequest { function_id: "f".to_string(), field_types: vec![field], ..Default::default() };
+        assert_eq!(client.run_function(run).await.unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    server.abort();
+}
@@ -0,0 +1,51 @@
+use macros::function;
+use basemodules::MiniModalError;
+use basemodules::function::{Function, StreamingFunction, BatchFunction};
+use basemodules::registry::{functions, find_function};
+use std::pin::Pin;
+use std::future::Future;
+use futures::{StreamExt, Stream};
+
+#[function(secrets = ["db"], profile = "release")]
+async fn train(name: &str, (epochs, batch): (u32, u32)) -> Result<String, MiniModalError> {
+    Ok(format!("{} {} {}", name, epochs, batch))
+}
+
+mod jobs {
+    use super::*;
+
+    #[function]
+    pub fn score<T>(value: T) -> Result<T, MiniModalError>
+    where
+        T: serde::Serialize + for<'de> serde::Deserialize<'de> + Send + Sync + std::fmt::Debug + 'static,
+    {
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_functions_are_registered_with_metadata() {
+    let train = find_function("registry::train").expect("train is registered");
+    assert!(train.is_async);
+    assert!(!train.is_generic);
+    assert_eq!(train.package, "minimodal_rs");
+    assert_eq!(train.args.len(), 2);
+    assert_eq!(train.args[0].name, "name");
+    // types are stringified tokens
+    assert_eq!(train.args[0].ty.replace(' ', ""), "&str");
+    assert_eq!(train.args[0].wire_type.replace(' ', ""), "std::string::String");
+    assert_eq!(train.args[1].name, "arg1");
+    assert_eq!(train.options.secrets, &["db"]);
+    assert_eq!(train.options.profile, Some("release"));
+
+    let score = find_function("registry::jobs::score").expect("functions in modules are registered");
+    assert!(!score.is_async);
+    assert!(score.is_generic);
+}
+
+#[test]
+fn test_functions_lists_every_function() {
+    let paths: Vec<String> = functions().map(|function| function.path()).collect();
+    assert!(paths.contains(&"registry::train".to_string()));
+    assert!(paths.contains(&"registry::jobs::score".to_string()));
+}
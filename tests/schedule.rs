@@ -0,0 +1,98 @@
+use std::time::Duration;
+use chrono::{DateTime, TimeZone, Utc};
+use minimodal_rs::server::schedule::{Schedule, Cron, parse_period};
+use minimodal_rs::server::scheduler::{Scheduler, RunStatus};
+use minimodal_rs::server::jobs::JobStatus;
+
+fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+}
+
+#[test]
+fn test_parse_period() {
+    assert_eq!(parse_period("30s").unwrap(), Duration::from_secs(30));
+    assert_eq!(parse_period("15m").unwrap(), Duration::from_secs(15 * 60));
+    assert_eq!(parse_period("1h30m").unwrap(), Duration::from_secs(90 * 60));
+    assert_eq!(parse_period("1d").unwrap(), Duration::from_secs(24 * 60 * 60));
+    for invalid in ["", "15", "m", "0s", "1w", "-5m"] {
+        assert!(parse_period(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_cron_next_after() {
+    let nightly = Cron::parse("0 3 * * *").unwrap();
+    assert_eq!(nightly.next_after(at(2024, 1, 1, 2, 59)), Some(at(2024, 1, 1, 3, 0)));
+    assert_eq!(nightly.next_after(at(2024, 1, 1, 3, 0)), Some(at(2024, 1, 2, 3, 0)));
+    assert_eq!(nightly.next_after(at(2024, 12, 31, 4, 0)), Some(at(2025, 1, 1, 3, 0)));
+
+    let quarter = Cron::parse("*/15 9-17 * * 1-5").unwrap();
+    // 2024-01-06 is a saturday
+    assert_eq!(quarter.next_after(at(2024, 1, 5, 17, 45)), Some(at(2024, 1, 8, 9, 0)));
+    assert_eq!(quarter.next_after(at(2024, 1, 8, 9, 7)), Some(at(2024, 1, 8, 9, 15)));
+
+    // sunday is 0 or 7, either day field matches when both are restricted
+    let sundays = Cron::parse("0 0 * * 7").unwrap();
+    assert_eq!(sundays.next_after(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 7, 0, 0)));
+    let first_or_monday = Cron::parse("0 0 1 * 1").unwrap();
+    assert_eq!(first_or_monday.next_after(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 8, 0, 0)));
+    assert_eq!(first_or_monday.next_after(at(2024, 1, 29, 0, 0)), Some(at(2024, 2, 1, 0, 0)));
+
+    assert_eq!(Cron::parse("0 0 29 2 *").unwrap().next_after(at(2024, 3, 1, 0, 0)), Some(at(2028, 2, 29, 0, 0)));
+    assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(at(2024, 1, 1, 0, 0)), None);
+
+    for invalid in ["0 3 * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+        assert!(Cron::parse(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_schedule_parse() {
+    assert_eq!(Schedule::parse("", "").unwrap(), None);
+    assert_eq!(Schedule::parse("", "15m").unwrap(), Some(Schedule::Every(Duration::from_secs(15 * 60))));
+    assert!(matches!(Schedule::parse("0 3 * * *", "").unwrap(), Some(Schedule::Cron(_))));
+    assert!(Schedule::parse("0 3 * * *", "15m").is_err());
+}
+
+#[test]
+fn test_scheduler_runs_without_overlap() {
+    let scheduler = Scheduler::new();
+    let every_minute = Schedule::parse("", "1m").unwrap().unwrap();
+    let start = at(2024, 1, 1, 0, 0);
+
+    // first due a period after the function is seen
+    assert_eq!(scheduler.due("prod", "app::sync", &every_minute, start), None);
+    let scheduled_at = scheduler.due("prod", "app::sync", &every_minute, at(2024, 1, 1, 0, 1)).unwrap();
+    assert_eq!(scheduled_at, at(2024, 1, 1, 0, 1));
+    assert_eq!(scheduler.due("prod", "app::sync", &every_minute, at(2024, 1, 1, 0, 1)), None);
+
+    scheduler.start("prod", "app::sync", 2, "job-1", scheduled_at);
+    assert!(scheduler.is_running("prod", "app::sync"));
+    scheduler.skip("prod", "app::sync", 2, at(2024, 1, 1, 0, 2));
+    scheduler.finish("prod", "app::sync", "job-1", JobStatus::Succeeded, vec!["done".to_string()]);
+    assert!(!scheduler.is_running("prod", "app::sync"));
+
+    let runs = scheduler.runs("prod", "", 0);
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].status, RunStatus::Skipped);
+    assert_eq!(runs[1].status, RunStatus::Job(JobStatus::Succeeded));
+    assert_eq!(runs[1].logs, vec!["done"]);
+    assert!(runs[1].finished_at.is_some());
+    assert_eq!(scheduler.runs("", "app::sync", 1).len(), 1);
+    assert!(scheduler.runs("staging", "", 0).is_empty());
+
+    // a long run is still running once the history forgot it
+    scheduler.start("prod", "app::sync", 2, "job-2", at(2024, 1, 1, 0, 3));
+    for minute in 0..1000 {
+        scheduler.skip("prod", "app::other", 2, at(2024, 1, 2, 0, 0) + chrono::Duration::minutes(minute));
+    }
+    assert!(scheduler.runs("", "app::sync", 0).is_empty());
+    assert!(scheduler.is_running("prod", "app::sync"));
+    scheduler.finish("prod", "app::sync", "job-2", JobStatus::Succeeded, Vec::new());
+    assert!(!scheduler.is_running("prod", "app::sync"));
+
+    // a changed schedule starts over
+    let hourly = Schedule::parse("0 * * * *", "").unwrap().unwrap();
+    assert_eq!(scheduler.due("prod", "app::sync", &hourly, at(2024, 1, 1, 0, 30)), None);
+    assert_eq!(scheduler.due("prod", "app::sync", &hourly, at(2024, 1, 1, 1, 0)), Some(at(2024, 1, 1, 1, 0)));
+}
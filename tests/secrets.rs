@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use minimodal_rs::server::secrets::{SecretStore, redact};
+
+fn temp_secrets_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("minimodal_secrets_{}", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn test_secret_store_roundtrip() {
+    let secrets_dir = temp_secrets_dir();
+    let store = SecretStore::new(&secrets_dir).unwrap();
+
+    let values = HashMap::from([
+        ("DB_USER".to_string(), "admin".to_string()),
+        ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+    ]);
+    store.create("db", values.clone()).unwrap();
+
+    assert_eq!(store.get("db").unwrap(), values);
+    assert_eq!(
+        store.list().unwrap(),
+        vec![("db".to_string(), vec!["DB_PASSWORD".to_string(), "DB_USER".to_string()])]
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(secrets_dir.join("db.json")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    std::fs::remove_dir_all(secrets_dir).unwrap();
+}
+
+#[test]
+fn test_secret_store_rejects_invalid_names() {
+    let secrets_dir = temp_secrets_dir();
+    let store = SecretStore::new(&secrets_dir).unwrap();
+
+    assert!(store.create("../escape", HashMap::new()).is_err());
+    assert!(store.create("db", HashMap::from([("NOT VALID".to_string(), "x".to_string())])).is_err());
+    assert!(store.resolve(&["missing".to_string()]).is_err());
+
+    std::fs::remove_dir_all(secrets_dir).unwrap();
+}
+
+#[test]
+fn test_redact() {
+    let secret_values = vec!["hunter2".to_string(), "".to_string()];
+    assert_eq!(redact("password=hunter2", &secret_values), "password=*****");
+    assert_eq!(redact("nothing to hide", &secret_values), "nothing to hide");
+}
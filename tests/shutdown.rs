@@ -0,0 +1,71 @@
+use std::fs;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio_stream::StreamExt;
+use tonic::transport::Channel;
+use tonic_health::pb::{HealthCheckRequest, health_client::HealthClient};
+use tonic_health::ServingStatus;
+use tonic_reflection::pb::v1::{
+    ServerReflectionRequest,
+    server_reflection_client::ServerReflectionClient,
+    server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse,
+};
+use minimodal_rs::server::server::{serve_with_shutdown, ServeOptions};
+
+#[tokio::test]
+async fn test_health_reflection_and_graceful_shutdown() {
+    let dir = std::env::temp_dir().join(format!("minimodal-shutdown-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let options = ServeOptions {
+        shutdown_timeout: Duration::from_millis(100),
+        ..ServeOptions::default()
+    };
+    let server = tokio::spawn(serve_with_shutdown(
+        format!("127.0.0.1:{}", port).parse().unwrap(),
+        dir.join("shadow").to_string_lossy().to_string(),
+        dir.join("secrets").to_string_lossy().to_string(),
+        dir.join("deployments").to_string_lossy().to_string(),
+        options,
+        async { let _ = stopped.await; },
+    ));
+
+    let addr = format!("http://127.0.0.1:{}", port);
+    let mut channel = None;
+    for _ in 0..50 {
+        if let Ok(connected) = Channel::from_shared(addr.clone()).unwrap().connect().await {
+            channel = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let channel = channel.expect("the server did not start");
+
+    let mut health = HealthClient::new(channel.clone());
+    let request = HealthCheckRequest { service: "minimodal.MiniModal".to_string() };
+    let status = health.check(request.clone()).await.unwrap().into_inner().status;
+    assert_eq!(status, ServingStatus::Serving as i32);
+
+    let mut reflection = ServerReflectionClient::new(channel);
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = reflection.server_reflection_info(tokio_stream::once(request)).await.unwrap().into_inner();
+    let services = match responses.next().await.unwrap().unwrap().message_response {
+        Some(MessageResponse::ListServicesResponse(services)) => services.service,
+        response => panic!("expected the services, got {:?}", response),
+    };
+    let names: Vec<String> = services.into_iter().map(|service| service.name).collect();
+    assert!(names.contains(&"minimodal.MiniModal".to_string()), "{:?}", names);
+    assert!(names.contains(&"grpc.health.v1.Health".to_string()), "{:?}", names);
+    drop(responses);
+
+    stop.send(()).unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(10), server).await
+        .expect("the server did not shut down");
+    assert!(result.unwrap().is_ok());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -73,4 +73,21 @@ mod tests {
             assert_eq!(normalized_result, normalized_expected, "Case '{}' failed. Expected:\n{}\nGot:\n{}", name, wrapped_expected, result);
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod expose_tests {
+    use super::*;
+    use minimodal_rs::parse_file::expose_remote_functions;
+
+    #[test]
+    fn test_expose_remote_functions() {
+        let input = "mod jobs { mod train { #[function] fn remote() {} fn helper() {} pub fn public() {} } }";
+        let expected = "pub(crate) mod jobs { pub(crate) mod train { #[function] pub(crate) fn remote() {} fn helper() {} pub fn public() {} } }";
+
+        let mut ast = syn::parse_str::<syn::File>(input).unwrap();
+        expose_remote_functions(&mut ast, vec!["function".to_string()]);
+        let result = prettyplease::unparse(&ast);
+
+        assert_eq!(normalize_whitespace(&result), normalize_whitespace(expected));
+    }
+}
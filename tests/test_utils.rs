@@ -1,12 +1,22 @@
+// each test target uses only some of the helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::process::Child;
+use std::time::{Duration, Instant};
+use tonic::transport::Channel;
+use tonic_health::pb::{HealthCheckRequest, health_client::HealthClient};
+use tonic_health::ServingStatus;
 
 pub fn start_server(dirname: Option<&str>) -> Result<Child, Box<dyn std::error::Error>> {
     let mut base_path = "src/server".to_string();
     let mut commands = vec!["run", "--bin", "minimodal-server"];
     if let Some(dirname) = dirname {
         base_path.push_str(dirname);
-        commands.push("-dirname");
+        commands.extend(["--", "-dirname"]);
         commands.push(&base_path);
     }
     
@@ -17,4 +27,63 @@ pub fn start_server(dirname: Option<&str>) -> Result<Child, Box<dyn std::error::
         Ok(child) => Ok(child),
         Err(e) => Err(Box::new(e)),
     }
-}
\ No newline at end of file
+}
+
+/// waits until the health service of the server at `addr` reports
+/// the MiniModal service as serving, the server may still be compiling
+pub async fn wait_until_serving(addr: &str, timeout: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let request = HealthCheckRequest { service: "minimodal.MiniModal".to_string() };
+    loop {
+        if let Ok(channel) = Channel::from_shared(addr.to_string())?.connect().await {
+            if let Ok(response) = HealthClient::new(channel).check(request.clone()).await {
+                if response.into_inner().status == ServingStatus::Serving as i32 {
+                    return Ok(());
+                }
+            }
+        }
+        if started.elapsed() > timeout {
+            return Err(format!("the server at {} is not serving after {:?}", addr, timeout).into());
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// A directory of its own below the system temp dir, removed with its
+/// content when dropped, also when the test panics
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("minimodal-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// writes `content` to `path` below `root`, creating the directories on the way
+pub fn write(root: &Path, path: &str, content: impl AsRef<[u8]>) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
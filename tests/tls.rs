@@ -0,0 +1,75 @@
+use std::fs;
+use std::time::Duration;
+use basemodules::session::{ClientTls, Session};
+use minimodal_proto::proto::minimodal::ListJobsRequest;
+use minimodal_rs::server::server::{serve, ServeOptions};
+use minimodal_rs::server::tls::{generate_dev_ca, ServerTls};
+
+#[tokio::test]
+async fn test_mutual_tls() {
+    let dir = std::env::temp_dir().join(format!("minimodal-tls-{}", uuid::Uuid::new_v4()));
+    let certs = generate_dev_ca(&dir.join("certs"), &[]).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&certs.client_key).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let options = ServeOptions {
+        tls: Some(ServerTls {
+            cert: certs.server_cert.clone(),
+            key: certs.server_key.clone(),
+            client_ca: Some(certs.ca_cert.clone()),
+        }),
+        ..ServeOptions::default()
+    };
+    let server = tokio::spawn(serve(
+        format!("127.0.0.1:{}", port).parse().unwrap(),
+        dir.join("shadow").to_string_lossy().to_string(),
+        dir.join("secrets").to_string_lossy().to_string(),
+        dir.join("deployments").to_string_lossy().to_string(),
+        options,
+    ));
+
+    let addr = format!("https://localhost:{}", port);
+    let session = Session::new(&addr, None).with_tls(ClientTls {
+        ca_cert: Some(certs.ca_cert.clone()),
+        client_cert: Some(certs.client_cert.clone()),
+        client_key: Some(certs.client_key.clone()),
+    });
+    let mut client = None;
+    for _ in 0..50 {
+        if let Ok(connected) = session.connect().await {
+            client = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut client = client.expect("the server did not start");
+    assert!(client.list_jobs(ListJobsRequest {}).await.is_ok());
+
+    // the server requires a client certificate
+    let anonymous = Session::new(&addr, None).with_tls(ClientTls {
+        ca_cert: Some(certs.ca_cert.clone()),
+        ..ClientTls::default()
+    });
+    if let Ok(mut client) = anonymous.connect().await {
+        assert!(client.list_jobs(ListJobsRequest {}).await.is_err());
+    }
+    // and doesn't speak plain HTTP/2
+    if let Ok(mut client) = Session::new(format!("http://localhost:{}", port), None).connect().await {
+        assert!(client.list_jobs(ListJobsRequest {}).await.is_err());
+    }
+    // a certificate without its key is a mistake
+    let incomplete = Session::new(&addr, None).with_tls(ClientTls {
+        ca_cert: Some(certs.ca_cert.clone()),
+        client_cert: Some(certs.client_cert.clone()),
+        client_key: None,
+    });
+    assert!(incomplete.connect().await.is_err());
+
+    server.abort();
+    let _ = fs::remove_dir_all(&dir);
+}
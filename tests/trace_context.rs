@@ -0,0 +1,89 @@
+use basemodules::trace_context;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing::{Span, info_span};
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+/// runs `f` with a subscriber recording the spans with OpenTelemetry
+fn with_tracing<T>(f: impl FnOnce() -> T) -> T {
+    let tracer = TracerProvider::builder().build().tracer("test");
+    let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    tracing::subscriber::with_default(subscriber, f)
+}
+
+#[test]
+fn test_parse_and_format_traceparent() {
+    let span_context = trace_context::parse(TRACEPARENT).unwrap();
+    assert_eq!(span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+    assert!(span_context.is_sampled());
+    assert!(span_context.is_remote());
+    assert_eq!(trace_context::format(&span_context), TRACEPARENT);
+
+    for malformed in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902bz-01",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        // all zero ids are invalid
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+    ] {
+        assert!(trace_context::parse(malformed).is_none(), "{}", malformed);
+    }
+}
+
+#[test]
+fn test_span_continues_the_trace_of_its_parent() {
+    with_tracing(|| {
+        let span = info_span!("job");
+        trace_context::set_parent(&span, TRACEPARENT);
+        let traceparent = trace_context::traceparent(&span).unwrap();
+        let span_context = trace_context::parse(&traceparent).unwrap();
+        assert_eq!(span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+
+        // a child of the span is in the same trace
+        let child = span.in_scope(|| {
+            let run = info_span!("run");
+            trace_context::traceparent(&run).unwrap()
+        });
+        assert_eq!(trace_context::parse(&child).unwrap().trace_id(), span_context.trace_id());
+        assert_ne!(child, traceparent);
+    });
+}
+
+#[test]
+fn test_request_carries_the_current_span() {
+    with_tracing(|| {
+        let span = info_span!("remote");
+        let _entered = span.enter();
+        let mut request = tonic::Request::new(());
+        trace_context::inject(&mut request);
+
+        let traceparent = trace_context::extract(&request).unwrap();
+        assert_eq!(Some(traceparent), trace_context::traceparent(&Span::current()));
+    });
+}
+
+#[test]
+fn test_malformed_traceparent_is_not_extracted() {
+    let mut request = tonic::Request::new(());
+    request.metadata_mut().insert(trace_context::TRACEPARENT_HEADER, "not-a-traceparent".parse().unwrap());
+    assert_eq!(trace_context::extract(&request), None);
+}
+
+#[test]
+fn test_function_passes_on_the_traceparent_it_was_started_with() {
+    // without a subscriber the spans are not recorded,
+    // like in an entrypoint started by the server
+    assert_eq!(trace_context::traceparent(&info_span!("call")), None);
+    std::env::set_var(trace_context::TRACEPARENT_ENV, TRACEPARENT);
+    let mut request = tonic::Request::new(());
+    trace_context::inject(&mut request);
+    std::env::remove_var(trace_context::TRACEPARENT_ENV);
+    assert_eq!(trace_context::extract(&request).as_deref(), Some(TRACEPARENT));
+}
@@ -0,0 +1,44 @@
+use minimodal_rs::server::type_names::{resolve_const_value, resolve_type_name};
+use rstest::*;
+
+fn normalize_whitespace(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[rstest]
+#[case::primitive("i32", "i32")]
+#[case::alloc("alloc::vec::Vec<alloc::string::String>", "::std::vec::Vec<::std::string::String>")]
+#[case::core("core::option::Option<u8>", "::std::option::Option<u8>")]
+#[case::private_std_module(
+    "std::collections::hash::map::HashMap<alloc::string::String, i32>", 
+    "::std::collections::hash_map::HashMap<::std::string::String, i32>"
+)]
+#[case::btree("alloc::collections::btree::map::BTreeMap<u8, u8>", "::std::collections::btree_map::BTreeMap<u8, u8>")]
+#[case::mounted_crate("my_app::jobs::Stats", "crate::jobs::Stats")]
+#[case::nested_mounted_crate("(my_app::A, [my_app::B; 2])", "(crate::A, [crate::B; 2])")]
+#[case::other_crate("polars_core::frame::DataFrame", "polars_core::frame::DataFrame")]
+#[case::reference("&str", "&str")]
+fn test_resolve_type_name(#[case] type_name: &str, #[case] expected: &str) {
+    let resolved = resolve_type_name(type_name, "my_app").unwrap();
+    assert_eq!(normalize_whitespace(&resolved), normalize_whitespace(expected));
+}
+
+#[test]
+fn test_resolve_type_name_rejects_unnameable_types() {
+    assert!(resolve_type_name("my_app::main::{{closure}}", "my_app").is_err());
+}
+
+#[rstest]
+#[case::integer("3", "3")]
+#[case::negative("-3", "- 3")]
+#[case::boolean("true", "true")]
+#[case::char("'x'", "'x'")]
+fn test_resolve_const_value(#[case] value: &str, #[case] expected: &str) {
+    assert_eq!(resolve_const_value(value).unwrap(), expected);
+}
+
+#[test]
+fn test_resolve_const_value_rejects_expressions() {
+    assert!(resolve_const_value("i32").is_err());
+    assert!(resolve_const_value("{ std::process::exit(1) }").is_err());
+}
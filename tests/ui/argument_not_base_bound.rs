@@ -0,0 +1,18 @@
+#![allow(non_camel_case_types, unused_imports)]
+use std::future::Future;
+use std::pin::Pin;
+use futures::{Stream, StreamExt};
+use basemodules::MiniModalError;
+use basemodules::function::{BatchFunction, Function, StreamingFunction};
+use macros::function;
+
+// everything but Sync
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Counter(std::cell::Cell<i32>);
+
+#[function]
+async fn count(counter: Counter, total: Counter) -> Result<i32, MiniModalError> {
+    Ok(counter.0.get() + total.0.get())
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+#![allow(non_camel_case_types, unused_imports)]
+use std::future::Future;
+use std::pin::Pin;
+use futures::{Stream, StreamExt};
+use basemodules::MiniModalError;
+use basemodules::function::{BatchFunction, Function, StreamingFunction};
+use macros::function;
+
+struct Model;
+
+impl Model {
+    #[function]
+    async fn predict(&self, a: i32) -> Result<i32, MiniModalError> {
+        Ok(a)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+#![allow(non_camel_case_types, unused_imports)]
+use std::future::Future;
+use std::pin::Pin;
+use futures::{Stream, StreamExt};
+use basemodules::MiniModalError;
+use basemodules::function::{BatchFunction, Function, StreamingFunction};
+use macros::function;
+
+#[function]
+async fn sum(values: impl Iterator<Item = i32>) -> Result<i32, MiniModalError> {
+    Ok(values.sum())
+}
+
+fn main() {}